@@ -1,15 +1,15 @@
-use crate::intcode::{Intcode, Response};
+use crate::intcode::Intcode;
 use crate::map::{Coord, Map};
 
+use std::cell::RefCell;
 use std::default;
 use std::fmt;
 
 pub fn part1(input: &str) -> Result<u64, String> {
-    let mut intcode: Intcode = input.parse()?;
+    let intcode: Intcode = input.parse()?;
     let mut ship = Ship::default();
 
     // Start on a black panel.
-    intcode.input.push(0);
     ship.run(intcode)?;
 
     println!("{}", ship.painted_panels);
@@ -18,11 +18,11 @@ pub fn part1(input: &str) -> Result<u64, String> {
 }
 
 pub fn part2(input: &str) -> Result<u64, String> {
-    let mut intcode: Intcode = input.parse()?;
+    let intcode: Intcode = input.parse()?;
     let mut ship = Ship::default();
 
     // Start on a white panel.
-    intcode.input.push(1);
+    ship.white_panels.points.insert(ship.robot.position);
     ship.run(intcode)?;
 
     println!("{}", ship.white_panels);
@@ -38,50 +38,64 @@ struct Ship {
 }
 
 impl Ship {
-    pub fn run(&mut self, mut intcode: Intcode) -> Result<(), &'static str> {
-        loop {
-            let result = intcode.run();
-
-            let direction = intcode.output.pop().ok_or("No direction output.")?;
-            let color = intcode.output.pop().ok_or("No color output.")?;
-
-            self.painted_panels.points.insert(self.robot.position);
-
-            match color {
-                0 => {
-                    self.white_panels.points.remove(&self.robot.position);
-                }
-                1 => {
-                    self.white_panels.points.insert(self.robot.position);
-                }
-                _ => return Err("Invalid color."),
-            }
+    pub fn run(&mut self, intcode: Intcode) -> Result<(), String> {
+        let ship = RefCell::new(self);
+        let mut color = None;
+        let mut error = None;
+
+        // The robot outputs a color to paint followed by a direction to turn, and reads the color
+        // of the panel it ends up on.
+        intcode
+            .with_io(
+                || Some(ship.borrow().panel_color()),
+                |value| match color.take() {
+                    None => color = Some(value),
+                    Some(color) => {
+                        if let Err(e) = ship.borrow_mut().paint(color, value) {
+                            error.get_or_insert(e);
+                        }
+                    }
+                },
+            )
+            .run()?;
+
+        error.map_or(Ok(()), Err)
+    }
+
+    fn panel_color(&self) -> i64 {
+        if self.white_panels.points.contains(&self.robot.position) {
+            1
+        } else {
+            0
+        }
+    }
 
-            match direction {
-                0 => {
-                    self.robot.facing.turn_left();
-                    self.robot.advance();
-                }
-                1 => {
-                    self.robot.facing.turn_right();
-                    self.robot.advance();
-                }
-                _ => return Err("Invalid direction."),
+    fn paint(&mut self, color: i64, direction: i64) -> Result<(), String> {
+        self.painted_panels.points.insert(self.robot.position);
+
+        match color {
+            0 => {
+                self.white_panels.points.remove(&self.robot.position);
+            }
+            1 => {
+                self.white_panels.points.insert(self.robot.position);
             }
+            _ => return Err("Invalid color.".to_string()),
+        }
 
-            match result {
-                Response::Terminated => return Ok(()),
-                Response::InputRequired => {
-                    intcode
-                        .input
-                        .push(if self.white_panels.points.contains(&self.robot.position) {
-                            1
-                        } else {
-                            0
-                        })
-                }
+        match direction {
+            0 => {
+                self.robot.facing.turn_left();
+                self.robot.advance();
             }
+            1 => {
+                self.robot.facing.turn_right();
+                self.robot.advance();
+            }
+            _ => return Err("Invalid direction.".to_string()),
         }
+
+        Ok(())
     }
 }
 
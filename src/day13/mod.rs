@@ -1,3 +1,4 @@
+use std::cell::RefCell;
 use std::cmp::{max, Ordering};
 use std::collections::HashMap;
 use std::convert::TryFrom;
@@ -5,12 +6,12 @@ use std::fmt;
 use std::thread;
 use std::time::Duration;
 
-use crate::intcode::{Intcode, Response};
+use crate::intcode::Intcode;
 use crate::map::{Coord, CoordDiff};
 
 pub fn part1(input: &str) -> Result<u64, String> {
     let mut intcode: Intcode = input.parse()?;
-    intcode.run();
+    intcode.run()?;
 
     let game = Game::try_from(&intcode.output.split_off(0)[..])?;
     println!("{}", game);
@@ -29,38 +30,43 @@ pub fn part2(input: &str) -> Result<u64, String> {
 fn part2_speed(input: &str, speed: Duration) -> Result<u64, String> {
     let mut intcode: Intcode = input.parse()?;
     intcode.set(0, 2);
-    intcode.run();
 
-    intcode.input.push(0);
+    let game = RefCell::new(Game::default());
+    let mut tile = Vec::with_capacity(3);
+    let mut error = None;
 
-    let mut game = Game::try_from(&intcode.output.split_off(0)[..])?;
-    println!("{}", game);
+    // Tiles arrive as triples of output. Whenever the game asks for the joystick position, show
+    // the screen and steer the paddle towards where the ball is heading.
+    intcode
+        .with_io(
+            || {
+                let game = game.borrow();
+                println!("{}", game);
 
-    while let Response::InputRequired = intcode.run() {
-        game.update(&intcode.output.split_off(0)[..])?;
-        println!("{}", game);
-
-        intcode.input.push(
-            if let (Some(paddle), Some(ball)) = (game.paddle, game.ball) {
-                match (ball + game.ball_direction.unwrap_or(CoordDiff::ZERO))
-                    .x
-                    .cmp(&paddle.x)
-                {
-                    Ordering::Less => -1,
-                    Ordering::Equal => 0,
-                    Ordering::Greater => 1,
+                if speed > Duration::from_nanos(0) {
+                    thread::sleep(speed);
                 }
-            } else {
-                0
+
+                Some(game.joystick())
             },
-        );
+            |value| {
+                tile.push(value);
 
-        if speed > Duration::from_nanos(0) {
-            thread::sleep(speed);
-        }
+                if tile.len() == 3 {
+                    if let Err(e) = game.borrow_mut().update(&tile) {
+                        error.get_or_insert(e);
+                    }
+                    tile.clear();
+                }
+            },
+        )
+        .run()?;
+
+    if let Some(error) = error {
+        return Err(error);
     }
 
-    game.update(&intcode.output.split_off(0)[..])?;
+    let game = game.into_inner();
     println!("{}", game);
 
     Ok(game.score as u64)
@@ -76,6 +82,21 @@ struct Game {
 }
 
 impl Game {
+    fn joystick(&self) -> i64 {
+        if let (Some(paddle), Some(ball)) = (self.paddle, self.ball) {
+            match (ball + self.ball_direction.unwrap_or(CoordDiff::ZERO))
+                .x
+                .cmp(&paddle.x)
+            {
+                Ordering::Less => -1,
+                Ordering::Equal => 0,
+                Ordering::Greater => 1,
+            }
+        } else {
+            0
+        }
+    }
+
     fn update(&mut self, input: &[i64]) -> Result<(), String> {
         for chunk in input.chunks_exact(3) {
             self.update_part([chunk[0], chunk[1], chunk[2]])?;
@@ -201,7 +222,8 @@ impl From<&Tile> for char {
 
 #[cfg(test)]
 mod test {
-    use super::{part1, part2_speed, Duration};
+    use super::{part1, part2_speed, Duration, Intcode};
+    use crate::intcode::session::Session;
 
     #[test]
     fn part1_solution() {
@@ -216,4 +238,13 @@ mod test {
             part2_speed(include_str!("input.txt"), Duration::from_nanos(0))
         );
     }
+
+    #[test]
+    fn replay_session() {
+        let mut intcode: Intcode = include_str!("input.txt").parse().unwrap();
+        intcode.set(0, 2);
+
+        let session = Session::load(include_str!("session.txt").as_bytes()).unwrap();
+        assert_eq!(Ok(()), session.replay(intcode));
+    }
 }
@@ -102,18 +102,18 @@ fn explore(intcode: Intcode) -> Result<(Maze, Coord), String> {
                 let location = coord + direction.into();
 
                 if !maze.contains_key(&location) {
-                    match move_droid(&mut droid, direction) {
+                    match move_droid(&mut droid, direction)? {
                         Some(0) => {
                             maze.insert(location, Tile::Wall);
                         }
                         Some(1) => {
                             maze.insert(location, Tile::Floor);
-                            move_droid(&mut droid, direction.reverse());
+                            move_droid(&mut droid, direction.reverse())?;
                             legal_moves.push(direction);
                         }
                         Some(2) => {
                             maze.insert(location, Tile::Floor);
-                            move_droid(&mut droid, direction.reverse());
+                            move_droid(&mut droid, direction.reverse())?;
                             legal_moves.push(direction);
 
                             oxygen_coord = Some(location);
@@ -127,12 +127,12 @@ fn explore(intcode: Intcode) -> Result<(Maze, Coord), String> {
             while legal_moves.len() > 1 {
                 let direction = legal_moves.pop().unwrap();
                 let mut droid_clone = droid.clone();
-                move_droid(&mut droid_clone, direction);
+                move_droid(&mut droid_clone, direction)?;
                 new_droids.insert(coord + direction.into(), droid_clone);
             }
 
             if let Some(direction) = legal_moves.pop() {
-                move_droid(&mut droid, direction);
+                move_droid(&mut droid, direction)?;
                 new_droids.insert(coord + direction.into(), droid);
             }
         }
@@ -148,22 +148,23 @@ fn explore(intcode: Intcode) -> Result<(Maze, Coord), String> {
     ))
 }
 
-fn move_droid(droid: &mut Intcode, direction: Direction) -> Option<i64> {
-    droid.input.push(match direction {
+fn move_droid(droid: &mut Intcode, direction: Direction) -> Result<Option<i64>, String> {
+    droid.input.push_back(match direction {
         Direction::North => 1,
         Direction::East => 4,
         Direction::South => 2,
         Direction::West => 3,
     });
 
-    droid.run();
+    droid.run()?;
 
-    droid.output.pop()
+    Ok(droid.output.pop())
 }
 
 #[cfg(test)]
 mod test {
-    use super::{part1, part2};
+    use super::{part1, part2, Intcode};
+    use crate::intcode::session::Session;
 
     #[test]
     fn part1_solution() {
@@ -174,4 +175,11 @@ mod test {
     fn part2_solution() {
         assert_eq!(Ok(382), part2(include_str!("input.txt")));
     }
+
+    #[test]
+    fn replay_session() {
+        let intcode: Intcode = include_str!("input.txt").parse().unwrap();
+        let session = Session::load(include_str!("session.txt").as_bytes()).unwrap();
+        assert_eq!(Ok(()), session.replay(intcode));
+    }
 }
@@ -1,8 +1,9 @@
+use crate::intcode::terminal::Terminal;
 use crate::intcode::Intcode;
 
 pub fn part1(input: &str) -> Result<u64, String> {
     let mut intcode: Intcode = input.parse()?;
-    intcode.run();
+    intcode.run()?;
 
     let map = intcode.output_string();
 
@@ -19,29 +20,20 @@ pub fn part2(input: &str) -> Result<u64, String> {
     let mut intcode: Intcode = input.parse()?;
     intcode.set(0, 2);
 
-    // Main movement routine
-    intcode.input_str(&"A,A,B,C,B,C,B,C,B,A\n");
-
-    // Function A
-    intcode.input_str(&"R,10,L,12,R,6\n");
-
-    // Function B
-    intcode.input_str(&"R,6,R,10,R,12,R,6\n");
-
-    // Function C
-    intcode.input_str(&"R,10,L,12,L,12\n");
-
-    // "Continuous video feed"
-    intcode.input_str(&"n\n");
-    intcode.run();
-
-    let result = intcode
-        .output
-        .pop()
-        .map(|i| i as u64)
-        .ok_or_else(|| "No output.".to_string());
-    println!("{}", intcode.output_string());
-    result
+    let screen = Terminal::new(intcode).script(&[
+        ("Main:", "A,A,B,C,B,C,B,C,B,A"),
+        ("Function A:", "R,10,L,12,R,6"),
+        ("Function B:", "R,6,R,10,R,12,R,6"),
+        ("Function C:", "R,10,L,12,L,12"),
+        ("Continuous video feed?", "n"),
+    ])?;
+
+    println!("{}", screen.text());
+    screen
+        .values
+        .last()
+        .map(|&i| i as u64)
+        .ok_or_else(|| "No output.".to_string())
 }
 
 fn get_intersections(map: &str) -> Vec<(u64, u64)> {
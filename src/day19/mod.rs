@@ -51,9 +51,9 @@ pub fn part2(input: &str) -> Result<u64, String> {
 pub fn is_hit(intcode: &Intcode, coord: impl Into<Coord>) -> Result<bool, String> {
     let coord = coord.into();
     let mut intcode = intcode.clone();
-    intcode.input.push(coord.x);
-    intcode.input.push(coord.y);
-    intcode.run();
+    intcode.input.push_back(coord.x);
+    intcode.input.push_back(coord.y);
+    intcode.run()?;
 
     match intcode.output.pop() {
         Some(0) => Ok(false),
@@ -1,4 +1,5 @@
-use crate::intcode::Intcode;
+use crate::intcode::symbolic::{Constraint, End, Explorer, Linear, Relation, Value};
+use crate::intcode::{Intcode, Response};
 
 pub fn part1(input: &str) -> Result<u64, String> {
     let mut intcode: Intcode = input.parse()?;
@@ -6,25 +7,26 @@ pub fn part1(input: &str) -> Result<u64, String> {
     intcode.set(1, 12);
     intcode.set(2, 2);
 
-    intcode.run();
+    intcode.run()?;
 
     Ok(intcode.get(0) as u64)
 }
 
+/// Rather than trying every noun and verb, run the program once with both left as variables, and
+/// solve for the ones that give the wanted result.
 pub fn part2(input: &str) -> Result<u64, String> {
-    let clean_intcode: Intcode = input.parse()?;
-
-    for noun in 0..100 {
-        for verb in 0..100 {
-            let mut intcode = clean_intcode.clone();
-
-            intcode.set(1, noun);
-            intcode.set(2, verb);
-
-            intcode.run();
-
-            if intcode.get(0) == 19690720 {
-                return Ok((100 * noun + verb) as u64);
+    let mut explorer = Explorer::new(input.parse()?);
+    let noun = explorer.set_symbolic(1, "noun", 0..100);
+    let verb = explorer.set_symbolic(2, "verb", 0..100);
+
+    for path in explorer.explore() {
+        if let (End::Stopped(Response::Terminated), Value::Linear(result)) =
+            (&path.end, path.get(0))
+        {
+            let goal = Constraint::new(result, Relation::Eq, Linear::constant(19690720));
+
+            if let Some(values) = explorer.solve(&path, &[goal]) {
+                return Ok((100 * values[noun] + values[verb]) as u64);
             }
         }
     }
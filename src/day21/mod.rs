@@ -1,10 +1,12 @@
-use super::intcode::Intcode;
+use super::intcode::terminal::Terminal;
 
-pub fn part1(input: &str) -> Result<u64, String> {
-    let mut intcode: Intcode = input.parse()?;
+use std::time::Duration;
+
+/// Springscript runs are abandoned after this long.
+const TIMEOUT: Duration = Duration::from_secs(10);
 
-    intcode.input_str(
-        "
+pub fn part1(input: &str) -> Result<u64, String> {
+    let script = "
 NOT A J
 NOT B T
 OR T J
@@ -13,22 +15,12 @@ OR T J
 AND D J
 WALK
 "
-        .trim_start(),
-    );
-
-    intcode.run();
-
-    println!("{}", intcode.output_string());
+    .trim();
 
-    match intcode.output.last() {
-        Some(&i) if i > 255 => Ok(i as u64),
-        _ => Err(format!("Failed after {} steps.", intcode.steps)),
-    }
+    run(input, script)
 }
 
 pub fn part2(input: &str) -> Result<u64, String> {
-    let mut intcode: Intcode = input.parse()?;
-
     // AB.D.FG.. -- no jump (prev: jump)
     // A.C.EF... -- no jump
     // .B.DE...I -- jump
@@ -41,8 +33,7 @@ pub fn part2(input: &str) -> Result<u64, String> {
     // Is there a reason the round after won't do? (B is a hole or F is a hole)
     // Or the round after that? (C is a hole or G is a hole)
 
-    intcode.input_str(
-        "
+    let script = "
 NOT A J
 NOT B T
 OR T J
@@ -57,16 +48,21 @@ NOT A T
 OR T J
 RUN
 "
-        .trim_start(),
-    );
+    .trim();
+
+    run(input, script)
+}
 
-    intcode.run();
+/// Run a springscript program, returning the hull damage the springdroid reports.
+fn run(input: &str, script: &str) -> Result<u64, String> {
+    let mut terminal = Terminal::new(input.parse()?).with_timeout(TIMEOUT);
+    let screen = terminal.script(&[("Input instructions:", script)])?;
 
-    println!("{}", intcode.output_string());
+    println!("{}", screen.text());
 
-    match intcode.output.last() {
-        Some(&i) if i > 255 => Ok(i as u64),
-        _ => Err(format!("Failed after {} steps.", intcode.steps)),
+    match screen.values.last() {
+        Some(&i) => Ok(i as u64),
+        None => Err(format!("Failed after {} steps.", terminal.intcode.steps)),
     }
 }
 
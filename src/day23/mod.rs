@@ -1,125 +1,99 @@
+use super::intcode::runtime::{Handle, Mode, Runtime};
+use super::intcode::scheduler::{Outcome, Policy, Route, Scheduler};
 use super::intcode::Intcode;
 
+const NETWORK: &str = "network";
+const NAT: i64 = 255;
+const COMPUTERS: i64 = 50;
+
+/// The computers run concurrently, so give each a short turn rather than letting one run ahead.
+const STEPS_PER_TURN: u64 = 1_000;
+
+/// Only the first packet to the NAT matters, so the computers take turns on this thread and stop as
+/// soon as it's sent.
 pub fn part1(input: &str) -> Result<u64, String> {
-    let (mut nodes, mut packets) = parse(input)?;
+    let intcode: Intcode = input.parse()?;
+    let mut network = Scheduler::new(Policy::RoundRobin(STEPS_PER_TURN));
+
+    let computers: Vec<usize> = (0..COMPUTERS)
+        .map(|i| {
+            let computer = network.add(format!("computer {}", i), intcode.clone());
+            network.send(computer, &[i]);
+            network.set_idle_input(computer, -1);
+            computer
+        })
+        .collect();
+    network.connect(NETWORK, &computers, Route::Addressed(3))?;
 
     loop {
-        for (i, node) in nodes.iter_mut().enumerate() {
-            node.run();
-
-            while !node.output.is_empty() {
-                let mut iter = node.output.drain(..3);
-                let address = iter
-                    .next()
-                    .ok_or_else(|| format!("Missing address on node {}", i))?
-                    as usize;
-
-                let packet = [
-                    iter.next()
-                        .ok_or_else(|| format!("Missing x value on node {}", i))?,
-                    iter.next()
-                        .ok_or_else(|| format!("Missing y value on node {}", i))?,
-                ];
-
-                println!("{} => {}: {:?}", i, address, packet);
-
-                if address == 255 {
-                    return Ok(packet[1] as u64);
-                } else {
-                    packets[address].push(packet);
-                }
-            }
+        if !network.round()? {
+            return Err("The network went idle without sending to the NAT.".to_string());
         }
 
-        nodes.iter_mut().enumerate().for_each(|(i, node)| {
-            if packets[i].is_empty() {
-                node.input.push(-1);
-            } else {
-                let [x, y] = packets[i].remove(0);
-                node.input.push(x);
-                node.input.push(y);
-            }
-        });
+        if let Some(packet) = network
+            .take_undelivered(NETWORK)
+            .into_iter()
+            .find(|packet| packet[0] == NAT)
+        {
+            return Ok(packet[2] as u64);
+        }
     }
 }
 
+/// The NAT acts whenever the whole network is idle, so each computer gets its own thread and the
+/// NAT waits for them all to block.
 pub fn part2(input: &str) -> Result<u64, String> {
-    let (mut nodes, mut packets) = parse(input)?;
-    let mut is_idle = true;
+    let network = parse(input)?;
     let mut last_nat = None;
     let mut nat = None;
 
     loop {
-        for (i, node) in nodes.iter_mut().enumerate() {
-            node.run();
-
-            while !node.output.is_empty() {
-                is_idle = false;
-
-                let mut iter = node.output.drain(..3);
-                let address = iter
-                    .next()
-                    .ok_or_else(|| format!("Missing address on node {}", i))?
-                    as usize;
-
-                let packet = [
-                    iter.next()
-                        .ok_or_else(|| format!("Missing x value on node {}", i))?,
-                    iter.next()
-                        .ok_or_else(|| format!("Missing y value on node {}", i))?,
-                ];
-
-                println!("{} => {}: {:?}", i, address, packet);
-
-                if address == 255 {
-                    nat = Some(packet);
-                } else {
-                    packets[address].push(packet);
-                }
-            }
+        if network.wait()? == Outcome::Halted {
+            return Err("Every computer on the network halted.".to_string());
         }
 
-        nodes.iter_mut().enumerate().for_each(|(i, node)| {
-            if packets[i].is_empty() {
-                node.input.push(-1);
-            } else {
-                is_idle = false;
-
-                let [x, y] = packets[i].remove(0);
-                node.input.push(x);
-                node.input.push(y);
-            }
-        });
-
-        if is_idle {
-            if let Some(nat) = nat {
-                if last_nat == Some(nat) {
-                    return Ok(nat[1] as u64);
-                }
-
-                packets[0].push(nat);
+        if let Some(&packet) = nat_packets(&network).last() {
+            nat = Some(packet);
+        }
 
-                last_nat = Some(nat);
-            }
+        let packet =
+            nat.ok_or_else(|| "The network went idle before using the NAT.".to_string())?;
+        if last_nat == Some(packet) {
+            return Ok(packet[1] as u64);
         }
 
-        is_idle = true;
+        network.send(0, &packet);
+        last_nat = Some(packet);
     }
 }
 
-fn parse(input: &str) -> Result<(Vec<Intcode>, Vec<Vec<[i64; 2]>>), String> {
+/// The packets sent to the NAT since last time.
+fn nat_packets(network: &Handle) -> Vec<[i64; 2]> {
+    network
+        .take_undelivered(NETWORK)
+        .into_iter()
+        .filter(|packet| packet[0] == NAT)
+        .map(|packet| [packet[1], packet[2]])
+        .collect()
+}
+
+/// Fifty computers, each on its own thread and given its address, then -1 whenever there are no
+/// packets waiting.
+fn parse(input: &str) -> Result<Handle, String> {
     let intcode: Intcode = input.parse()?;
+    let mut network = Runtime::new(Mode::Threaded);
 
-    let nodes: Vec<Intcode> = (0..=49)
+    let computers: Vec<usize> = (0..COMPUTERS)
         .map(|i| {
-            let mut node = intcode.clone();
-            node.input.push(i);
-            node
+            let computer = network.add(format!("computer {}", i), intcode.clone());
+            network.send(computer, &[i]);
+            network.set_idle_input(computer, -1);
+            computer
         })
         .collect();
-    let packets: Vec<Vec<[i64; 2]>> = (0..=49).map(|_| Vec::new()).collect();
+    network.connect(NETWORK, &computers, Route::Addressed(3))?;
 
-    Ok((nodes, packets))
+    Ok(network.start())
 }
 
 #[cfg(test)]
@@ -1,3 +1,4 @@
+use crate::intcode::terminal::Terminal;
 use crate::intcode::Intcode;
 use std::collections;
 use std::fmt;
@@ -6,12 +7,14 @@ use std::io::prelude::*;
 use std::str;
 
 pub fn part1(input: &str) -> Result<u64, String> {
-    let mut game = Game::new(input);
+    let mut game = Game::new(input)?;
     game.loot()?;
 
     let stdin = io::stdin();
     loop {
-        println!("{:?}", game.room);
+        if let Some(room) = &game.room {
+            println!("{:?}", room);
+        }
 
         let mut input = String::new();
         stdin
@@ -19,9 +22,16 @@ pub fn part1(input: &str) -> Result<u64, String> {
             .read_line(&mut input)
             .map_err(|_| "Unable to read from stdin")?;
 
-        if let Ok(command) = input.parse() {
+        if let Some(path) = input.trim().strip_prefix("save ") {
+            game.terminal.intcode.save_file(path)?;
+            println!("Saved to {}", path);
+        } else if let Some(path) = input.trim().strip_prefix("load ") {
+            game.terminal = Terminal::new(Intcode::load_file(path)?);
+            game.room = None;
+            println!("Loaded {}, move to look around", path);
+        } else if let Ok(command) = input.parse() {
             println!("{:?}", command);
-            game.command(command);
+            game.command(command)?;
         } else {
             println!("Invalid command: {}", input);
         }
@@ -29,63 +39,60 @@ pub fn part1(input: &str) -> Result<u64, String> {
 }
 
 struct Game {
-    intcode: Intcode,
-    room: Room,
+    terminal: Terminal,
+    room: Option<Room>,
 }
 
 impl Game {
-    pub fn new(input: &str) -> Self {
-        let mut intcode: Intcode = input.parse().unwrap();
-        intcode.run();
-        let room: Room = intcode.output_string().parse().unwrap();
-        intcode.output.clear();
+    pub fn new(input: &str) -> Result<Self, String> {
+        let mut terminal = Terminal::new(input.parse()?);
+        let room: Room = terminal.expect("Command?")?.text().parse()?;
 
-        Game { intcode, room }
+        Ok(Game {
+            terminal,
+            room: Some(room),
+        })
     }
 
-    pub fn command(&mut self, command: Command) {
-        self.intcode.input_str(&String::from(&command)[..]);
-        self.intcode.input.push('\n' as i64);
-        self.intcode.run();
+    pub fn command(&mut self, command: Command) -> Result<(), String> {
+        let screen = self.terminal.send(&String::from(&command))?;
 
-        if let Ok(room) = self.intcode.output_string().parse() {
-            println!("{}", self.intcode.output_string());
-            self.room = room;
-        } else {
-            println!("{}", self.intcode.output_string());
+        println!("{}", screen);
+        if let Ok(room) = screen.text().parse() {
+            self.room = Some(room);
         }
 
-        self.intcode.output.clear();
+        Ok(())
     }
 
-    pub fn loot(&mut self) -> Result<(), &'static str> {
-        self.command("south".parse()?); // Hull Breach -> Holodeck
-        self.command("west".parse()?); // Holodeck -> Corridor
-        self.command("north".parse()?); // Corridor -> Crew Quarters
-                                        //self.command("take fuel cell".parse()?);
-        self.command("south".parse()?); // Crew Quarters -> Corridor
-        self.command("east".parse()?); // Corridor -> Holodeck
-        self.command("north".parse()?); // Holodeck -> Hull Breach
-        self.command("north".parse()?); // Hull Breach -> Stables
-        self.command("east".parse()?); // Stables -> Gift Wrapping Center
-                                       //self.command("take candy cane".parse()?);
-        self.command("south".parse()?); // Gift Wrapping Center -> Engineering
-        self.command("take hypercube".parse()?);
-        self.command("north".parse()?); // Engineering -> Gift Wrapping Center
-        self.command("west".parse()?); // Gift Wrapping Center -> Stables
-        self.command("north".parse()?); // Stables -> Observatory
-                                        //self.command("take coin".parse()?);
-        self.command("east".parse()?); // Observatory -> Hallway
-        self.command("take tambourine".parse()?);
-        self.command("west".parse()?); // Hallway -> Observatory
-        self.command("west".parse()?); // Observatory -> Arcade
-        self.command("take spool of cat6".parse()?);
-        self.command("north".parse()?); // Arcade -> Navigation
-        self.command("take weather machine".parse()?);
-        self.command("west".parse()?); // Navigation -> Hot Chocolate Fountain
-                                       //self.command("take mutex".parse()?); // Navigation -> Hot Chocolate Fountain
-        self.command("west".parse()?); // Hot Chocolate Fountain -> Security Checkpoint
-        self.command("west".parse()?); // Security Checkpoint -> Cockpit
+    pub fn loot(&mut self) -> Result<(), String> {
+        self.command("south".parse()?)?; // Hull Breach -> Holodeck
+        self.command("west".parse()?)?; // Holodeck -> Corridor
+        self.command("north".parse()?)?; // Corridor -> Crew Quarters
+                                         //self.command("take fuel cell".parse()?)?;
+        self.command("south".parse()?)?; // Crew Quarters -> Corridor
+        self.command("east".parse()?)?; // Corridor -> Holodeck
+        self.command("north".parse()?)?; // Holodeck -> Hull Breach
+        self.command("north".parse()?)?; // Hull Breach -> Stables
+        self.command("east".parse()?)?; // Stables -> Gift Wrapping Center
+                                        //self.command("take candy cane".parse()?)?;
+        self.command("south".parse()?)?; // Gift Wrapping Center -> Engineering
+        self.command("take hypercube".parse()?)?;
+        self.command("north".parse()?)?; // Engineering -> Gift Wrapping Center
+        self.command("west".parse()?)?; // Gift Wrapping Center -> Stables
+        self.command("north".parse()?)?; // Stables -> Observatory
+                                         //self.command("take coin".parse()?)?;
+        self.command("east".parse()?)?; // Observatory -> Hallway
+        self.command("take tambourine".parse()?)?;
+        self.command("west".parse()?)?; // Hallway -> Observatory
+        self.command("west".parse()?)?; // Observatory -> Arcade
+        self.command("take spool of cat6".parse()?)?;
+        self.command("north".parse()?)?; // Arcade -> Navigation
+        self.command("take weather machine".parse()?)?;
+        self.command("west".parse()?)?; // Navigation -> Hot Chocolate Fountain
+                                        //self.command("take mutex".parse()?)?; // Navigation -> Hot Chocolate Fountain
+        self.command("west".parse()?)?; // Hot Chocolate Fountain -> Security Checkpoint
+        self.command("west".parse()?)?; // Security Checkpoint -> Cockpit
         Ok(())
     }
 }
@@ -291,3 +298,16 @@ impl From<&Command> for String {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::Intcode;
+    use crate::intcode::session::Session;
+
+    #[test]
+    fn replay_session() {
+        let intcode: Intcode = include_str!("input.txt").parse().unwrap();
+        let session = Session::load(include_str!("session.txt").as_bytes()).unwrap();
+        assert_eq!(Ok(()), session.replay(intcode));
+    }
+}
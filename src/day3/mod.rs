@@ -134,7 +134,7 @@ impl str::FromStr for Line {
 
         for segment in raw.trim().split(',') {
             let direction = segment.chars().nth(0).unwrap();
-            let distance = &segment[1..].parse().unwrap();
+            let distance = &segment[1..].parse::<i64>().unwrap();
 
             match direction {
                 'R' | 'L' => {
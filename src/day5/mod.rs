@@ -3,8 +3,8 @@ use crate::intcode::Intcode;
 pub fn part1(input: &str) -> Result<u64, String> {
     let mut intcode: Intcode = input.parse()?;
 
-    intcode.input.push(1);
-    intcode.run();
+    intcode.input.push_back(1);
+    intcode.run()?;
 
     println!("Output: {:?}", intcode.output);
 
@@ -18,8 +18,8 @@ pub fn part1(input: &str) -> Result<u64, String> {
 pub fn part2(input: &str) -> Result<u64, String> {
     let mut intcode: Intcode = input.parse()?;
 
-    intcode.input.push(5);
-    intcode.run();
+    intcode.input.push_back(5);
+    intcode.run()?;
 
     println!("Output: {:?}", intcode.output);
 
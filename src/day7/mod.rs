@@ -1,4 +1,6 @@
-use crate::intcode::{Intcode, Response};
+use crate::intcode::runtime::{Mode, Runtime};
+use crate::intcode::scheduler::{Outcome, Route};
+use crate::intcode::Intcode;
 
 pub fn part1(input: &str) -> Result<u64, String> {
     let mut max = 0;
@@ -10,11 +12,11 @@ pub fn part1(input: &str) -> Result<u64, String> {
                 for d in (0..5).filter(|d| !&[a, b, c][..].contains(d)) {
                     for e in (0..5).filter(|e| !&[a, b, c, d][..].contains(e)) {
                         let output = Signal::new()
-                            .amplify(&intcode, a)
-                            .amplify(&intcode, b)
-                            .amplify(&intcode, c)
-                            .amplify(&intcode, d)
-                            .amplify(&intcode, e)
+                            .amplify(&intcode, a)?
+                            .amplify(&intcode, b)?
+                            .amplify(&intcode, c)?
+                            .amplify(&intcode, d)?
+                            .amplify(&intcode, e)?
                             .get_output();
 
                         println!("{}, {}, {}, {}, {} => {}", a, b, c, d, e, output);
@@ -40,7 +42,7 @@ pub fn part2(input: &str) -> Result<u64, String> {
             for c in (5..10).filter(|c| !&[a, b][..].contains(c)) {
                 for d in (5..10).filter(|d| !&[a, b, c][..].contains(d)) {
                     for e in (5..10).filter(|e| !&[a, b, c, d][..].contains(e)) {
-                        let output = Signal::feedback(&intcode, [a, b, c, d, e]);
+                        let output = Signal::feedback(&intcode, [a, b, c, d, e])?;
 
                         println!("{}, {}, {}, {}, {} => {}", a, b, c, d, e, output);
 
@@ -63,36 +65,49 @@ impl Signal {
         Self(0)
     }
 
-    pub fn amplify(&mut self, intcode: &Intcode, phase: i64) -> &mut Self {
+    pub fn amplify(&mut self, intcode: &Intcode, phase: i64) -> Result<&mut Self, String> {
         let mut intcode = intcode.clone().with_input(&[phase, self.0]);
-        intcode.run();
+        intcode.run()?;
         self.0 = intcode.output.pop().unwrap();
-        self
+        Ok(self)
     }
 
-    pub fn feedback(intcode: &Intcode, phases: [i64; 5]) -> i64 {
-        let mut amplifiers = [
-            intcode.clone().with_input(&phases[0..1]),
-            intcode.clone().with_input(&phases[1..2]),
-            intcode.clone().with_input(&phases[2..3]),
-            intcode.clone().with_input(&phases[3..4]),
-            intcode.clone().with_input(&phases[4..5]),
-        ];
-
-        let mut signal = 0;
-        loop {
-            for i in 0..4 {
-                amplifiers[i].input.push(signal);
-                amplifiers[i].run();
-                signal = amplifiers[i].output.pop().unwrap();
-            }
-
-            amplifiers[4].input.push(signal);
-            if Response::Terminated == amplifiers[4].run() {
-                break amplifiers[4].output.pop().unwrap();
-            } else {
-                signal = amplifiers[4].output.pop().unwrap();
-            }
+    /// Connect the amplifiers in a loop, each on its own thread, then run them until they all halt.
+    /// The last amplifier's final output is left waiting for the first.
+    pub fn feedback(intcode: &Intcode, phases: [i64; 5]) -> Result<i64, String> {
+        let mut runtime = Runtime::new(Mode::Threaded);
+        let amplifiers: Vec<usize> = phases
+            .iter()
+            .enumerate()
+            .map(|(i, &phase)| {
+                runtime.add(
+                    format!("amplifier {}", i),
+                    intcode.clone().with_input(&[phase]),
+                )
+            })
+            .collect();
+
+        for (i, &amplifier) in amplifiers.iter().enumerate() {
+            let next = amplifiers[(i + 1) % amplifiers.len()];
+            runtime.connect(
+                &format!("{} to {}", amplifier, next),
+                &[amplifier],
+                Route::To(vec![next]),
+            )?;
+        }
+        runtime.send(amplifiers[0], &[0]);
+
+        let network = runtime.start();
+        let outcome = network.wait()?;
+        let machines = network.finish()?;
+
+        match outcome {
+            Outcome::Halted => machines[amplifiers[0]]
+                .input
+                .back()
+                .copied()
+                .ok_or_else(|| "No output from the last amplifier.".to_string()),
+            Outcome::Idle => Err("Amplifiers stopped without halting.".to_string()),
         }
     }
 
@@ -3,8 +3,8 @@ use crate::intcode::Intcode;
 pub fn part1(input: &str) -> Result<u64, String> {
     let mut intcode = input.parse::<Intcode>()?;
 
-    intcode.input.push(1);
-    intcode.run();
+    intcode.input.push_back(1);
+    intcode.run()?;
 
     println!("Output: {:?}", intcode.output);
 
@@ -18,8 +18,8 @@ pub fn part1(input: &str) -> Result<u64, String> {
 pub fn part2(input: &str) -> Result<u64, String> {
     let mut intcode = input.parse::<Intcode>()?;
 
-    intcode.input.push(2);
-    intcode.run();
+    intcode.input.push_back(2);
+    intcode.run()?;
 
     println!("Output: {:?}", intcode.output);
 
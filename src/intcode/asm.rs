@@ -0,0 +1,372 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use super::{Instruction, Opcode, Param};
+
+/// Assemble a program written in Intcode mnemonics into a form that can be loaded by
+/// `Intcode::new`.
+///
+/// ```text
+/// ; Count down from the input to zero.
+///         in -> [counter]
+/// loop:   out [counter]
+///         add [counter], #-1 -> [counter]
+///         jt [counter], #loop
+///         hlt
+/// counter: data 0
+/// ```
+///
+/// Operands are `#value` for immediate mode, `[address]` for position mode and `[rb+offset]` for
+/// relative mode, where values and addresses may be labels. The output parameter can be written
+/// either as a final operand or after `->`. A leading address such as `0010:`, as found in the
+/// disassembler's listing, is checked against the current address.
+pub fn assemble(source: &str) -> Result<Vec<i64>, AsmError> {
+    let mut statements = Vec::new();
+    let mut labels = HashMap::new();
+    let mut address = 0;
+
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let error = |message: String| AsmError {
+            line: line_number,
+            message,
+        };
+
+        let mut rest = line.split(';').next().unwrap_or("").trim();
+
+        while let Some(colon) = rest.find(':') {
+            let name = rest[..colon].trim();
+
+            if !name.is_empty() && name.chars().all(|c| c.is_ascii_digit()) {
+                let expected: usize = name
+                    .parse()
+                    .map_err(|_| error(format!("Invalid address {}", name)))?;
+                if expected != address {
+                    return Err(error(format!(
+                        "Address {} does not match the assembled address {}",
+                        name, address
+                    )));
+                }
+            } else if is_label(name) {
+                if labels.insert(name.to_string(), address).is_some() {
+                    return Err(error(format!("Duplicate label {}", name)));
+                }
+            } else {
+                return Err(error(format!("Invalid label {}", name)));
+            }
+
+            rest = rest[colon + 1..].trim();
+        }
+
+        if rest.is_empty() {
+            continue;
+        }
+
+        let (mnemonic, operands) = match rest.find(char::is_whitespace) {
+            Some(i) => (&rest[..i], rest[i..].trim()),
+            None => (rest, ""),
+        };
+        let (inputs, output) = match operands.find("->") {
+            Some(i) => (operands[..i].trim(), Some(operands[i + 2..].trim())),
+            None => (operands, None),
+        };
+        let mut operands: Vec<&str> = if inputs.is_empty() {
+            Vec::new()
+        } else {
+            inputs.split(',').map(str::trim).collect()
+        };
+        operands.extend(output);
+
+        let statement = if mnemonic.eq_ignore_ascii_case("data") {
+            Statement::Data(
+                operands
+                    .iter()
+                    .map(|operand| parse_value(operand))
+                    .collect::<Result<_, _>>()
+                    .map_err(error)?,
+            )
+        } else {
+            let opcode = Opcode::ALL
+                .iter()
+                .find(|opcode| opcode.mnemonic().eq_ignore_ascii_case(mnemonic))
+                .copied()
+                .ok_or_else(|| error(format!("Unknown mnemonic {}", mnemonic)))?;
+
+            if operands.len() != opcode.param_count() {
+                return Err(error(format!(
+                    "{} takes {} operands, found {}",
+                    opcode,
+                    opcode.param_count(),
+                    operands.len()
+                )));
+            }
+
+            let operands = operands
+                .iter()
+                .map(|operand| parse_operand(operand))
+                .collect::<Result<Vec<Operand>, String>>()
+                .map_err(error)?;
+
+            if let Some(index) = opcode.output_param() {
+                if let Operand::Immediate(_) = operands[index] {
+                    return Err(error(format!(
+                        "Output operand of {} must not be immediate",
+                        opcode
+                    )));
+                }
+            }
+
+            Statement::Instruction(opcode, operands)
+        };
+
+        address += statement.size();
+        statements.push((line_number, statement));
+    }
+
+    let mut program = Vec::with_capacity(address);
+
+    for (line_number, statement) in statements {
+        let resolve = |value: &Value| {
+            value.resolve(&labels).map_err(|message| AsmError {
+                line: line_number,
+                message,
+            })
+        };
+
+        match statement {
+            Statement::Data(values) => {
+                for value in values.iter() {
+                    program.push(resolve(value)?);
+                }
+            }
+            Statement::Instruction(opcode, operands) => {
+                let params = operands
+                    .iter()
+                    .map(|operand| {
+                        Ok(match operand {
+                            Operand::Position(value) => Param::Position(resolve(value)?),
+                            Operand::Immediate(value) => Param::Immediate(resolve(value)?),
+                            Operand::Relative(offset) => Param::Relative(*offset),
+                        })
+                    })
+                    .collect::<Result<Vec<Param>, AsmError>>()?;
+
+                program.extend(Instruction::encode(opcode, &params));
+            }
+        }
+    }
+
+    Ok(program)
+}
+
+#[derive(Debug, PartialEq)]
+pub struct AsmError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Line {}: {}", self.line, self.message)
+    }
+}
+
+impl From<AsmError> for String {
+    fn from(error: AsmError) -> Self {
+        error.to_string()
+    }
+}
+
+enum Statement {
+    Instruction(Opcode, Vec<Operand>),
+    Data(Vec<Value>),
+}
+
+impl Statement {
+    fn size(&self) -> usize {
+        match self {
+            Statement::Instruction(opcode, _) => opcode.param_count() + 1,
+            Statement::Data(values) => values.len(),
+        }
+    }
+}
+
+enum Operand {
+    Position(Value),
+    Immediate(Value),
+    Relative(i64),
+}
+
+enum Value {
+    Number(i64),
+    Label(String),
+}
+
+impl Value {
+    fn resolve(&self, labels: &HashMap<String, usize>) -> Result<i64, String> {
+        match self {
+            Value::Number(value) => Ok(*value),
+            Value::Label(name) => labels
+                .get(name)
+                .map(|&address| address as i64)
+                .ok_or_else(|| format!("Undefined label {}", name)),
+        }
+    }
+}
+
+fn parse_operand(raw: &str) -> Result<Operand, String> {
+    if let Some(value) = raw.strip_prefix('#') {
+        Ok(Operand::Immediate(parse_value(value)?))
+    } else if let Some(inner) = raw.strip_prefix('[').and_then(|r| r.strip_suffix(']')) {
+        let inner = inner.trim();
+
+        let offset = inner
+            .strip_prefix("rb")
+            .map(str::trim_start)
+            .filter(|offset| offset.is_empty() || offset.starts_with(&['+', '-'][..]));
+
+        if let Some(offset) = offset {
+            let offset = offset.trim_start_matches('+').replace(' ', "");
+
+            if offset.is_empty() {
+                Ok(Operand::Relative(0))
+            } else {
+                offset
+                    .parse()
+                    .map(Operand::Relative)
+                    .map_err(|_| format!("Invalid relative offset {}", raw))
+            }
+        } else {
+            Ok(Operand::Position(parse_value(inner)?))
+        }
+    } else {
+        Err(format!("Invalid operand {}", raw))
+    }
+}
+
+fn parse_value(raw: &str) -> Result<Value, String> {
+    let raw = raw.trim();
+
+    if let Ok(value) = raw.parse() {
+        Ok(Value::Number(value))
+    } else if is_label(raw) {
+        Ok(Value::Label(raw.to_string()))
+    } else {
+        Err(format!("Invalid value {}", raw))
+    }
+}
+
+fn is_label(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::disasm::disassemble;
+    use super::super::Intcode;
+    use super::*;
+
+    #[test]
+    fn assemble_countdown() {
+        let program = assemble(
+            "
+; Count down from the input to zero.
+        in -> [counter]
+loop:   out [counter]
+        add [counter], #-1 -> [counter]
+        jt [counter], #loop
+        hlt
+counter: data 0
+",
+        )
+        .unwrap();
+
+        assert_eq!(
+            vec![3, 12, 4, 12, 1001, 12, -1, 12, 1005, 12, 2, 99, 0],
+            program
+        );
+
+        let mut intcode = Intcode::new(program);
+        intcode.input.push_back(3);
+        intcode.run().unwrap();
+        assert_eq!(vec![3, 2, 1], intcode.output);
+    }
+
+    #[test]
+    fn assemble_relative() {
+        assert_eq!(
+            vec![109, -1, 203, 1, 21201, 0, 5, 3, 22207, -2, 1, 0, 99],
+            assemble(
+                "arb #-1
+                 in -> [rb+1]
+                 add [rb], #5, [rb+3]
+                 LT [rb - 2], [rb + 1] -> [rb+0]
+                 HLT"
+            )
+            .unwrap(),
+        );
+    }
+
+    #[test]
+    fn assemble_errors() {
+        assert_eq!(
+            Err(AsmError {
+                line: 2,
+                message: "Unknown mnemonic jmp".to_string(),
+            }),
+            assemble("hlt\njmp #0"),
+        );
+        assert_eq!(
+            Err(AsmError {
+                line: 1,
+                message: "ADD takes 3 operands, found 2".to_string(),
+            }),
+            assemble("add #1, #2"),
+        );
+        assert_eq!(
+            Err(AsmError {
+                line: 1,
+                message: "Output operand of IN must not be immediate".to_string(),
+            }),
+            assemble("in #3"),
+        );
+        assert_eq!(
+            Err(AsmError {
+                line: 3,
+                message: "Undefined label nowhere".to_string(),
+            }),
+            assemble("\nhlt\njt #1, #nowhere"),
+        );
+        assert_eq!(
+            Err(AsmError {
+                line: 2,
+                message: "Duplicate label a".to_string(),
+            }),
+            assemble("a: hlt\na: hlt"),
+        );
+        assert_eq!(
+            Err(AsmError {
+                line: 2,
+                message: "Address 0003 does not match the assembled address 1".to_string(),
+            }),
+            assemble("0000: hlt\n0003: hlt"),
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        for input in &[
+            include_str!("../day2/input.txt"),
+            include_str!("../day5/input.txt"),
+            include_str!("../day9/input.txt"),
+            include_str!("../day13/input.txt"),
+            include_str!("../day25/input.txt"),
+        ] {
+            let intcode: Intcode = input.parse().unwrap();
+            assert_eq!(intcode.data, assemble(&disassemble(&intcode)).unwrap());
+        }
+    }
+}
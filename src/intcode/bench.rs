@@ -0,0 +1,270 @@
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+use super::runtime::{Mode, Runtime};
+use super::scheduler::{Outcome, Policy, Route, Scheduler};
+use super::{Engine, Intcode};
+
+/// Stop a workload after this many instructions, for programs that would otherwise run forever.
+const STEP_LIMIT: u64 = 2_000_000;
+
+/// How many jobs the source machine of the network benchmark hands to every worker, and how long
+/// each worker counts down for each one.
+const NETWORK_JOBS: usize = 10;
+const NETWORK_WORK: i64 = 50_000;
+
+/// A puzzle input and what to feed it. The input is repeated for as long as the program asks for
+/// more.
+pub(crate) struct Workload {
+    pub(crate) day: u8,
+    pub(crate) program: &'static str,
+    pub(crate) patches: &'static [(usize, i64)],
+    pub(crate) input: Vec<i64>,
+}
+
+pub(crate) fn workloads() -> Vec<Workload> {
+    let ascii = |text: &str| text.chars().map(|c| c as i64).collect();
+
+    vec![
+        Workload {
+            day: 2,
+            program: include_str!("../day2/input.txt"),
+            patches: &[(1, 12), (2, 2)],
+            input: vec![],
+        },
+        Workload {
+            day: 5,
+            program: include_str!("../day5/input.txt"),
+            patches: &[],
+            input: vec![5],
+        },
+        Workload {
+            day: 7,
+            program: include_str!("../day7/input.txt"),
+            patches: &[],
+            input: vec![5, 0],
+        },
+        Workload {
+            day: 9,
+            program: include_str!("../day9/input.txt"),
+            patches: &[],
+            input: vec![2],
+        },
+        Workload {
+            day: 11,
+            program: include_str!("../day11/input.txt"),
+            patches: &[],
+            input: vec![0, 1],
+        },
+        Workload {
+            day: 13,
+            program: include_str!("../day13/input.txt"),
+            patches: &[(0, 2)],
+            input: vec![0],
+        },
+        Workload {
+            day: 15,
+            program: include_str!("../day15/input.txt"),
+            patches: &[],
+            input: vec![1, 4, 2, 3],
+        },
+        Workload {
+            day: 17,
+            program: include_str!("../day17/input.txt"),
+            patches: &[],
+            input: vec![],
+        },
+        Workload {
+            day: 19,
+            program: include_str!("../day19/input.txt"),
+            patches: &[],
+            input: vec![25, 30],
+        },
+        Workload {
+            day: 21,
+            program: include_str!("../day21/input.txt"),
+            patches: &[],
+            input: ascii("NOT A J\nNOT C T\nAND D T\nOR T J\nWALK\n"),
+        },
+        Workload {
+            day: 23,
+            program: include_str!("../day23/input.txt"),
+            patches: &[],
+            input: vec![0, -1],
+        },
+        Workload {
+            day: 25,
+            program: include_str!("../day25/input.txt"),
+            patches: &[],
+            input: ascii("south\nnorth\n"),
+        },
+    ]
+}
+
+/// Run every Intcode day's program under each engine, keeping the fastest of `rounds` runs, and
+/// tabulate the results.
+pub fn benchmark(rounds: u32) -> Result<String, String> {
+    let mut output = String::new();
+    let (mut interpreted_total, mut decoded_total) = (Duration::default(), Duration::default());
+
+    writeln!(output, "day      steps  interpreter      decoded  speedup").unwrap();
+
+    for workload in workloads() {
+        let (steps, interpreted) = time(&workload, Engine::Interpreter, rounds)?;
+        let (decoded_steps, decoded) = time(&workload, Engine::Decoded, rounds)?;
+
+        if steps != decoded_steps {
+            return Err(format!(
+                "Day {} ran {} steps interpreted but {} decoded",
+                workload.day, steps, decoded_steps
+            ));
+        }
+
+        writeln!(
+            output,
+            "{:>3} {:>10} {:>12.2?} {:>12.2?} {:>7.1}x",
+            workload.day,
+            steps,
+            interpreted,
+            decoded,
+            interpreted.as_secs_f64() / decoded.as_secs_f64()
+        )
+        .unwrap();
+
+        interpreted_total += interpreted;
+        decoded_total += decoded;
+    }
+
+    writeln!(
+        output,
+        "all {:>10} {:>12.2?} {:>12.2?} {:>7.1}x",
+        "",
+        interpreted_total,
+        decoded_total,
+        interpreted_total.as_secs_f64() / decoded_total.as_secs_f64()
+    )
+    .unwrap();
+
+    Ok(output)
+}
+
+/// Returns the number of steps executed and the fastest time taken.
+fn time(workload: &Workload, engine: Engine, rounds: u32) -> Result<(u64, Duration), String> {
+    let mut template: Intcode = workload.program.parse()?;
+    for &(address, value) in workload.patches {
+        template.set(address, value);
+    }
+
+    let mut best = None;
+    let mut steps = 0;
+
+    for _ in 0..rounds.max(1) {
+        let mut input = workload.input.iter().copied().cycle();
+        let mut intcode = template
+            .clone()
+            .with_engine(engine)
+            .with_io(|| input.next(), |_| {});
+        let start = Instant::now();
+
+        intcode
+            .run_for(STEP_LIMIT)
+            .map_err(|e| format!("Day {}: {}", workload.day, e))?;
+
+        let elapsed = start.elapsed();
+        best = Some(best.map_or(elapsed, |best: Duration| best.min(elapsed)));
+        steps = intcode.steps;
+    }
+
+    Ok((steps, best.unwrap()))
+}
+
+/// Run a network of one machine fanning jobs out to `workers` others, on a single thread with a
+/// `Scheduler` and then with a `Runtime` in each mode, and tabulate the fastest of `rounds` runs.
+pub fn network(workers: usize, rounds: u32) -> Result<String, String> {
+    // Reads a number, counts down from it, outputs 1, then starts again.
+    let worker = Intcode::new(vec![
+        3, 20, 1001, 20, -1, 20, 1005, 20, 2, 104, 1, 1105, 1, 0, 0, 0, 0, 0, 0, 0, 0,
+    ]);
+    let mut source = Vec::new();
+    for _ in 0..NETWORK_JOBS {
+        source.extend(&[104, NETWORK_WORK]);
+    }
+    source.push(99);
+    let source = Intcode::new(source);
+
+    let mut output = String::new();
+    writeln!(output, "mode           workers       steps         time").unwrap();
+
+    for mode in &[None, Some(Mode::Deterministic), Some(Mode::Threaded)] {
+        let mut best = None;
+        let mut steps = 0;
+
+        for _ in 0..rounds.max(1) {
+            let start = Instant::now();
+            let machines = match mode {
+                None => {
+                    let mut scheduler = Scheduler::new(Policy::UntilBlocked);
+                    let ids: Vec<usize> = (0..=workers)
+                        .map(|i| {
+                            let intcode = if i == 0 { &source } else { &worker };
+                            scheduler.add(format!("machine {}", i), intcode.clone())
+                        })
+                        .collect();
+                    scheduler.connect("jobs", &[0], Route::To(ids[1..].to_vec()))?;
+
+                    if scheduler.run()? != Outcome::Idle {
+                        return Err("The workers halted.".to_string());
+                    }
+                    ids.iter()
+                        .map(|&id| scheduler.machine(id).clone())
+                        .collect()
+                }
+                Some(mode) => {
+                    let mut runtime = Runtime::new(*mode);
+                    let ids: Vec<usize> = (0..=workers)
+                        .map(|i| {
+                            let intcode = if i == 0 { &source } else { &worker };
+                            runtime.add(format!("machine {}", i), intcode.clone())
+                        })
+                        .collect();
+                    runtime.connect("jobs", &[0], Route::To(ids[1..].to_vec()))?;
+
+                    let handle = runtime.start();
+                    if handle.wait()? != Outcome::Idle {
+                        return Err("The workers halted.".to_string());
+                    }
+                    handle.finish()?
+                }
+            };
+            let elapsed = start.elapsed();
+
+            let done: usize = machines.iter().map(|m| m.output.len()).sum();
+            if done != workers * NETWORK_JOBS {
+                return Err(format!(
+                    "Expected {} jobs to be done, but {} were",
+                    workers * NETWORK_JOBS,
+                    done
+                ));
+            }
+
+            best = Some(best.map_or(elapsed, |best: Duration| best.min(elapsed)));
+            steps = machines.iter().map(|m| m.steps).sum();
+        }
+
+        let name = match mode {
+            None => "scheduler".to_string(),
+            Some(mode) => format!("{:?}", mode).to_lowercase(),
+        };
+        writeln!(
+            output,
+            "{:<13} {:>8} {:>11} {:>12.2?}",
+            name,
+            workers,
+            steps,
+            best.unwrap()
+        )
+        .unwrap();
+    }
+
+    Ok(output)
+}
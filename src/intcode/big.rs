@@ -0,0 +1,256 @@
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryFrom;
+
+use num_bigint::BigInt;
+
+use super::memory::PAGE_SIZE;
+use super::{Intcode, Opcode, Response, VmError};
+
+/// An Intcode machine whose words are arbitrary precision integers, for programs that compute
+/// values too large for an `i64`. It only runs programs, and much more slowly than `Intcode`; the
+/// tools all work on `Intcode`.
+///
+/// A machine stopped by `VmError::Overflow` under `Arithmetic::Checked` can be converted with
+/// `BigIntcode::from` and resumed from the instruction that overflowed.
+#[derive(Clone, Debug)]
+pub struct BigIntcode {
+    pub data: Vec<BigInt>,
+    far: HashMap<usize, BigInt>,
+    pub input: VecDeque<BigInt>,
+    pub output: Vec<BigInt>,
+    cursor: usize,
+    relative_base: BigInt,
+    pub steps: u64,
+}
+
+impl BigIntcode {
+    pub fn new(data: Vec<BigInt>) -> Self {
+        Self {
+            data,
+            far: HashMap::new(),
+            input: VecDeque::new(),
+            output: Vec::new(),
+            cursor: 0,
+            relative_base: BigInt::from(0),
+            steps: 0,
+        }
+    }
+
+    pub fn with_input(mut self, data: &[i64]) -> Self {
+        self.input
+            .extend(data.iter().map(|&value| BigInt::from(value)));
+        self
+    }
+
+    pub fn get(&self, address: usize) -> BigInt {
+        match self.data.get(address) {
+            Some(value) => value.clone(),
+            None => self.far.get(&address).cloned().unwrap_or_default(),
+        }
+    }
+
+    pub fn set(&mut self, address: usize, value: BigInt) {
+        if address < self.data.len() {
+            self.data[address] = value;
+        } else if address < self.data.len() + PAGE_SIZE {
+            let (len, far) = (self.data.len(), &mut self.far);
+            self.data
+                .extend((len..=address).map(|a| far.remove(&a).unwrap_or_default()));
+            self.data[address] = value;
+        } else {
+            self.far.insert(address, value);
+        }
+    }
+
+    pub fn run(&mut self) -> Result<Response, VmError> {
+        loop {
+            if let Some(result) = self.step()? {
+                break Ok(result);
+            }
+        }
+    }
+
+    pub fn step(&mut self) -> Result<Option<Response>, VmError> {
+        self.steps += 1;
+        let raw = self.get(self.cursor);
+        let opcode = i64::try_from(&raw % 100)
+            .ok()
+            .and_then(|code| Opcode::try_from(code).ok())
+            .ok_or_else(|| VmError::UnknownOpcode {
+                cursor: self.cursor,
+                instruction: small(&raw),
+            })?;
+
+        match opcode {
+            Opcode::Add => {
+                let value = self.param(&raw, 0)? + self.param(&raw, 1)?;
+                self.store(&raw, 2, value)?;
+            }
+            Opcode::Mul => {
+                let value = self.param(&raw, 0)? * self.param(&raw, 1)?;
+                self.store(&raw, 2, value)?;
+            }
+            Opcode::Input => match self.input.pop_front() {
+                Some(value) => self.store(&raw, 0, value)?,
+                None => return Ok(Some(Response::InputRequired)),
+            },
+            Opcode::Output => {
+                let value = self.param(&raw, 0)?;
+                self.output.push(value);
+            }
+            Opcode::JumpIfTrue | Opcode::JumpIfFalse => {
+                let zero = self.param(&raw, 0)? == BigInt::from(0);
+                if zero == (opcode == Opcode::JumpIfFalse) {
+                    let target = self.param(&raw, 1)?;
+                    self.cursor = self.address(&raw, target)?;
+                    return Ok(None);
+                }
+            }
+            Opcode::LessThan => {
+                let value = (self.param(&raw, 0)? < self.param(&raw, 1)?) as i64;
+                self.store(&raw, 2, BigInt::from(value))?;
+            }
+            Opcode::Equals => {
+                let value = (self.param(&raw, 0)? == self.param(&raw, 1)?) as i64;
+                self.store(&raw, 2, BigInt::from(value))?;
+            }
+            Opcode::AdjustRelativeBase => self.relative_base += self.param(&raw, 0)?,
+            Opcode::Halt => return Ok(Some(Response::Terminated)),
+        }
+
+        self.cursor += 1 + opcode.param_count();
+        Ok(None)
+    }
+
+    fn mode(&self, raw: &BigInt, param_index: usize) -> Result<i64, VmError> {
+        let mode = raw / BigInt::from(10i64.pow(param_index as u32 + 2)) % 10;
+        match small(&mode) {
+            mode @ 0..=2 => Ok(mode),
+            mode => Err(VmError::InvalidMode {
+                cursor: self.cursor,
+                instruction: small(raw),
+                param_index,
+                mode,
+            }),
+        }
+    }
+
+    fn param(&self, raw: &BigInt, param_index: usize) -> Result<BigInt, VmError> {
+        let param = self.get(self.cursor + param_index + 1);
+
+        Ok(match self.mode(raw, param_index)? {
+            0 => self.get(self.address(raw, param)?),
+            1 => param,
+            _ => self.get(self.address(raw, param + &self.relative_base)?),
+        })
+    }
+
+    fn store(&mut self, raw: &BigInt, param_index: usize, value: BigInt) -> Result<(), VmError> {
+        let param = self.get(self.cursor + param_index + 1);
+
+        let address = match self.mode(raw, param_index)? {
+            0 => self.address(raw, param)?,
+            1 => {
+                return Err(VmError::ImmediateWrite {
+                    cursor: self.cursor,
+                    instruction: small(raw),
+                    param_index,
+                })
+            }
+            _ => self.address(raw, param + &self.relative_base)?,
+        };
+
+        self.set(address, value);
+        Ok(())
+    }
+
+    /// Addresses beyond the reach of a `usize` are reported as an overflow.
+    fn address(&self, raw: &BigInt, address: BigInt) -> Result<usize, VmError> {
+        if address < BigInt::from(0) {
+            return Err(VmError::NegativeAddress {
+                cursor: self.cursor,
+                instruction: small(raw),
+                address: small(&address),
+            });
+        }
+
+        usize::try_from(&address).map_err(|_| VmError::Overflow {
+            cursor: self.cursor,
+            instruction: small(raw),
+        })
+    }
+}
+
+/// Copies the machine's memory, registers and queued input. Its output, tracer and profiler are
+/// left behind.
+impl From<Intcode> for BigIntcode {
+    fn from(intcode: Intcode) -> Self {
+        let mut big = BigIntcode::new(
+            intcode
+                .data
+                .iter()
+                .map(|&word| BigInt::from(word))
+                .collect(),
+        );
+        for (address, value) in intcode.pages.words() {
+            big.set(address, BigInt::from(value));
+        }
+
+        big.input = intcode
+            .input
+            .iter()
+            .map(|&value| BigInt::from(value))
+            .collect();
+        big.cursor = intcode.cursor;
+        big.relative_base = BigInt::from(intcode.relative_base);
+        big.steps = intcode.steps;
+        big
+    }
+}
+
+/// A word for an error report, saturated if it doesn't fit in an `i64`.
+fn small(word: &BigInt) -> i64 {
+    i64::try_from(word).unwrap_or(if *word < BigInt::from(0) {
+        i64::MIN
+    } else {
+        i64::MAX
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::Arithmetic;
+    use super::*;
+
+    /// Reads a number, then squares it three times and outputs the result.
+    const POWER: [i64; 18] = [
+        3, 17, 2, 17, 17, 17, 2, 17, 17, 17, 2, 17, 17, 17, 4, 17, 99, 0,
+    ];
+
+    #[test]
+    fn huge_values() {
+        let mut intcode = BigIntcode::new(POWER.iter().map(|&word| BigInt::from(word)).collect())
+            .with_input(&[1 << 20]);
+
+        assert_eq!(Ok(Response::Terminated), intcode.run());
+        assert_eq!(vec![BigInt::from(2).pow(160)], intcode.output);
+        assert_eq!(6, intcode.steps);
+    }
+
+    #[test]
+    fn resume_after_overflow() {
+        let mut intcode = Intcode::new(POWER.to_vec())
+            .with_arithmetic(Arithmetic::Checked)
+            .with_input(&[1 << 20]);
+
+        let error = intcode.run().unwrap_err();
+        assert_eq!(
+            "Arithmetic overflow in instruction 2 at offset 6!",
+            error.to_string()
+        );
+
+        let mut big = BigIntcode::from(intcode);
+        assert_eq!(Ok(Response::Terminated), big.run());
+        assert_eq!(vec![BigInt::from(2).pow(160)], big.output);
+    }
+}
@@ -0,0 +1,528 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt::{self, Write};
+
+use super::disasm::{copied_immediate, find_code};
+use super::{Instruction, Intcode, Opcode, Param};
+
+/// A control-flow graph of the code found in a program, along with some facts about it that are
+/// useful when reading the disassembly.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cfg {
+    /// Basic blocks indexed by their first address.
+    pub blocks: BTreeMap<usize, Block>,
+
+    /// Jumps whose target is read from memory, so can't be followed without running the program.
+    pub indirect_jumps: Vec<usize>,
+
+    /// Instructions that write to an address holding code.
+    pub code_writes: Vec<CodeWrite>,
+
+    pub subroutines: Vec<Subroutine>,
+}
+
+/// A run of instructions that is only ever entered at the top and left at the bottom.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Block {
+    pub instructions: Vec<Instruction>,
+    pub edges: Vec<Edge>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Edge {
+    pub target: usize,
+    pub kind: EdgeKind,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum EdgeKind {
+    /// Execution continues with the following instruction.
+    Fallthrough,
+
+    /// A jump to an immediate target.
+    Jump,
+
+    /// A jump to a subroutine.
+    Call,
+
+    /// From a call, direct or indirect, to the return address it pushed, where the subroutine is
+    /// expected to come back.
+    Return,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct CodeWrite {
+    /// The instruction doing the write.
+    pub address: usize,
+
+    /// The address written to.
+    pub target: usize,
+}
+
+/// A jump target that is called using the convention in the puzzle inputs: store the address of the
+/// following instruction relative to the relative base, then jump unconditionally. The subroutine
+/// returns by jumping to the stored address, which is an indirect jump through a relative
+/// parameter.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Subroutine {
+    pub entry: usize,
+
+    /// The jumps that call the subroutine.
+    pub callers: Vec<usize>,
+
+    /// The indirect jumps that return from it.
+    pub returns: Vec<usize>,
+
+    /// The blocks making up the subroutine, not counting those of any subroutines it calls.
+    pub blocks: Vec<usize>,
+}
+
+impl Block {
+    pub fn start(&self) -> usize {
+        self.instructions[0].address
+    }
+
+    /// The address following the last instruction.
+    pub fn end(&self) -> usize {
+        let last = self.last();
+        last.address + last.size()
+    }
+
+    pub fn last(&self) -> &Instruction {
+        self.instructions.last().unwrap()
+    }
+}
+
+/// Build a control-flow graph of the program in `Intcode::data`, using the same search for code as
+/// the disassembler.
+pub fn analyze(intcode: &Intcode) -> Cfg {
+    let data = &intcode.data;
+    let (code, _) = find_code(data);
+
+    let instructions: BTreeMap<usize, Instruction> = code
+        .into_iter()
+        .map(|address| {
+            (
+                address,
+                Instruction::decode(address, &data[address..]).unwrap(),
+            )
+        })
+        .collect();
+    let covered: HashSet<usize> = instructions
+        .values()
+        .flat_map(|instruction| instruction.address..instruction.address + instruction.size())
+        .collect();
+
+    // Blocks start at the entry point, at jump targets, after jumps and halts, and wherever code
+    // doesn't follow on from the previous instruction.
+    let mut leaders = BTreeSet::new();
+    let mut previous_end = None;
+
+    for (&address, instruction) in instructions.iter() {
+        if previous_end != Some(address) {
+            leaders.insert(address);
+        }
+        if let Some(target) = instruction.jump_target() {
+            if target >= 0 && instructions.contains_key(&(target as usize)) {
+                leaders.insert(target as usize);
+            }
+        }
+        if is_control(instruction) {
+            leaders.insert(address + instruction.size());
+        }
+        previous_end = Some(address + instruction.size());
+    }
+
+    let mut blocks: BTreeMap<usize, Block> = BTreeMap::new();
+    let mut current: Option<Block> = None;
+
+    for (&address, instruction) in instructions.iter() {
+        if leaders.contains(&address) {
+            if let Some(block) = current.take() {
+                blocks.insert(block.start(), block);
+            }
+        }
+
+        current
+            .get_or_insert_with(|| Block {
+                instructions: Vec::new(),
+                edges: Vec::new(),
+            })
+            .instructions
+            .push(*instruction);
+    }
+    if let Some(block) = current {
+        blocks.insert(block.start(), block);
+    }
+
+    let starts: HashSet<usize> = blocks.keys().copied().collect();
+    let mut indirect_jumps = Vec::new();
+    let mut calls: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+
+    for block in blocks.values_mut() {
+        let last = *block.last();
+        let next = block.end();
+
+        if last.opcode == Opcode::Halt {
+            continue;
+        }
+
+        if is_control(&last) {
+            match last.jump_target() {
+                Some(target) if target >= 0 && starts.contains(&(target as usize)) => {
+                    let target = target as usize;
+
+                    if last.is_terminal() && pushes_return_address(block, next) {
+                        calls.entry(target).or_default().push(last.address);
+                        block.edges.push(Edge {
+                            target,
+                            kind: EdgeKind::Call,
+                        });
+                        if starts.contains(&next) {
+                            block.edges.push(Edge {
+                                target: next,
+                                kind: EdgeKind::Return,
+                            });
+                        }
+                        continue;
+                    }
+
+                    block.edges.push(Edge {
+                        target,
+                        kind: EdgeKind::Jump,
+                    });
+                }
+                Some(_) => {}
+                None => {
+                    indirect_jumps.push(last.address);
+
+                    // A call through a function pointer, such as the day 25 input passes to
+                    // subroutines that iterate over a list.
+                    if last.is_terminal() && pushes_return_address(block, next) {
+                        if starts.contains(&next) {
+                            block.edges.push(Edge {
+                                target: next,
+                                kind: EdgeKind::Return,
+                            });
+                        }
+                        continue;
+                    }
+                }
+            }
+
+            if last.is_terminal() {
+                continue;
+            }
+        }
+
+        if starts.contains(&next) {
+            block.edges.push(Edge {
+                target: next,
+                kind: EdgeKind::Fallthrough,
+            });
+        }
+    }
+
+    let code_writes = instructions
+        .values()
+        .filter_map(|instruction| match instruction.output() {
+            Some(Param::Position(target))
+                if target >= 0 && covered.contains(&(target as usize)) =>
+            {
+                Some(CodeWrite {
+                    address: instruction.address,
+                    target: target as usize,
+                })
+            }
+            _ => None,
+        })
+        .collect();
+
+    let subroutines = calls
+        .into_iter()
+        .map(|(entry, callers)| {
+            let body = reachable(&blocks, entry);
+            let returns = body
+                .iter()
+                .map(|start| &blocks[start])
+                .filter(|block| {
+                    let last = block.last();
+                    is_control(last)
+                        && last.is_terminal()
+                        && matches!(last.params().get(1), Some(Param::Relative(_)))
+                        && !pushes_return_address(block, block.end())
+                })
+                .map(|block| block.last().address)
+                .collect();
+
+            Subroutine {
+                entry,
+                callers,
+                returns,
+                blocks: body,
+            }
+        })
+        .collect();
+
+    Cfg {
+        blocks,
+        indirect_jumps,
+        code_writes,
+        subroutines,
+    }
+}
+
+fn is_control(instruction: &Instruction) -> bool {
+    matches!(
+        instruction.opcode,
+        Opcode::JumpIfTrue | Opcode::JumpIfFalse | Opcode::Halt
+    )
+}
+
+/// True if the block copies `return_address` into memory relative to the relative base before it
+/// jumps.
+fn pushes_return_address(block: &Block, return_address: usize) -> bool {
+    block.instructions.iter().any(|instruction| {
+        copied_immediate(instruction) == Some(return_address as i64)
+            && matches!(instruction.output(), Some(Param::Relative(_)))
+    })
+}
+
+/// The blocks reachable from `start` without following calls, in address order.
+pub(super) fn reachable(blocks: &BTreeMap<usize, Block>, start: usize) -> Vec<usize> {
+    let mut seen = BTreeSet::new();
+    let mut pending = vec![start];
+
+    while let Some(address) = pending.pop() {
+        if !seen.insert(address) {
+            continue;
+        }
+
+        pending.extend(
+            blocks[&address]
+                .edges
+                .iter()
+                .filter(|edge| edge.kind != EdgeKind::Call)
+                .map(|edge| edge.target),
+        );
+    }
+
+    seen.into_iter().collect()
+}
+
+impl Cfg {
+    /// Render the graph in Graphviz DOT format. Subroutine entries have a double border, blocks
+    /// ending in an indirect jump are red and blocks containing code that is written to are
+    /// orange.
+    pub fn to_dot(&self) -> String {
+        let entries: HashSet<usize> = self.subroutines.iter().map(|s| s.entry).collect();
+        let written: HashSet<usize> = self.code_writes.iter().map(|w| w.target).collect();
+        let mut output = String::new();
+
+        writeln!(output, "digraph intcode {{").unwrap();
+        writeln!(output, "    node [shape=box, fontname=\"monospace\"];").unwrap();
+
+        for (start, block) in self.blocks.iter() {
+            let mut attributes = vec![format!(
+                "label=\"{}\"",
+                block
+                    .instructions
+                    .iter()
+                    .map(|instruction| format!("{:04}: {}\\l", instruction.address, instruction))
+                    .collect::<String>()
+            )];
+
+            if entries.contains(start) {
+                attributes.push("peripheries=2".to_string());
+            }
+            if self.indirect_jumps.contains(&block.last().address) {
+                attributes.push("color=red".to_string());
+            } else if (*start..block.end()).any(|address| written.contains(&address)) {
+                attributes.push("color=orange".to_string());
+            }
+
+            writeln!(output, "    n{} [{}];", start, attributes.join(", ")).unwrap();
+
+            for edge in block.edges.iter() {
+                let style = match edge.kind {
+                    EdgeKind::Fallthrough => " [style=dashed]",
+                    EdgeKind::Jump => "",
+                    EdgeKind::Call => " [label=\"call\"]",
+                    EdgeKind::Return => " [label=\"return\", style=dashed]",
+                };
+                writeln!(output, "    n{} -> n{}{};", start, edge.target, style).unwrap();
+            }
+        }
+
+        output.push_str("}\n");
+        output
+    }
+}
+
+/// ```text
+/// 0000..0009: call 0010, return 0009
+/// 0009..0010
+/// 0010..0015: indirect
+/// subroutine 0010: called from 0006, returns from 0012
+/// code write at 0002 to 0001
+/// ```
+impl fmt::Display for Cfg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (start, block) in self.blocks.iter() {
+            write!(f, "{:04}..{:04}", start, block.end())?;
+
+            let mut edges: Vec<String> = block
+                .edges
+                .iter()
+                .map(|edge| {
+                    let kind = match edge.kind {
+                        EdgeKind::Fallthrough => "next",
+                        EdgeKind::Jump => "jump",
+                        EdgeKind::Call => "call",
+                        EdgeKind::Return => "return",
+                    };
+                    format!("{} {:04}", kind, edge.target)
+                })
+                .collect();
+            if self.indirect_jumps.contains(&block.last().address) {
+                edges.push("indirect".to_string());
+            }
+
+            if edges.is_empty() {
+                writeln!(f)?;
+            } else {
+                writeln!(f, ": {}", edges.join(", "))?;
+            }
+        }
+
+        let list = |addresses: &[usize]| {
+            addresses
+                .iter()
+                .map(|address| format!("{:04}", address))
+                .collect::<Vec<String>>()
+                .join(", ")
+        };
+
+        for subroutine in self.subroutines.iter() {
+            writeln!(
+                f,
+                "subroutine {:04}: called from {}, returns from {}",
+                subroutine.entry,
+                list(&subroutine.callers),
+                list(&subroutine.returns)
+            )?;
+        }
+
+        for write in self.code_writes.iter() {
+            writeln!(
+                f,
+                "code write at {:04} to {:04}",
+                write.address, write.target
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn subroutine_program() -> Intcode {
+        // Push a return address, call a subroutine, then halt. The subroutine returns through an
+        // indirect jump.
+        Intcode::new(vec![
+            109, 20, 21101, 0, 9, 0, 1105, 1, 10, 99, 204, 1, 2106, 0, 0,
+        ])
+    }
+
+    #[test]
+    fn subroutine() {
+        let cfg = analyze(&subroutine_program());
+
+        assert_eq!(
+            vec![0, 9, 10],
+            cfg.blocks.keys().copied().collect::<Vec<usize>>()
+        );
+        assert_eq!(
+            vec![
+                Edge {
+                    target: 10,
+                    kind: EdgeKind::Call,
+                },
+                Edge {
+                    target: 9,
+                    kind: EdgeKind::Return,
+                },
+            ],
+            cfg.blocks[&0].edges,
+        );
+        assert_eq!(vec![12], cfg.indirect_jumps);
+        assert_eq!(
+            vec![Subroutine {
+                entry: 10,
+                callers: vec![6],
+                returns: vec![12],
+                blocks: vec![10],
+            }],
+            cfg.subroutines,
+        );
+        assert_eq!(
+            "0000..0009: call 0010, return 0009
+0009..0010
+0010..0015: indirect
+subroutine 0010: called from 0006, returns from 0012
+",
+            cfg.to_string(),
+        );
+    }
+
+    #[test]
+    fn self_modifying() {
+        // Outputs 1, then overwrites the output instruction's parameter with 2 and jumps back.
+        let cfg = analyze(&Intcode::new(vec![104, 1, 1101, 1, 1, 1, 1005, 1, 0, 99]));
+
+        assert_eq!(
+            vec![CodeWrite {
+                address: 2,
+                target: 1,
+            }],
+            cfg.code_writes,
+        );
+        assert_eq!(
+            "0000..0009: jump 0000, next 0009
+0009..0010
+code write at 0002 to 0001
+",
+            cfg.to_string(),
+        );
+    }
+
+    #[test]
+    fn dot() {
+        assert_eq!(
+            "digraph intcode {
+    node [shape=box, fontname=\"monospace\"];
+    n0 [label=\"0000: ARB #20\\l0002: ADD #0, #9 -> [rb+0]\\l0006: JT #1, #10\\l\"];
+    n0 -> n10 [label=\"call\"];
+    n0 -> n9 [label=\"return\", style=dashed];
+    n9 [label=\"0009: HLT\\l\"];
+    n10 [label=\"0010: OUT [rb+1]\\l0012: JF #0, [rb+0]\\l\", peripheries=2, color=red];
+}
+",
+            analyze(&subroutine_program()).to_dot(),
+        );
+    }
+
+    #[test]
+    fn analyze_inputs() {
+        let intcode: Intcode = include_str!("../day9/input.txt").parse().unwrap();
+        let cfg = analyze(&intcode);
+
+        assert!(!cfg.subroutines.is_empty());
+        for subroutine in cfg.subroutines.iter() {
+            assert!(subroutine.blocks.contains(&subroutine.entry));
+            assert!(!subroutine.returns.is_empty());
+        }
+    }
+}
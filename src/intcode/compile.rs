@@ -0,0 +1,339 @@
+use std::fmt::Write;
+
+use super::disasm::find_code;
+use super::{Instruction, Intcode, Opcode, Param};
+
+/// Translate the program in `intcode.data` into the source of a Rust module that runs it natively.
+/// The module is meant to be placed in this crate, and exposes
+///
+/// ```text
+/// pub const PROGRAM: [i64; N];
+/// pub fn run<I: IntcodeInput, O: IntcodeOutput>(intcode: &mut Intcode<I, O>)
+///     -> Result<Response, VmError>;
+/// ```
+///
+/// where `run` behaves exactly like `Intcode::run` on a plain machine loaded with `PROGRAM`. Only
+/// the instructions found by following control flow from address 0 are compiled, and each is only
+/// used while its words in memory are unchanged. Anything else, including self-modified code, is
+/// left to the interpreter one instruction at a time.
+///
+/// The machine's `Arithmetic` is respected, but compiled instructions bypass everything else that
+/// can be attached to it: they aren't seen by a tracer, profiler or session recorder, and they run
+/// with their standard meaning even when a `Dialect` overrides their opcode. Only run compiled code
+/// on a machine with none of these attached, unless that's what's wanted.
+pub fn compile(intcode: &Intcode) -> String {
+    let data = &intcode.data;
+    let (code, _) = find_code(data);
+    let mut addresses: Vec<usize> = code.into_iter().collect();
+    addresses.sort_unstable();
+
+    let mut output = String::new();
+
+    writeln!(
+        output,
+        "//! Compiled from a {} word Intcode program by `cargo run compile`.
+#![allow(unused_parens, clippy::all)]
+
+use crate::intcode::io::{{IntcodeInput, IntcodeOutput}};
+use crate::intcode::{{Intcode, Response, VmError}};
+
+#[rustfmt::skip]
+pub const PROGRAM: [i64; {}] = [{}];
+
+pub fn run<I: IntcodeInput, O: IntcodeOutput>(
+    intcode: &mut Intcode<I, O>,
+) -> Result<Response, VmError> {{
+    loop {{
+        let cursor = intcode.cursor();
+        let rb = intcode.relative_base();
+
+        match cursor {{",
+        data.len(),
+        data.len(),
+        data.iter()
+            .map(|word| word.to_string())
+            .collect::<Vec<String>>()
+            .join(", ")
+    )
+    .unwrap();
+
+    for address in addresses {
+        let instruction = Instruction::decode(address, &data[address..]).unwrap();
+
+        // Leave constant negative addresses to the interpreter, which reports them.
+        if instruction
+            .params()
+            .iter()
+            .any(|param| matches!(param, Param::Position(a) if *a < 0))
+        {
+            continue;
+        }
+
+        writeln!(
+            output,
+            "            // {}\n            {} if unchanged(intcode, {}, {}) => {{",
+            instruction,
+            address,
+            address,
+            instruction.size()
+        )
+        .unwrap();
+        output.push_str(&compile_instruction(&instruction));
+        output.push_str("            }\n");
+    }
+
+    output.push_str(
+        "            _ => {
+                if let Some(response) = intcode.step()? {
+                    return Ok(response);
+                }
+            }
+        }
+    }
+}
+
+fn unchanged<I, O>(intcode: &Intcode<I, O>, address: usize, size: usize) -> bool {
+    intcode.data.get(address..address + size) == Some(&PROGRAM[address..address + size])
+}
+
+fn address(cursor: usize, instruction: i64, address: i64) -> Result<usize, VmError> {
+    if address < 0 {
+        Err(VmError::NegativeAddress {
+            cursor,
+            instruction,
+            address,
+        })
+    } else {
+        Ok(address as usize)
+    }
+}
+",
+    );
+
+    output
+}
+
+/// The body of a match arm executing one instruction. Parameters are resolved in the same order as
+/// the interpreter so that faults are reported identically.
+fn compile_instruction(instruction: &Instruction) -> String {
+    let (cursor, raw) = (instruction.address, instruction.raw);
+    let next = cursor + instruction.size();
+    let params = instruction.params();
+
+    let read = |param: Param| match param {
+        Param::Position(address) => format!("intcode.get({})", address),
+        Param::Immediate(value) => format!("({})", value),
+        Param::Relative(offset) => format!(
+            "intcode.get(address({}, {}, intcode.add_words(rb, {})?)?)",
+            cursor, raw, offset
+        ),
+    };
+    let target = |param: Param| match param {
+        Param::Relative(offset) => format!(
+            "address({}, {}, intcode.add_words(rb, {})?)?",
+            cursor, raw, offset
+        ),
+        param => param.value().to_string(),
+    };
+    let store = |value: String| {
+        format!(
+            "                let value = {};
+                intcode.set({}, value);
+                intcode.set_cursor({});\n",
+            value,
+            target(params[2]),
+            next
+        )
+    };
+
+    let body = match instruction.opcode {
+        Opcode::Add => store(format!(
+            "intcode.add_words({}, {})?",
+            read(params[0]),
+            read(params[1])
+        )),
+        Opcode::Mul => store(format!(
+            "intcode.mul_words({}, {})?",
+            read(params[0]),
+            read(params[1])
+        )),
+        Opcode::LessThan => store(format!(
+            "({} < {}) as i64",
+            read(params[0]),
+            read(params[1])
+        )),
+        Opcode::Equals => store(format!(
+            "({} == {}) as i64",
+            read(params[0]),
+            read(params[1])
+        )),
+        Opcode::Input => format!(
+            "                let value = match intcode.input.read() {{
+                    Some(value) => value,
+                    None => return Ok(Response::InputRequired),
+                }};
+                intcode.set({}, value);
+                intcode.set_cursor({});\n",
+            target(params[0]),
+            next
+        ),
+        Opcode::Output => format!(
+            "                let value = {};
+                intcode.output.write(value);
+                intcode.set_cursor({});\n",
+            read(params[0]),
+            next
+        ),
+        Opcode::JumpIfTrue | Opcode::JumpIfFalse => format!(
+            "                if {} {} 0 {{
+                    let target = {};
+                    intcode.set_cursor(address({}, {}, target)?);
+                }} else {{
+                    intcode.set_cursor({});
+                }}\n",
+            read(params[0]),
+            if instruction.opcode == Opcode::JumpIfTrue {
+                "!="
+            } else {
+                "=="
+            },
+            read(params[1]),
+            cursor,
+            raw,
+            next
+        ),
+        Opcode::AdjustRelativeBase => format!(
+            "                let value = {};
+                let value = intcode.add_words(rb, value)?;
+                intcode.set_relative_base(value);
+                intcode.set_cursor({});\n",
+            read(params[0]),
+            next
+        ),
+        Opcode::Halt => "                return Ok(Response::Terminated);\n".to_string(),
+    };
+
+    format!("                intcode.steps += 1;\n{}", body)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::process::Command;
+
+    #[test]
+    fn compile_countdown() {
+        // Reads a number, then counts it down to zero, printing each value.
+        let source = compile(&Intcode::new(vec![
+            3, 12, 4, 12, 1001, 12, -1, 12, 1005, 12, 2, 99, 0,
+        ]));
+
+        assert!(source.contains(
+            "pub const PROGRAM: [i64; 13] = [3, 12, 4, 12, 1001, 12, -1, 12, 1005, 12, 2, 99, 0];"
+        ));
+        assert!(source.contains(
+            "            // ADD [12], #-1 -> [12]
+            4 if unchanged(intcode, 4, 4) => {
+                intcode.steps += 1;
+                let value = intcode.add_words(intcode.get(12), (-1))?;
+                intcode.set(12, value);
+                intcode.set_cursor(8);
+            }
+"
+        ));
+        assert!(source.contains(
+            "            // JT [12], #2
+            8 if unchanged(intcode, 8, 3) => {
+                intcode.steps += 1;
+                if intcode.get(12) != 0 {
+                    let target = (2);
+                    intcode.set_cursor(address(8, 1005, target)?);
+                } else {
+                    intcode.set_cursor(11);
+                }
+            }
+"
+        ));
+    }
+
+    /// Compile every Intcode input in the repo, build the results with `rustc` alongside the
+    /// interpreter, and check that both behave identically on the benchmark workloads.
+    #[test]
+    #[ignore]
+    fn compiled_matches_interpreter() {
+        let root = env!("CARGO_MANIFEST_DIR");
+        let dir = env::temp_dir().join(format!("intcode-compile-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let mut harness = format!(
+            "#[allow(dead_code)]\n#[path = \"{}/src/intcode/mod.rs\"]\nmod intcode;\n",
+            root
+        );
+        let mut dispatch = String::new();
+
+        for workload in super::super::bench::workloads() {
+            let intcode: Intcode = workload.program.parse().unwrap();
+            fs::write(
+                dir.join(format!("day{}.rs", workload.day)),
+                compile(&intcode),
+            )
+            .unwrap();
+
+            writeln!(harness, "mod day{};", workload.day).unwrap();
+            writeln!(
+                dispatch,
+                "            {} => day{}::run(&mut compiled),",
+                workload.day, workload.day
+            )
+            .unwrap();
+        }
+
+        write!(
+            harness,
+            "
+fn main() {{
+    for workload in intcode::bench::workloads() {{
+        let mut template: intcode::Intcode = workload.program.parse().unwrap();
+        for &(address, value) in workload.patches {{
+            template.set(address, value);
+        }}
+
+        let mut interpreted = template.clone().with_input(&workload.input);
+        let mut compiled = template.with_input(&workload.input);
+
+        let expected = interpreted.run();
+        let actual = match workload.day {{
+{}            _ => unreachable!(),
+        }};
+
+        assert_eq!(expected, actual, \"day {{}}\", workload.day);
+        assert_eq!(interpreted.output, compiled.output, \"day {{}}\", workload.day);
+        assert_eq!(interpreted.data, compiled.data, \"day {{}}\", workload.day);
+        assert_eq!(interpreted.steps, compiled.steps, \"day {{}}\", workload.day);
+        assert_eq!(interpreted.cursor(), compiled.cursor(), \"day {{}}\", workload.day);
+    }}
+}}
+",
+            dispatch
+        )
+        .unwrap();
+        fs::write(dir.join("main.rs"), harness).unwrap();
+
+        let status = Command::new(env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string()))
+            .args(["--edition", "2018", "--cap-lints", "allow", "-o"])
+            .arg(dir.join("harness"))
+            .arg(dir.join("main.rs"))
+            .status()
+            .unwrap();
+        assert!(status.success(), "compiled programs failed to build");
+
+        let status = Command::new(dir.join("harness")).status().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(
+            status.success(),
+            "compiled programs differ from the interpreter"
+        );
+    }
+}
@@ -0,0 +1,571 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
+use std::io::{BufRead, Write};
+
+use super::history::History;
+use super::{Intcode, Response};
+
+const HELP: &str = "\
+step [n]            Execute the next instruction, or the next n instructions
+next                Like step, but run an unconditional jump through to the following address
+continue            Run until a breakpoint, watchpoint, input request or halt
+back [n]            Undo the last instruction, or the last n instructions
+reverse             Run backwards until a breakpoint, watchpoint or the start of the history
+goto <step>         Rewind or run to the point where step instructions have been executed
+break <addr>        Stop before executing the instruction at addr
+delete <addr>       Remove the breakpoint at addr
+watch <addr>        Stop whenever the value at addr changes
+unwatch <addr>      Remove the watchpoint at addr
+info                Show the cursor, relative base, step count, memory use and breakpoints
+x <addr> [count]    Show count memory cells starting at addr
+set <addr> <value>  Write value to addr
+jump <addr>         Move the cursor to addr
+base <value>        Set the relative base
+input <values...>   Queue numeric input values
+ascii <text>        Queue text as ASCII input, followed by a newline
+io                  Show the pending input and output queues
+output              Print and clear the output queue
+save <path>         Save the machine state to a snapshot file
+load <path>         Replace the machine state with a snapshot file
+quit                Exit the debugger";
+
+/// An interactive debugger wrapping an `Intcode` machine. Commands are read one per line; most have
+/// a single letter shorthand (`s`, `n`, `c`, `b`, `d`, `w`, `i`, `q`).
+///
+/// Every instruction executed is recorded in a `History` so that it can be undone. Changing memory,
+/// the cursor or the relative base by hand, or loading a snapshot, starts a new history.
+pub struct Debugger {
+    pub intcode: Intcode,
+    breakpoints: BTreeSet<usize>,
+    watchpoints: BTreeMap<usize, i64>,
+    history: History,
+}
+
+impl Debugger {
+    pub fn new(intcode: Intcode) -> Self {
+        Self {
+            intcode,
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeMap::new(),
+            history: History::default(),
+        }
+    }
+
+    /// Record execution in `history` rather than one with the default memory budget.
+    pub fn with_history(mut self, history: History) -> Self {
+        self.history = history;
+        self
+    }
+
+    /// Run a read-eval-print loop until `quit` or the end of the input.
+    pub fn repl(&mut self, input: impl BufRead, mut output: impl Write) -> Result<(), String> {
+        let io_error = |e: std::io::Error| e.to_string();
+
+        writeln!(output, "{}", self.location()).map_err(io_error)?;
+        write!(output, "> ").map_err(io_error)?;
+        output.flush().map_err(io_error)?;
+
+        for line in input.lines() {
+            let line = line.map_err(io_error)?;
+
+            match self.execute(&line) {
+                Ok(Some(result)) => writeln!(output, "{}", result),
+                Ok(None) => return Ok(()),
+                Err(e) => writeln!(output, "Error: {}", e),
+            }
+            .map_err(io_error)?;
+
+            write!(output, "> ").map_err(io_error)?;
+            output.flush().map_err(io_error)?;
+        }
+
+        Ok(())
+    }
+
+    /// Execute a single command, returning the text to display or `None` if the user asked to
+    /// quit.
+    pub fn execute(&mut self, line: &str) -> Result<Option<String>, String> {
+        let mut words = line.split_whitespace();
+        let command = match words.next() {
+            Some(command) => command,
+            None => return Ok(Some(String::new())),
+        };
+        let args: Vec<&str> = words.collect();
+
+        let result = match command {
+            "s" | "step" => {
+                let count = args.first().map_or(Ok(1), |arg| parse(arg))?;
+                self.step(count)?
+            }
+            "n" | "next" => self.next()?,
+            "c" | "continue" => self.resume(None)?,
+            "back" => {
+                let count = args.first().map_or(Ok(1), |arg| parse(arg))?;
+                self.back(count)
+            }
+            "reverse" => self.reverse(),
+            "goto" => self.goto(parse(arg(&args, 0)?)?)?,
+            "b" | "break" => {
+                let address = parse(arg(&args, 0)?)?;
+                self.breakpoints.insert(address);
+                format!("Breakpoint at {:04}", address)
+            }
+            "d" | "delete" => {
+                let address = parse(arg(&args, 0)?)?;
+                if !self.breakpoints.remove(&address) {
+                    return Err(format!("No breakpoint at {:04}", address));
+                }
+                format!("Deleted breakpoint at {:04}", address)
+            }
+            "w" | "watch" => {
+                let address = parse(arg(&args, 0)?)?;
+                let value = self.intcode.get(address);
+                self.watchpoints.insert(address, value);
+                format!("Watching {:04} = {}", address, value)
+            }
+            "unwatch" => {
+                let address = parse(arg(&args, 0)?)?;
+                if self.watchpoints.remove(&address).is_none() {
+                    return Err(format!("No watchpoint at {:04}", address));
+                }
+                format!("Stopped watching {:04}", address)
+            }
+            "i" | "info" => self.info(),
+            "x" => {
+                let address: usize = parse(arg(&args, 0)?)?;
+                let count: usize = args.get(1).map_or(Ok(8), |arg| parse(arg))?;
+                let end = address.checked_add(count).ok_or_else(|| {
+                    format!("{} words from {} is past the end of memory", count, address)
+                })?;
+                (address..end)
+                    .map(|a| format!("{:04}: {}", a, self.intcode.get(a)))
+                    .collect::<Vec<String>>()
+                    .join("\n")
+            }
+            "set" => {
+                let address = parse(arg(&args, 0)?)?;
+                let value = parse(arg(&args, 1)?)?;
+                self.intcode.set(address, value);
+                self.history.clear();
+                format!("{:04} = {}", address, value)
+            }
+            "jump" => {
+                self.intcode.set_cursor(parse(arg(&args, 0)?)?);
+                self.history.clear();
+                self.location()
+            }
+            "base" => {
+                let value = parse(arg(&args, 0)?)?;
+                self.intcode.set_relative_base(value);
+                self.history.clear();
+                format!("relative base: {}", value)
+            }
+            "input" => {
+                for arg in args.iter() {
+                    self.intcode.input.push_back(parse(arg)?);
+                }
+                format!("Input: {:?}", self.intcode.input)
+            }
+            "ascii" => {
+                let text = line.trim_start()[command.len()..].trim_start();
+                self.intcode.input_str(text);
+                self.intcode.input.push_back('\n' as i64);
+                format!("Queued {} characters", text.len() + 1)
+            }
+            "io" => format!(
+                "Input: {:?}\nOutput: {:?}",
+                self.intcode.input, self.intcode.output
+            ),
+            "output" => {
+                let output = self.intcode.output_string();
+                self.intcode.output.clear();
+                output
+            }
+            "save" => {
+                let path = arg(&args, 0)?;
+                self.intcode.save_file(path)?;
+                format!("Saved to {}", path)
+            }
+            "load" => {
+                let path = arg(&args, 0)?;
+                self.intcode = Intcode::load_file(path)?;
+                self.history.clear();
+                format!("Loaded {}\n{}", path, self.location())
+            }
+            "h" | "help" => HELP.to_string(),
+            "q" | "quit" => return Ok(None),
+            _ => return Err(format!("Unknown command {}, try help", command)),
+        };
+
+        Ok(Some(result))
+    }
+
+    fn step(&mut self, count: usize) -> Result<String, String> {
+        let mut output = String::new();
+
+        for _ in 0..count {
+            if let Some(stop) = self.step_once()? {
+                writeln!(output, "{}", stop).unwrap();
+                break;
+            }
+        }
+
+        output.push_str(&self.location());
+        Ok(output)
+    }
+
+    /// Step over a subroutine call. Calls are unconditional jumps made after pushing the address of
+    /// the following instruction, so run until execution comes back there.
+    fn next(&mut self) -> Result<String, String> {
+        match self.intcode.instruction(self.intcode.cursor()) {
+            Ok(instruction) if instruction.is_terminal() && instruction.jump_target().is_some() => {
+                self.resume(Some(instruction.address + instruction.size()))
+            }
+            _ => self.step(1),
+        }
+    }
+
+    /// Run until something interesting happens, optionally stopping at an additional address.
+    fn resume(&mut self, until: Option<usize>) -> Result<String, String> {
+        let stop = loop {
+            if let Some(stop) = self.step_once()? {
+                break stop;
+            }
+
+            let cursor = self.intcode.cursor();
+            if self.breakpoints.contains(&cursor) {
+                break format!("Breakpoint at {:04}", cursor);
+            }
+            if until == Some(cursor) {
+                break String::new();
+            }
+        };
+
+        Ok(if stop.is_empty() {
+            self.location()
+        } else {
+            format!("{}\n{}", stop, self.location())
+        })
+    }
+
+    fn back(&mut self, count: usize) -> String {
+        let mut output = String::new();
+
+        for _ in 0..count {
+            if !self.history.step_back(&mut self.intcode) {
+                writeln!(output, "At the start of the history").unwrap();
+                break;
+            }
+        }
+
+        self.sync_watchpoints();
+        output.push_str(&self.location());
+        output
+    }
+
+    /// Undo instructions until the cursor reaches a breakpoint or a watched value changes.
+    fn reverse(&mut self) -> String {
+        let stop = loop {
+            if !self.history.step_back(&mut self.intcode) {
+                break "At the start of the history".to_string();
+            }
+
+            let cursor = self.intcode.cursor();
+            let changes = self.watchpoint_changes(&format!("undoing {:04}", cursor));
+            if !changes.is_empty() {
+                break changes.join("\n");
+            }
+            if self.breakpoints.contains(&cursor) {
+                break format!("Breakpoint at {:04}", cursor);
+            }
+        };
+
+        format!("{}\n{}", stop, self.location())
+    }
+
+    /// Rewind to an earlier step, or run forwards to a later one.
+    fn goto(&mut self, step: u64) -> Result<String, String> {
+        if step <= self.intcode.steps {
+            self.history.goto(&mut self.intcode, step)?;
+            self.sync_watchpoints();
+            return Ok(self.location());
+        }
+
+        while self.intcode.steps < step {
+            if let Some(stop) = self.step_once()? {
+                return Ok(format!("{}\n{}", stop, self.location()));
+            }
+        }
+
+        Ok(self.location())
+    }
+
+    /// Execute one instruction, describing why execution should stop if it should.
+    fn step_once(&mut self) -> Result<Option<String>, String> {
+        let cursor = self.intcode.cursor();
+
+        match self.history.step(&mut self.intcode)? {
+            Some(Response::Terminated) => return Ok(Some("Program halted".to_string())),
+            Some(Response::InputRequired) => {
+                return Ok(Some(format!(
+                    "Input required at {:04}, output so far: {:?}",
+                    cursor, self.intcode.output
+                )))
+            }
+            // Single steps are never cut short by a budget.
+            None | Some(Response::BudgetExhausted) => {}
+        }
+
+        let changes = self.watchpoint_changes(&format!("written by {:04}", cursor));
+
+        Ok(if changes.is_empty() {
+            None
+        } else {
+            Some(changes.join("\n"))
+        })
+    }
+
+    /// Describe and remember any changes to watched values, explaining each with `cause`.
+    fn watchpoint_changes(&mut self, cause: &str) -> Vec<String> {
+        let mut changes = Vec::new();
+
+        for (&address, value) in self.watchpoints.iter_mut() {
+            let current = self.intcode.get(address);
+            if current != *value {
+                changes.push(format!(
+                    "Watchpoint {:04}: {} -> {} ({})",
+                    address, value, current, cause
+                ));
+                *value = current;
+            }
+        }
+
+        changes
+    }
+
+    fn sync_watchpoints(&mut self) {
+        for (&address, value) in self.watchpoints.iter_mut() {
+            *value = self.intcode.get(address);
+        }
+    }
+
+    fn info(&self) -> String {
+        format!(
+            "cursor: {:04}\nrelative base: {}\nsteps: {}\nmemory: {}\nhistory: {}\nbreakpoints: {:?}\nwatchpoints: {:?}\n{}",
+            self.intcode.cursor(),
+            self.intcode.relative_base(),
+            self.intcode.steps,
+            self.intcode.memory_stats(),
+            match self.history.earliest() {
+                Some(earliest) => format!(
+                    "steps {} to {}, {} bytes",
+                    earliest,
+                    self.intcode.steps,
+                    self.history.bytes()
+                ),
+                None => "empty".to_string(),
+            },
+            self.breakpoints,
+            self.watchpoints.keys().collect::<Vec<&usize>>(),
+            self.location()
+        )
+    }
+
+    /// The instruction about to be executed.
+    fn location(&self) -> String {
+        let cursor = self.intcode.cursor();
+
+        match self.intcode.instruction(cursor) {
+            Ok(instruction) => format!("{:04}: {}", cursor, instruction),
+            Err(e) => format!("{:04}: {}", cursor, e),
+        }
+    }
+}
+
+fn arg<'a>(args: &[&'a str], index: usize) -> Result<&'a str, String> {
+    args.get(index)
+        .copied()
+        .ok_or_else(|| "Missing argument, try help".to_string())
+}
+
+fn parse<T: std::str::FromStr>(raw: &str) -> Result<T, String> {
+    raw.parse().map_err(|_| format!("Invalid number {}", raw))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn debugger() -> Debugger {
+        // Reads a number, then counts it down to zero, printing each value.
+        Debugger::new(Intcode::new(vec![
+            3, 12, 4, 12, 1001, 12, -1, 12, 1005, 12, 2, 99, 0,
+        ]))
+    }
+
+    #[test]
+    fn step_and_input() {
+        let mut debugger = debugger();
+
+        assert_eq!(
+            Ok(Some(
+                "Input required at 0000, output so far: []\n0000: IN -> [12]".to_string()
+            )),
+            debugger.execute("step"),
+        );
+        assert!(debugger.execute("input 2").is_ok());
+        assert_eq!(
+            Ok(Some("0004: ADD [12], #-1 -> [12]".to_string())),
+            debugger.execute("s 2"),
+        );
+        assert_eq!(
+            Ok(Some("Input: []\nOutput: [2]".to_string())),
+            debugger.execute("io"),
+        );
+        assert_eq!(
+            Ok(Some("0002: OUT [12]".to_string())),
+            debugger.execute("jump 2"),
+        );
+        assert_eq!(
+            Ok(Some("0011: 99\n0012: 2".to_string())),
+            debugger.execute("x 11 2"),
+        );
+        assert_eq!(
+            Err(format!(
+                "2 words from {} is past the end of memory",
+                usize::MAX
+            )),
+            debugger.execute(&format!("x {} 2", usize::MAX)),
+        );
+    }
+
+    #[test]
+    fn breakpoints() {
+        let mut debugger = debugger();
+        debugger.intcode.input.push_back(3);
+
+        assert!(debugger.execute("b 8").is_ok());
+        assert_eq!(
+            Ok(Some("Breakpoint at 0008\n0008: JT [12], #2".to_string())),
+            debugger.execute("c"),
+        );
+        assert_eq!(2, debugger.intcode.get(12));
+        assert!(debugger.execute("delete 8").is_ok());
+        assert_eq!(
+            Ok(Some("Program halted\n0011: HLT".to_string())),
+            debugger.execute("continue"),
+        );
+        assert_eq!(vec![3, 2, 1], debugger.intcode.output);
+        assert!(debugger.execute("delete 8").is_err());
+    }
+
+    #[test]
+    fn watchpoints() {
+        let mut debugger = debugger();
+        debugger.intcode.input.push_back(3);
+
+        assert_eq!(
+            Ok(Some("Watching 0012 = 0".to_string())),
+            debugger.execute("w 12")
+        );
+        assert_eq!(
+            Ok(Some(
+                "Watchpoint 0012: 0 -> 3 (written by 0000)\n0002: OUT [12]".to_string()
+            )),
+            debugger.execute("c"),
+        );
+        assert_eq!(
+            Ok(Some(
+                "Watchpoint 0012: 3 -> 2 (written by 0004)\n0008: JT [12], #2".to_string()
+            )),
+            debugger.execute("c"),
+        );
+    }
+
+    #[test]
+    fn reverse() {
+        let mut debugger = debugger();
+        debugger.intcode.input.push_back(3);
+
+        assert!(debugger.execute("b 8").is_ok());
+        assert!(debugger.execute("c").is_ok());
+        assert!(debugger.execute("c").is_ok());
+        assert_eq!(
+            Ok(Some("0004: ADD [12], #-1 -> [12]".to_string())),
+            debugger.execute("back"),
+        );
+        assert_eq!(2, debugger.intcode.get(12));
+
+        assert!(debugger.execute("w 12").is_ok());
+        assert_eq!(
+            Ok(Some("Breakpoint at 0008\n0008: JT [12], #2".to_string())),
+            debugger.execute("reverse"),
+        );
+        assert_eq!(
+            Ok(Some(
+                "Watchpoint 0012: 2 -> 3 (undoing 0004)\n0004: ADD [12], #-1 -> [12]".to_string()
+            )),
+            debugger.execute("reverse"),
+        );
+        assert_eq!(
+            Ok(Some(
+                "Watchpoint 0012: 3 -> 0 (undoing 0000)\n0000: IN -> [12]".to_string()
+            )),
+            debugger.execute("reverse"),
+        );
+        assert_eq!(
+            Ok(Some(
+                "At the start of the history\n0000: IN -> [12]".to_string()
+            )),
+            debugger.execute("reverse"),
+        );
+        assert_eq!(
+            Ok(Some("Input: [3]\nOutput: []".to_string())),
+            debugger.execute("io"),
+        );
+
+        assert!(debugger.execute("unwatch 12").is_ok());
+        assert_eq!(
+            Ok(Some("0002: OUT [12]".to_string())),
+            debugger.execute("goto 7"),
+        );
+        assert_eq!(vec![3, 2], debugger.intcode.output);
+        assert_eq!(
+            Ok(Some("0004: ADD [12], #-1 -> [12]".to_string())),
+            debugger.execute("goto 2"),
+        );
+        assert_eq!(vec![3], debugger.intcode.output);
+    }
+
+    #[test]
+    fn next_over_call() {
+        // Push a return address, call a subroutine that outputs 7, then halt.
+        let mut debugger = Debugger::new(Intcode::new(vec![
+            109, 20, 21101, 0, 9, 0, 1105, 1, 10, 99, 104, 7, 2106, 0, 0,
+        ]));
+
+        assert!(debugger.execute("s 2").is_ok());
+        assert_eq!(Ok(Some("0009: HLT".to_string())), debugger.execute("n"));
+        assert_eq!(vec![7], debugger.intcode.output);
+    }
+
+    #[test]
+    fn repl() {
+        let mut output = Vec::new();
+        debugger()
+            .repl(
+                "input 1\nx 12 1\nbogus\nquit\nstep\n".as_bytes(),
+                &mut output,
+            )
+            .unwrap();
+
+        assert_eq!(
+            "0000: IN -> [12]
+> Input: [1]
+> 0012: 0
+> Error: Unknown command bogus, try help
+> ",
+            String::from_utf8(output).unwrap(),
+        );
+    }
+}
@@ -0,0 +1,214 @@
+use super::io::{IntcodeInput, IntcodeOutput};
+use super::{Instruction, Intcode, Opcode, Param, Response, VmError};
+
+/// How an `Intcode` machine executes instructions. Both engines behave identically, including the
+/// errors they raise.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Engine {
+    /// Decode each instruction from memory every time it is executed.
+    Interpreter,
+
+    /// Decode each instruction the first time it is executed and reuse the result until one of
+    /// its words is overwritten. Much faster for programs that loop, which is most of them.
+    Decoded,
+}
+
+/// Decoded instructions indexed by the address they start at, along with which words they were
+/// decoded from so that writes elsewhere don't have to search for stale instructions.
+#[derive(Clone, Debug, Default)]
+pub(super) struct DecodeCache {
+    instructions: Vec<Option<Instruction>>,
+    covered: Vec<bool>,
+}
+
+impl DecodeCache {
+    fn get(&self, address: usize) -> Option<Instruction> {
+        self.instructions.get(address).copied().flatten()
+    }
+
+    fn insert(&mut self, instruction: Instruction) {
+        let (start, end) = (
+            instruction.address,
+            instruction.address + instruction.size(),
+        );
+
+        if end > self.instructions.len() {
+            self.instructions.resize(end, None);
+            self.covered.resize(end, false);
+        }
+
+        self.instructions[start] = Some(instruction);
+        self.covered[start..end].iter_mut().for_each(|c| *c = true);
+    }
+
+    /// Forget any instruction that includes `address`. Instructions are at most four words long.
+    #[inline]
+    pub(super) fn invalidate(&mut self, address: usize) {
+        if self.covered.get(address) != Some(&true) {
+            return;
+        }
+
+        for slot in self.instructions[address.saturating_sub(3)..=address].iter_mut() {
+            *slot = None;
+        }
+        self.covered[address] = false;
+    }
+}
+
+impl<I: IntcodeInput, O: IntcodeOutput> Intcode<I, O> {
+    pub(super) fn execute_decoded(&mut self) -> Result<Option<Response>, VmError> {
+        let cached = self
+            .decoded
+            .as_ref()
+            .and_then(|cache| cache.get(self.cursor));
+
+        let instruction = match cached {
+            Some(instruction) => instruction,
+            None => match self.instruction(self.cursor) {
+                // Opcodes replaced by a dialect are left to the interpreter, and never cached.
+                _ if self
+                    .dialect
+                    .as_ref()
+                    .is_some_and(|dialect| dialect.defines(self.get(self.cursor))) =>
+                {
+                    return self.interpret()
+                }
+                Ok(instruction) => {
+                    // Code in sparse memory isn't worth a cache sized to reach it.
+                    if instruction.address < self.data.len() {
+                        if let Some(cache) = &mut self.decoded {
+                            cache.insert(instruction);
+                        }
+                    }
+                    instruction
+                }
+                // Let the interpreter report the fault exactly as it would have.
+                Err(_) => return self.interpret(),
+            },
+        };
+
+        self.steps += 1;
+        let params = instruction.params();
+
+        match instruction.opcode {
+            Opcode::Add => {
+                let value = self.add_words(self.read(params[0])?, self.read(params[1])?)?;
+                self.write(params[2], value)?;
+            }
+            Opcode::Mul => {
+                let value = self.mul_words(self.read(params[0])?, self.read(params[1])?)?;
+                self.write(params[2], value)?;
+            }
+            Opcode::Input => match self.input.read() {
+                Some(value) => self.write(params[0], value)?,
+                None => return Ok(Some(Response::InputRequired)),
+            },
+            Opcode::Output => {
+                let value = self.read(params[0])?;
+                self.output.write(value);
+            }
+            Opcode::JumpIfTrue | Opcode::JumpIfFalse => {
+                if (self.read(params[0])? != 0) == (instruction.opcode == Opcode::JumpIfTrue) {
+                    self.cursor = self.address(self.read(params[1])?)?;
+                    return Ok(None);
+                }
+            }
+            Opcode::LessThan => {
+                let value = (self.read(params[0])? < self.read(params[1])?) as i64;
+                self.write(params[2], value)?;
+            }
+            Opcode::Equals => {
+                let value = (self.read(params[0])? == self.read(params[1])?) as i64;
+                self.write(params[2], value)?;
+            }
+            Opcode::AdjustRelativeBase => {
+                self.relative_base = self.add_words(self.relative_base, self.read(params[0])?)?
+            }
+            Opcode::Halt => return Ok(Some(Response::Terminated)),
+        }
+
+        self.cursor += instruction.size();
+        Ok(None)
+    }
+
+    #[inline]
+    fn read(&self, param: Param) -> Result<i64, VmError> {
+        Ok(match param {
+            Param::Position(address) => self.get(self.address(address)?),
+            Param::Immediate(value) => value,
+            Param::Relative(offset) => {
+                self.get(self.address(self.add_words(offset, self.relative_base)?)?)
+            }
+        })
+    }
+
+    #[inline]
+    fn write(&mut self, param: Param, value: i64) -> Result<(), VmError> {
+        let address = match param {
+            Param::Relative(offset) => self.address(self.add_words(offset, self.relative_base)?)?,
+            // Decoding rejects immediate output parameters.
+            param => self.address(param.value())?,
+        };
+
+        self.set(address, value);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::Intcode;
+    use super::*;
+
+    #[test]
+    fn matches_interpreter() {
+        for (input, program) in &[
+            (2, include_str!("../day9/input.txt")),
+            (5, include_str!("../day5/input.txt")),
+        ] {
+            let interpreted: Intcode = program.parse().unwrap();
+            let mut interpreted = interpreted.with_input(&[*input]);
+            let mut decoded = interpreted.clone().with_engine(Engine::Decoded);
+
+            assert_eq!(Ok(Response::Terminated), interpreted.run());
+            assert_eq!(Ok(Response::Terminated), decoded.run());
+            assert_eq!(interpreted.output, decoded.output);
+            assert_eq!(interpreted.steps, decoded.steps);
+            assert_eq!(interpreted.data, decoded.data);
+        }
+    }
+
+    #[test]
+    fn self_modifying() {
+        // Outputs 1, then overwrites the output instruction's parameter with 2 and jumps back.
+        let mut intcode =
+            Intcode::new(vec![104, 1, 1101, 1, 1, 1, 1005, 1, 0, 99]).with_engine(Engine::Decoded);
+
+        for _ in 0..4 {
+            intcode.step().unwrap();
+        }
+        assert_eq!(vec![1, 2], intcode.output);
+    }
+
+    #[test]
+    fn errors() {
+        let mut intcode = Intcode::new(vec![204, -5, 99]).with_engine(Engine::Decoded);
+        assert_eq!(
+            Err(VmError::NegativeAddress {
+                cursor: 0,
+                instruction: 204,
+                address: -5,
+            }),
+            intcode.step(),
+        );
+
+        let mut intcode = Intcode::new(vec![42]).with_engine(Engine::Decoded);
+        assert_eq!(
+            Err(VmError::UnknownOpcode {
+                cursor: 0,
+                instruction: 42,
+            }),
+            intcode.step(),
+        );
+    }
+}
@@ -0,0 +1,743 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use super::cfg::{self, Block, Cfg, EdgeKind};
+use super::disasm::copied_immediate;
+use super::{Instruction, Intcode, Opcode, Param};
+
+/// A subroutine found by `cfg::analyze`, or the code reached from the entry point.
+struct Function {
+    entry: usize,
+    name: String,
+
+    /// The words the function reserves on the stack with `ARB` as it starts, or zero if it doesn't.
+    frame: i64,
+
+    /// How many of the words at the bottom of the frame its callers fill in before calling it.
+    params: i64,
+
+    blocks: Vec<usize>,
+}
+
+/// How a block ends, with its operands already rendered.
+enum Terminator {
+    /// The block falls through to the next one.
+    None,
+    Halt,
+    Call(String),
+    Return,
+    Jump {
+        address: usize,
+        target: usize,
+    },
+    Branch {
+        when: String,
+        unless: String,
+        target: usize,
+    },
+    Indirect {
+        when: Option<String>,
+        target: String,
+    },
+}
+
+#[derive(Clone, Copy)]
+struct Loop {
+    head: usize,
+    end: usize,
+}
+
+enum Line {
+    Label(usize),
+    Code(usize, String),
+}
+
+/// Values a block computes that are folded into later statements rather than written out.
+#[derive(Default)]
+struct Pending {
+    /// Words of the block's own instructions, by address.
+    written: HashMap<usize, String>,
+
+    /// Arguments for the call the block ends with, by offset from the relative base.
+    args: BTreeMap<i64, String>,
+}
+
+/// Decompile the program in `Intcode::data` into pseudocode, with a function for `main` and one
+/// for each subroutine `cfg::analyze` found.
+///
+/// Blocks are arranged into `if`/`else` and `loop` statements where the jumps between them nest,
+/// with `goto` for anything else. Subroutines using the stack frame convention of the puzzle
+/// inputs, which reserve a frame with `ARB #n` on entry and find their return address at the
+/// bottom of it, have the words of the frame named: the caller's arguments `arg1`, `arg2`, ...,
+/// and the rest `local1`, `local2`, .... Values are returned in the first argument's word, even by
+/// functions taking no arguments, so `result` after a call refers to the callee's `arg1`.
+///
+/// A few idioms are folded away: copies written as `ADD #0, x`, comparisons that only feed a
+/// jump, and instructions that write the address a later instruction in the same block reads,
+/// which are shown as indexing into `mem`. Calls through a function pointer, as the day 25 input
+/// makes, are shown as calling the variable holding it.
+pub fn decompile(intcode: &Intcode) -> String {
+    let cfg = cfg::analyze(intcode);
+    let functions = functions(&cfg);
+    let mut out = String::new();
+
+    for function in functions.values() {
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out += &Writer::new(&cfg, &functions, function).write();
+    }
+
+    out
+}
+
+fn functions(cfg: &Cfg) -> BTreeMap<usize, Function> {
+    let mut functions = BTreeMap::new();
+
+    if cfg.blocks.contains_key(&0) {
+        functions.insert(
+            0,
+            Function {
+                entry: 0,
+                name: "main".to_string(),
+                frame: 0,
+                params: 0,
+                blocks: cfg::reachable(&cfg.blocks, 0),
+            },
+        );
+    }
+
+    let calling_blocks: HashMap<usize, &Block> = cfg
+        .blocks
+        .values()
+        .map(|block| (block.last().address, block))
+        .collect();
+
+    for subroutine in cfg.subroutines.iter() {
+        let first = cfg.blocks[&subroutine.entry].instructions[0];
+        let frame = match (first.opcode, first.params()) {
+            (Opcode::AdjustRelativeBase, [Param::Immediate(frame)]) if *frame > 0 => *frame,
+            _ => 0,
+        };
+
+        let params = subroutine
+            .callers
+            .iter()
+            .flat_map(|caller| calling_blocks[caller].instructions.iter())
+            .filter_map(|instruction| match instruction.output() {
+                Some(Param::Relative(offset)) if offset > 0 && offset < frame => Some(offset),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0);
+
+        functions
+            .entry(subroutine.entry)
+            .or_insert_with(|| Function {
+                entry: subroutine.entry,
+                name: format!("sub_{:04}", subroutine.entry),
+                frame,
+                params,
+                blocks: subroutine.blocks.clone(),
+            });
+    }
+
+    functions
+}
+
+struct Writer<'a> {
+    cfg: &'a Cfg,
+    functions: &'a BTreeMap<usize, Function>,
+    function: &'a Function,
+    lines: Vec<Line>,
+    gotos: BTreeSet<usize>,
+
+    /// Jumps the structure of the output already expresses.
+    implicit: HashSet<usize>,
+}
+
+impl<'a> Writer<'a> {
+    fn new(cfg: &'a Cfg, functions: &'a BTreeMap<usize, Function>, function: &'a Function) -> Self {
+        Self {
+            cfg,
+            functions,
+            function,
+            lines: Vec::new(),
+            gotos: BTreeSet::new(),
+            implicit: HashSet::new(),
+        }
+    }
+
+    fn write(mut self) -> String {
+        self.range(self.function.entry, usize::MAX, 1, None);
+
+        let params: Vec<String> = (1..=self.function.params)
+            .map(|k| format!("arg{}", k))
+            .collect();
+        let mut out = format!("fn {}({}) {{\n", self.function.name, params.join(", "));
+
+        for line in self.lines.iter() {
+            match line {
+                Line::Label(address) if self.gotos.contains(address) => {
+                    out += &format!("L{:04}:\n", address)
+                }
+                Line::Label(_) => {}
+                Line::Code(depth, code) => out += &format!("{}{}\n", "    ".repeat(*depth), code),
+            }
+        }
+
+        out.push_str("}\n");
+        out
+    }
+
+    fn line(&mut self, depth: usize, code: String) {
+        self.lines.push(Line::Code(depth, code));
+    }
+
+    /// Write the function's blocks from `start` up to `end`, returning whether execution can
+    /// continue past the last of them.
+    fn range(&mut self, start: usize, end: usize, depth: usize, current: Option<Loop>) -> bool {
+        let mut address = start;
+        let mut falls_through = true;
+
+        while let Some(block) = self.next_block(address, end) {
+            if current.is_none_or(|l| l.head != block) {
+                if let Some(loop_end) = self.loop_end(block).filter(|&loop_end| loop_end <= end) {
+                    self.line(depth, "loop {".to_string());
+                    let body = Loop {
+                        head: block,
+                        end: loop_end,
+                    };
+                    if self.range(block, loop_end, depth + 1, Some(body)) {
+                        self.line(depth + 1, "break".to_string());
+                    }
+                    self.line(depth, "}".to_string());
+
+                    address = loop_end;
+                    falls_through = true;
+                    continue;
+                }
+            }
+
+            let (next, continues) = self.block(block, end, depth, current);
+            address = next;
+            falls_through = continues;
+        }
+
+        falls_through
+    }
+
+    fn next_block(&self, address: usize, end: usize) -> Option<usize> {
+        self.function
+            .blocks
+            .iter()
+            .copied()
+            .find(|&block| block >= address && block < end)
+    }
+
+    /// The end of the loop headed by `block`, if any later block jumps back to it.
+    fn loop_end(&self, head: usize) -> Option<usize> {
+        self.function
+            .blocks
+            .iter()
+            .map(|start| &self.cfg.blocks[start])
+            .filter(|block| {
+                block.start() >= head
+                    && block
+                        .edges
+                        .iter()
+                        .any(|edge| edge.kind == EdgeKind::Jump && edge.target == head)
+            })
+            .map(|block| block.end())
+            .max()
+    }
+
+    /// Write a block and whatever its final jump encloses, returning where to carry on from and
+    /// whether execution can get there.
+    fn block(
+        &mut self,
+        start: usize,
+        end: usize,
+        depth: usize,
+        current: Option<Loop>,
+    ) -> (usize, bool) {
+        let cfg = self.cfg;
+        let block = &cfg.blocks[&start];
+        let next = block.end();
+
+        self.lines.push(Line::Label(start));
+        let (statements, terminator) = self.statements(block);
+        for statement in statements {
+            self.line(depth, statement);
+        }
+
+        match terminator {
+            Terminator::None => (next, true),
+            Terminator::Halt => {
+                self.line(depth, "halt".to_string());
+                (next, false)
+            }
+            Terminator::Call(call) => {
+                self.line(depth, call);
+                (next, true)
+            }
+            Terminator::Return => {
+                self.line(depth, "return".to_string());
+                (next, false)
+            }
+            Terminator::Indirect { when, target } => match when {
+                Some(when) => {
+                    self.line(depth, format!("if {} {{ goto *{} }}", when, target));
+                    (next, true)
+                }
+                None => {
+                    self.line(depth, format!("goto *{}", target));
+                    (next, false)
+                }
+            },
+            Terminator::Jump { address, target } => {
+                if let Some(code) = self.jump(address, target, next, end, current) {
+                    self.line(depth, code);
+                }
+                (next, false)
+            }
+            Terminator::Branch {
+                when,
+                unless,
+                target,
+            } => {
+                if let Some(l) = current {
+                    if target == l.head && next == l.end {
+                        self.line(depth, format!("if {} {{ break }}", unless));
+                        return (next, false);
+                    } else if target == l.head || target == l.end {
+                        let control = if target == l.head {
+                            "continue"
+                        } else {
+                            "break"
+                        };
+                        self.line(depth, format!("if {} {{ {} }}", when, control));
+                        return (next, true);
+                    }
+                }
+
+                if target <= next || target > end {
+                    self.gotos.insert(target);
+                    self.line(depth, format!("if {} {{ goto L{:04} }}", when, target));
+                    return (next, true);
+                }
+
+                self.line(depth, format!("if {} {{", unless));
+
+                // An unconditional jump at the end of the `if` part, past the target, makes the
+                // blocks in between an `else` part.
+                let join = self.next_block_before(next, target).and_then(|last| {
+                    match self.statements(&cfg.blocks[&last]).1 {
+                        Terminator::Jump {
+                            address,
+                            target: join,
+                        } if join > target
+                            && join <= end
+                            && current.is_none_or(|l| join != l.head && join != l.end) =>
+                        {
+                            Some((address, join))
+                        }
+                        _ => None,
+                    }
+                });
+
+                match join {
+                    Some((address, join)) => {
+                        self.implicit.insert(address);
+                        self.range(next, target, depth + 1, current);
+                        self.line(depth, "} else {".to_string());
+                        self.range(target, join, depth + 1, current);
+                        self.line(depth, "}".to_string());
+                        (join, true)
+                    }
+                    None => {
+                        self.range(next, target, depth + 1, current);
+                        self.line(depth, "}".to_string());
+                        (target, true)
+                    }
+                }
+            }
+        }
+    }
+
+    /// The last of the function's blocks starting between `start` and `end`.
+    fn next_block_before(&self, start: usize, end: usize) -> Option<usize> {
+        self.function
+            .blocks
+            .iter()
+            .copied()
+            .rfind(|&block| block >= start && block < end)
+    }
+
+    /// The statement for an unconditional jump, if one is needed.
+    fn jump(
+        &mut self,
+        address: usize,
+        target: usize,
+        next: usize,
+        end: usize,
+        current: Option<Loop>,
+    ) -> Option<String> {
+        if self.implicit.contains(&address) || target == next {
+            return None;
+        }
+
+        match current {
+            Some(l) if target == l.head && next == l.end => None,
+            Some(l) if target == l.head => Some("continue".to_string()),
+            Some(l) if target == l.end => Some("break".to_string()),
+            _ if target == end => None,
+            _ => {
+                self.gotos.insert(target);
+                Some(format!("goto L{:04}", target))
+            }
+        }
+    }
+
+    /// The statements for the block's instructions, and how it ends.
+    fn statements(&self, block: &Block) -> (Vec<String>, Terminator) {
+        let last = block.last();
+        let call = block
+            .edges
+            .iter()
+            .find(|edge| edge.kind == EdgeKind::Call)
+            .and_then(|edge| self.functions.get(&edge.target));
+        let returns = self
+            .cfg
+            .subroutines
+            .iter()
+            .any(|subroutine| subroutine.returns.contains(&last.address));
+        let indirect_call = call.is_none()
+            && last.is_terminal()
+            && last.jump_target().is_none()
+            && block.edges.iter().any(|edge| edge.kind == EdgeKind::Return);
+
+        let body = match last.opcode {
+            Opcode::JumpIfTrue | Opcode::JumpIfFalse | Opcode::Halt => {
+                &block.instructions[..block.instructions.len() - 1]
+            }
+            _ => &block.instructions[..],
+        };
+
+        let mut statements = Vec::new();
+        let mut pending = Pending::default();
+        let mut comparison = None;
+
+        for (index, instruction) in body.iter().enumerate() {
+            let frame = self.function.frame;
+            if let (Opcode::AdjustRelativeBase, [Param::Immediate(offset)]) =
+                (instruction.opcode, instruction.params())
+            {
+                let entering = instruction.address == self.function.entry && *offset == frame;
+                let leaving = returns && *offset == -frame;
+                if frame > 0 && (entering || leaving) {
+                    continue;
+                }
+            }
+
+            let value = match self.expression(instruction, &pending) {
+                Ok(value) => value,
+                Err(statement) => {
+                    statements.push(statement);
+                    continue;
+                }
+            };
+            let target = instruction.output().unwrap();
+
+            if let Param::Position(address) = target {
+                let address = address as usize;
+                let read_later = block.instructions[index + 1..]
+                    .iter()
+                    .any(|later| address > later.address && address < later.address + later.size());
+                if read_later {
+                    pending.written.insert(address, value);
+                    continue;
+                }
+            }
+
+            if let (true, Param::Relative(offset)) = (call.is_some() || indirect_call, target) {
+                if offset == 0 && copied_immediate(instruction) == Some(block.end() as i64) {
+                    continue;
+                }
+                if offset >= 1 && call.is_none_or(|callee| offset <= callee.params) {
+                    pending.args.insert(offset, value);
+                    continue;
+                }
+            }
+
+            let feeds_jump = index + 1 == body.len()
+                && matches!(last.opcode, Opcode::JumpIfTrue | Opcode::JumpIfFalse)
+                && last.params()[0] == target;
+            if feeds_jump {
+                if let Opcode::LessThan | Opcode::Equals = instruction.opcode {
+                    comparison = Some(self.comparison(instruction, &pending));
+                    continue;
+                }
+            }
+
+            statements.push(format!(
+                "{} = {}",
+                self.operand(
+                    instruction,
+                    instruction.opcode.output_param().unwrap(),
+                    &pending
+                ),
+                value
+            ));
+        }
+
+        let terminator = if indirect_call {
+            let args = self.arguments(&pending, pending.args.keys().max().copied().unwrap_or(0));
+            Terminator::Call(format!("{}({})", self.operand(last, 1, &pending), args))
+        } else {
+            self.terminator(last, call, returns, comparison, &pending)
+        };
+
+        (statements, terminator)
+    }
+
+    /// The arguments for a call, with any the block didn't fill in named after the variables
+    /// holding them.
+    fn arguments(&self, pending: &Pending, count: i64) -> String {
+        let args: Vec<String> = (1..=count)
+            .map(|k| {
+                pending
+                    .args
+                    .get(&k)
+                    .cloned()
+                    .unwrap_or_else(|| self.variable(k))
+            })
+            .collect();
+        args.join(", ")
+    }
+
+    fn terminator(
+        &self,
+        last: &Instruction,
+        call: Option<&Function>,
+        returns: bool,
+        comparison: Option<(String, String)>,
+        pending: &Pending,
+    ) -> Terminator {
+        match last.opcode {
+            Opcode::Halt => return Terminator::Halt,
+            Opcode::JumpIfTrue | Opcode::JumpIfFalse => {}
+            _ => return Terminator::None,
+        }
+
+        if let Some(callee) = call {
+            let args = self.arguments(pending, callee.params);
+            return Terminator::Call(format!("{}({})", callee.name, args));
+        }
+        if returns {
+            return Terminator::Return;
+        }
+
+        let (mut when, mut unless) = comparison.unwrap_or_else(|| {
+            let condition = self.operand(last, 0, pending);
+            (format!("{} != 0", condition), format!("{} == 0", condition))
+        });
+        if last.opcode == Opcode::JumpIfFalse {
+            std::mem::swap(&mut when, &mut unless);
+        }
+
+        match (last.params()[0], last.jump_target()) {
+            (Param::Immediate(_), _) if !last.is_terminal() => Terminator::None,
+            (_, Some(target)) if target >= 0 && last.is_terminal() => Terminator::Jump {
+                address: last.address,
+                target: target as usize,
+            },
+            (_, Some(target)) if target >= 0 => Terminator::Branch {
+                when,
+                unless,
+                target: target as usize,
+            },
+            _ => Terminator::Indirect {
+                when: Some(when).filter(|_| !last.is_terminal()),
+                target: self.operand(last, 1, pending),
+            },
+        }
+    }
+
+    /// The value an instruction computes, or the whole statement if it doesn't write one.
+    fn expression(&self, instruction: &Instruction, pending: &Pending) -> Result<String, String> {
+        let operand = |index| self.operand(instruction, index, pending);
+        let term = |index| {
+            let operand = operand(index);
+            if operand.contains(' ') {
+                format!("({})", operand)
+            } else {
+                operand
+            }
+        };
+
+        Ok(match instruction.opcode {
+            Opcode::Add => match (operand(0), operand(1)) {
+                (zero, other) | (other, zero) if zero == "0" => other,
+                (_, right) if right.starts_with('-') => format!("{} - {}", term(0), &right[1..]),
+                _ => format!("{} + {}", term(0), term(1)),
+            },
+            Opcode::Mul => match (operand(0), operand(1)) {
+                (one, other) | (other, one) if one == "1" => other,
+                (zero, _) | (_, zero) if zero == "0" => zero,
+                (minus, _) if minus == "-1" => format!("-{}", term(1)),
+                (_, minus) if minus == "-1" => format!("-{}", term(0)),
+                _ => format!("{} * {}", term(0), term(1)),
+            },
+            Opcode::LessThan | Opcode::Equals => self.comparison(instruction, pending).0,
+            Opcode::Input => "input()".to_string(),
+            Opcode::Output => return Err(format!("output({})", operand(0))),
+            Opcode::AdjustRelativeBase => return Err(format!("rb += {}", operand(0))),
+            Opcode::JumpIfTrue | Opcode::JumpIfFalse | Opcode::Halt => unreachable!(),
+        })
+    }
+
+    /// A comparison and its negation.
+    fn comparison(&self, instruction: &Instruction, pending: &Pending) -> (String, String) {
+        let a = self.operand(instruction, 0, pending);
+        let b = self.operand(instruction, 1, pending);
+
+        match instruction.opcode {
+            Opcode::LessThan => (format!("{} < {}", a, b), format!("{} >= {}", a, b)),
+            _ => (format!("{} == {}", a, b), format!("{} != {}", a, b)),
+        }
+    }
+
+    fn operand(&self, instruction: &Instruction, index: usize, pending: &Pending) -> String {
+        match (
+            instruction.params()[index],
+            pending.written.get(&(instruction.address + 1 + index)),
+        ) {
+            (Param::Immediate(_), Some(value)) => value.clone(),
+            (Param::Position(_), Some(value)) => format!("mem[{}]", value),
+            (Param::Relative(_), Some(value)) => format!("mem[rb + {}]", value),
+            (Param::Immediate(value), None) => value.to_string(),
+            (Param::Position(address), None) => format!("mem[{}]", address),
+            (Param::Relative(offset), None) => pending
+                .args
+                .get(&offset)
+                .cloned()
+                .unwrap_or_else(|| self.variable(offset)),
+        }
+    }
+
+    /// The name of the word at `offset` from the relative base.
+    fn variable(&self, offset: i64) -> String {
+        let frame = self.function.frame;
+        let params = self.function.params;
+
+        // With a frame of one word, the first argument shares the word at the relative base with
+        // the return addresses of the calls the function makes.
+        match offset + frame {
+            k if k > 0 && (offset < 0 || (k == 1 && frame > 0)) && k <= params.max(1) => {
+                format!("arg{}", k)
+            }
+            k if k > 0 && offset < 0 => format!("local{}", k - params),
+            _ if offset == 1 => "result".to_string(),
+            _ => format!("rb[{}]", offset),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn structure() {
+        let program = super::super::asm::assemble(
+            "
+                ARB #100
+                IN -> [rb+1]
+                ADD #0, #L_AFTER -> [rb+0]
+                JT #1, #L_SUM
+            L_AFTER:
+                OUT [rb+1]
+                HLT
+
+            ; sum(n): add up the numbers from n down to 1, printing whether each is even
+            L_SUM:
+                ARB #4
+                ADD #0, #0 -> [rb-2]
+            L_LOOP:
+                EQ [rb-3], #0 -> [rb-1]
+                JT [rb-1], #L_DONE
+                ADD [rb-2], [rb-3] -> [rb-2]
+                ADD [rb-3], #100 -> [L_INDEX]
+                DATA 21007          ; LT [index], #5 -> [rb-1]
+            L_INDEX:
+                DATA 0, 5, -1
+                JF [rb-1], #L_BIG
+                OUT #0
+                JT #1, #L_NEXT
+            L_BIG:
+                OUT #1
+            L_NEXT:
+                ADD [rb-3], #-1 -> [rb-3]
+                JT #1, #L_LOOP
+            L_DONE:
+                ADD [rb-2], #0 -> [rb-3]
+                ARB #-4
+                JT #1, [rb+0]
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(
+            "fn main() {
+    rb += 100
+    sub_0014(input())
+    output(result)
+    halt
+}
+
+fn sub_0014(arg1) {
+    local1 = 0
+    loop {
+        if arg1 == 0 { break }
+        local1 = local1 + arg1
+        if mem[arg1 + 100] < 5 {
+            output(0)
+        } else {
+            output(1)
+        }
+        arg1 = arg1 - 1
+    }
+    arg1 = local1
+    return
+}
+",
+            decompile(&Intcode::new(program))
+        );
+    }
+
+    #[test]
+    fn decompile_inputs() {
+        for input in &[
+            include_str!("../day9/input.txt"),
+            include_str!("../day21/input.txt"),
+            include_str!("../day25/input.txt"),
+        ] {
+            let intcode: Intcode = input.parse().unwrap();
+            let pseudocode = decompile(&intcode);
+
+            assert!(pseudocode.starts_with("fn main() {\n"));
+            assert!(pseudocode.contains("\nfn sub_"));
+        }
+
+        // Day 25 calls a function pointer on each item of a list.
+        let intcode: Intcode = include_str!("../day25/input.txt").parse().unwrap();
+        assert!(decompile(&intcode).contains(
+            "    loop {
+        if local1 == arg2 { break }
+        arg4((local1 * arg3) + arg1, local1)
+        local1 = local1 + 1
+    }
+"
+        ));
+    }
+}
@@ -0,0 +1,418 @@
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::sync::Arc;
+
+use super::io::{IntcodeInput, IntcodeOutput};
+use super::{Intcode, Response, VmError};
+
+/// How a custom opcode uses each of its parameters.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Role {
+    /// A value, read according to the parameter's mode and passed to the handler.
+    Read,
+
+    /// An address the handler's results are stored at. Immediate mode isn't allowed.
+    Write,
+}
+
+/// What the machine does after a custom opcode's handler returns.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+    /// Store these values at the instruction's write parameters, in order, and continue with the
+    /// following instruction.
+    Next(Vec<i64>),
+
+    /// Continue from this address.
+    Jump(usize),
+
+    /// Stop with this response, leaving the cursor on the instruction, as `HLT` does.
+    Stop(Response),
+}
+
+/// The parts of a machine a custom opcode's handler can use.
+pub trait Machine {
+    fn get(&self, address: usize) -> i64;
+    fn set(&mut self, address: usize, value: i64);
+    fn cursor(&self) -> usize;
+    fn relative_base(&self) -> i64;
+    fn set_relative_base(&mut self, relative_base: i64);
+
+    /// The next input value, if one is available.
+    fn read(&mut self) -> Option<i64>;
+    fn write(&mut self, value: i64);
+}
+
+/// `EXIT code`: output `code` and halt.
+pub const EXIT: i64 = 90;
+
+/// `CALL target`: store the return address at the relative base, move the relative base up a
+/// word, and jump to `target`.
+pub const CALL: i64 = 91;
+
+/// `RET`: move the relative base down a word and jump to the return address stored there.
+pub const RET: i64 = 92;
+
+/// `POLL -> r`: store the next input value at `r`, or -1 without waiting if there isn't one.
+pub const POLL: i64 = 93;
+
+/// Runs a custom opcode, given the values of its read parameters in order.
+pub type Handler = dyn Fn(&mut dyn Machine, &[i64]) -> Result<Action, String> + Send + Sync;
+
+#[derive(Clone)]
+struct Custom {
+    mnemonic: String,
+    roles: Vec<Role>,
+    handler: Arc<Handler>,
+}
+
+/// A table of opcodes added to, or replacing, the standard ones, for experimenting with
+/// extensions to Intcode. Attach it to a machine with `Intcode::with_dialect`; clones of the
+/// machine share it.
+///
+/// Custom opcodes are executed by the interpreter whichever engine is selected, and the static
+/// tools such as `disasm` and `cfg` only know the standard opcodes.
+#[derive(Clone, Default)]
+pub struct Dialect {
+    opcodes: BTreeMap<i64, Custom>,
+}
+
+impl Dialect {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Define opcode `code`, which must be two digits or fewer as the rest of an instruction holds
+    /// parameter modes. Defining a standard opcode replaces it.
+    pub fn define(
+        &mut self,
+        code: i64,
+        mnemonic: &str,
+        roles: &[Role],
+        handler: impl Fn(&mut dyn Machine, &[i64]) -> Result<Action, String> + Send + Sync + 'static,
+    ) -> Result<(), String> {
+        if !(0..100).contains(&code) {
+            return Err(format!("Opcode {} doesn't fit in two digits", code));
+        }
+        if let Some(custom) = self.opcodes.get(&code) {
+            return Err(format!(
+                "Opcode {} is already defined as {}",
+                code, custom.mnemonic
+            ));
+        }
+
+        self.opcodes.insert(
+            code,
+            Custom {
+                mnemonic: mnemonic.to_string(),
+                roles: roles.to_vec(),
+                handler: Arc::new(handler),
+            },
+        );
+        Ok(())
+    }
+
+    /// Standard Intcode with a few extensions: `EXIT`, `CALL`, `RET` and `POLL`.
+    pub fn extended() -> Self {
+        let mut dialect = Self::new();
+
+        dialect
+            .define(EXIT, "EXIT", &[Role::Read], |machine, args| {
+                machine.write(args[0]);
+                Ok(Action::Stop(Response::Terminated))
+            })
+            .unwrap();
+
+        dialect
+            .define(CALL, "CALL", &[Role::Read], |machine, args| {
+                let base = machine.relative_base();
+                if base < 0 {
+                    return Err(format!("Negative relative base {}", base));
+                }
+                let target = usize::try_from(args[0])
+                    .map_err(|_| format!("Negative call target {}", args[0]))?;
+                let top = base
+                    .checked_add(1)
+                    .ok_or_else(|| format!("Call stack overflow at relative base {}", base))?;
+
+                machine.set(base as usize, machine.cursor() as i64 + 2);
+                machine.set_relative_base(top);
+                Ok(Action::Jump(target))
+            })
+            .unwrap();
+
+        dialect
+            .define(RET, "RET", &[], |machine, _| {
+                let base = match machine.relative_base().checked_sub(1) {
+                    Some(base) if base >= 0 => base,
+                    _ => return Err("Return with an empty stack".to_string()),
+                };
+
+                let target = machine.get(base as usize);
+                machine.set_relative_base(base);
+                usize::try_from(target)
+                    .map(Action::Jump)
+                    .map_err(|_| format!("Negative return address {}", target))
+            })
+            .unwrap();
+
+        dialect
+            .define(POLL, "POLL", &[Role::Write], |machine, _| {
+                Ok(Action::Next(vec![machine.read().unwrap_or(-1)]))
+            })
+            .unwrap();
+
+        dialect
+    }
+
+    /// Whether the dialect defines the opcode of the instruction `raw`.
+    pub(super) fn defines(&self, raw: i64) -> bool {
+        self.opcodes.contains_key(&(raw % 100))
+    }
+}
+
+/// Lists the opcodes that differ from standard Intcode, eg. `Dialect(10 EXIT, 99 STOP)`.
+impl fmt::Debug for Dialect {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let opcodes: Vec<String> = self
+            .opcodes
+            .iter()
+            .map(|(code, custom)| format!("{} {}", code, custom.mnemonic))
+            .collect();
+        write!(f, "Dialect({})", opcodes.join(", "))
+    }
+}
+
+impl<I: IntcodeInput, O: IntcodeOutput> Machine for Intcode<I, O> {
+    fn get(&self, address: usize) -> i64 {
+        Intcode::get(self, address)
+    }
+
+    fn set(&mut self, address: usize, value: i64) {
+        Intcode::set(self, address, value)
+    }
+
+    fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    fn relative_base(&self) -> i64 {
+        self.relative_base
+    }
+
+    fn set_relative_base(&mut self, relative_base: i64) {
+        self.relative_base = relative_base;
+    }
+
+    fn read(&mut self) -> Option<i64> {
+        self.input.read()
+    }
+
+    fn write(&mut self, value: i64) {
+        self.output.write(value)
+    }
+}
+
+impl<I: IntcodeInput, O: IntcodeOutput> Intcode<I, O> {
+    /// Execute the instruction at the cursor if the machine's dialect defines its opcode.
+    pub(super) fn execute_custom(&mut self) -> Option<Result<Option<Response>, VmError>> {
+        let raw = self.get(self.cursor);
+        let custom = self.dialect.as_ref()?.opcodes.get(&(raw % 100))?.clone();
+
+        Some(self.run_custom(raw, &custom))
+    }
+
+    fn run_custom(&mut self, raw: i64, custom: &Custom) -> Result<Option<Response>, VmError> {
+        let mut args = Vec::new();
+        for (index, role) in custom.roles.iter().enumerate() {
+            if *role == Role::Read {
+                args.push(self.get_param(index)?);
+            }
+        }
+
+        let action = (custom.handler)(self, &args).map_err(|message| VmError::Handler {
+            cursor: self.cursor,
+            instruction: raw,
+            message,
+        })?;
+
+        match action {
+            Action::Next(values) => {
+                let writes: Vec<usize> = custom
+                    .roles
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, role)| **role == Role::Write)
+                    .map(|(index, _)| index)
+                    .collect();
+                if values.len() != writes.len() {
+                    return Err(VmError::Handler {
+                        cursor: self.cursor,
+                        instruction: raw,
+                        message: format!(
+                            "{} returned {} values for {} write parameters",
+                            custom.mnemonic,
+                            values.len(),
+                            writes.len()
+                        ),
+                    });
+                }
+
+                for (index, value) in writes.into_iter().zip(values) {
+                    self.set_pos(index, value)?;
+                }
+                self.cursor += 1 + custom.roles.len();
+                Ok(None)
+            }
+            Action::Jump(address) => {
+                self.cursor = address;
+                Ok(None)
+            }
+            Action::Stop(response) => Ok(Some(response)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::Engine;
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn custom_opcodes() {
+        let exit_code = Arc::new(Mutex::new(None));
+        let mut dialect = Dialect::new();
+
+        // EXIT a: halt, recording a as the exit code.
+        let code = exit_code.clone();
+        dialect
+            .define(10, "EXIT", &[Role::Read], move |_, args| {
+                *code.lock().unwrap() = Some(args[0]);
+                Ok(Action::Stop(Response::Terminated))
+            })
+            .unwrap();
+
+        // SWAP a, b -> c, d: store b at c and a at d.
+        dialect
+            .define(
+                11,
+                "SWAP",
+                &[Role::Read, Role::Read, Role::Write, Role::Write],
+                |_, args| Ok(Action::Next(vec![args[1], args[0]])),
+            )
+            .unwrap();
+
+        // OUT a: replaces the standard output instruction, outputting twice a.
+        dialect
+            .define(4, "OUT2", &[Role::Read], |machine, args| {
+                machine.write(args[0] * 2);
+                Ok(Action::Next(vec![]))
+            })
+            .unwrap();
+
+        assert_eq!(
+            Err("Opcode 4 is already defined as OUT2".to_string()),
+            dialect.define(4, "OUT", &[Role::Read], |_, _| Ok(Action::Next(vec![])))
+        );
+        assert_eq!(
+            Err("Opcode 100 doesn't fit in two digits".to_string()),
+            dialect.define(100, "BIG", &[], |_, _| Ok(Action::Next(vec![])))
+        );
+        assert_eq!(
+            "Dialect(4 OUT2, 10 EXIT, 11 SWAP)",
+            format!("{:?}", dialect)
+        );
+
+        for &engine in &[Engine::Interpreter, Engine::Decoded] {
+            let mut intcode = Intcode::new(vec![1111, 3, 4, 12, 13, 4, 12, 4, 13, 110, 7, 0, 0, 0])
+                .with_engine(engine)
+                .with_dialect(dialect.clone());
+
+            assert_eq!(Ok(Response::Terminated), intcode.run());
+            assert_eq!(vec![8, 6], intcode.output);
+            assert_eq!(9, intcode.cursor());
+            assert_eq!(Some(7), *exit_code.lock().unwrap());
+        }
+    }
+
+    #[test]
+    fn extended() {
+        let program = vec![
+            109, 100, // ARB #100
+            191, 9, // CALL #9
+            293, 0, // POLL -> [rb+0]
+            290, 0, // EXIT [rb+0]
+            0, //
+            104, 7,  // OUT #7
+            92, // RET
+        ];
+
+        for (input, code) in [(vec![5], 5), (vec![], -1)] {
+            let mut intcode = Intcode::new(program.clone())
+                .with_input(&input)
+                .with_dialect(Dialect::extended());
+
+            assert_eq!(Ok(Response::Terminated), intcode.run());
+            assert_eq!(vec![7, code], intcode.output);
+            assert_eq!((6, 100), (intcode.cursor(), intcode.relative_base()));
+        }
+
+        for (program, offset) in [(vec![92], 0), (vec![109, i64::MIN, 92], 2)] {
+            let mut intcode = Intcode::new(program).with_dialect(Dialect::extended());
+            assert_eq!(
+                Err(format!(
+                    "Return with an empty stack in instruction 92 at offset {}!",
+                    offset
+                )),
+                intcode.run().map_err(String::from)
+            );
+        }
+
+        let mut intcode =
+            Intcode::new(vec![109, i64::MAX, 191, 0]).with_dialect(Dialect::extended());
+        assert_eq!(
+            Err(format!(
+                "Call stack overflow at relative base {} in instruction 191 at offset 2!",
+                i64::MAX
+            )),
+            intcode.run().map_err(String::from)
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let mut dialect = Dialect::new();
+        dialect
+            .define(20, "FAIL", &[Role::Read], |_, args| {
+                Err(format!("Refusing to handle {}", args[0]))
+            })
+            .unwrap();
+        dialect
+            .define(21, "PAIR", &[Role::Write], |_, _| {
+                Ok(Action::Next(vec![1, 2]))
+            })
+            .unwrap();
+
+        let mut intcode = Intcode::new(vec![1120, 5, 21, 99]).with_dialect(dialect.clone());
+        assert_eq!(
+            Err("Refusing to handle 5 in instruction 1120 at offset 0!".to_string()),
+            intcode.run().map_err(String::from)
+        );
+
+        intcode.set_cursor(2);
+        assert_eq!(
+            Err(
+                "PAIR returned 2 values for 1 write parameters in instruction 21 at offset 2!"
+                    .to_string()
+            ),
+            intcode.run().map_err(String::from)
+        );
+
+        let mut intcode = Intcode::new(vec![22]).with_dialect(dialect);
+        assert_eq!(
+            Err("Unknown opcode in instruction 22 at offset 0!".to_string()),
+            intcode.run().map_err(String::from)
+        );
+    }
+}
@@ -0,0 +1,312 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use super::disasm::{copied_immediate, find_code};
+use super::{Instruction, Intcode, Param};
+
+/// A piece of a program: an instruction `disasm` found by following control flow, or a word of
+/// data.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Unit {
+    Code(Instruction),
+    Data { address: usize, value: i64 },
+}
+
+impl Unit {
+    pub fn address(&self) -> usize {
+        match self {
+            Unit::Code(instruction) => instruction.address,
+            Unit::Data { address, .. } => *address,
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            Unit::Code(instruction) => instruction.size(),
+            Unit::Data { .. } => 1,
+        }
+    }
+
+    /// What two units must share to be lined up with each other: the opcode and modes of an
+    /// instruction, leaving its operands free to differ, or the value of a data word.
+    fn key(&self) -> (bool, i64) {
+        match self {
+            Unit::Code(instruction) => (true, instruction.raw),
+            Unit::Data { value, .. } => (false, *value),
+        }
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Unit::Code(instruction) => write!(f, "{:04}: {}", instruction.address, instruction),
+            Unit::Data { address, value } => write!(f, "{:04}: DATA {}", address, value),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Change {
+    /// Only in the first program.
+    Removed(Unit),
+
+    /// Only in the second program.
+    Added(Unit),
+
+    /// Lined up with each other, but with different operands or values.
+    Changed(Unit, Unit),
+}
+
+/// The differences between two programs, found by lining up their instructions and data.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProgramDiff {
+    pub changes: Vec<Change>,
+
+    /// Where the second program's addresses stop lining up with the first's: the address in the
+    /// first program, and what to add to it to get the address in the second from there on.
+    pub shifts: Vec<(usize, i64)>,
+}
+
+/// Compare two programs, such as two players' puzzle inputs or a program before and after it
+/// modifies itself.
+///
+/// Each program is split into instructions and data words as `disasm` would list them, and the
+/// two lists are lined up on the opcodes and modes of their instructions with a longest common
+/// subsequence, along with data words of the same value. Instructions that were lined up are then
+/// compared operand by operand, treating addresses as equal when they refer to words that were
+/// lined up too, so code that has only moved isn't reported. Immediate values are compared as they
+/// are, except for jump targets and the return addresses pushed before a call, making them the main
+/// thing reported when two puzzle inputs share their code.
+pub fn diff(a: &Intcode, b: &Intcode) -> ProgramDiff {
+    let (a, b) = (units(&a.data), units(&b.data));
+    let pairs = align(&a, &b);
+
+    let mut addresses = HashMap::new();
+    let mut shifts = Vec::new();
+    let mut shift = 0;
+
+    for &(i, j) in pairs.iter() {
+        let (start_a, start_b) = (a[i].address(), b[j].address());
+        for offset in 0..a[i].size().min(b[j].size()) {
+            addresses.insert((start_a + offset) as i64, (start_b + offset) as i64);
+        }
+
+        if start_b as i64 - start_a as i64 != shift {
+            shift = start_b as i64 - start_a as i64;
+            shifts.push((start_a, shift));
+        }
+    }
+
+    let mut changes = Vec::new();
+    let (mut i, mut j) = (0, 0);
+
+    for &(next_i, next_j) in pairs.iter().chain(Some(&(a.len(), b.len()))) {
+        // Data words in the same place in both programs are shown as changed.
+        let (removed, added) = (&a[i..next_i], &b[j..next_j]);
+        let data = removed
+            .iter()
+            .zip(added.iter())
+            .take_while(|(x, y)| !x.key().0 && !y.key().0)
+            .count();
+
+        changes.extend((0..data).map(|k| Change::Changed(removed[k], added[k])));
+        changes.extend(removed[data..].iter().map(|&unit| Change::Removed(unit)));
+        changes.extend(added[data..].iter().map(|&unit| Change::Added(unit)));
+
+        if next_i < a.len() && !same(&a[next_i], &b[next_j], &addresses) {
+            changes.push(Change::Changed(a[next_i], b[next_j]));
+        }
+        i = next_i + 1;
+        j = next_j + 1;
+    }
+
+    ProgramDiff { changes, shifts }
+}
+
+fn units(data: &[i64]) -> Vec<Unit> {
+    let (code, _) = find_code(data);
+    let mut units = Vec::new();
+    let mut address = 0;
+
+    while address < data.len() {
+        let unit = if code.contains(&address) {
+            Unit::Code(Instruction::decode(address, &data[address..]).unwrap())
+        } else {
+            Unit::Data {
+                address,
+                value: data[address],
+            }
+        };
+        address += unit.size();
+        units.push(unit);
+    }
+
+    units
+}
+
+/// The indices of the units lined up with each other, in order.
+fn align(a: &[Unit], b: &[Unit]) -> Vec<(usize, usize)> {
+    let prefix = a
+        .iter()
+        .zip(b.iter())
+        .take_while(|(x, y)| x.key() == y.key())
+        .count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x.key() == y.key())
+        .count();
+    let (n, m) = (a.len() - prefix - suffix, b.len() - prefix - suffix);
+
+    // lengths[i * (m + 1) + j] is the length of the longest common subsequence of the remaining
+    // units from a[prefix + i] and b[prefix + j] on.
+    let mut lengths = vec![0u32; (n + 1) * (m + 1)];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lengths[i * (m + 1) + j] = if a[prefix + i].key() == b[prefix + j].key() {
+                lengths[(i + 1) * (m + 1) + j + 1] + 1
+            } else {
+                lengths[(i + 1) * (m + 1) + j].max(lengths[i * (m + 1) + j + 1])
+            };
+        }
+    }
+
+    let mut pairs: Vec<(usize, usize)> = (0..prefix).map(|i| (i, i)).collect();
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if a[prefix + i].key() == b[prefix + j].key() {
+            pairs.push((prefix + i, prefix + j));
+            i += 1;
+            j += 1;
+        } else if lengths[(i + 1) * (m + 1) + j] >= lengths[i * (m + 1) + j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    pairs.extend((0..suffix).map(|k| (prefix + n + k, prefix + m + k)));
+
+    pairs
+}
+
+/// Whether two lined up units only differ by addresses that were lined up too.
+fn same(a: &Unit, b: &Unit, addresses: &HashMap<i64, i64>) -> bool {
+    let address = |x: i64, y: i64| x == y || addresses.get(&x) == Some(&y);
+
+    match (a, b) {
+        (Unit::Code(x), Unit::Code(y)) => {
+            x.params()
+                .iter()
+                .zip(y.params().iter())
+                .enumerate()
+                .all(|(index, pair)| match (*pair.0, *pair.1) {
+                    (Param::Position(p), Param::Position(q)) => address(p, q),
+                    (Param::Relative(p), Param::Relative(q)) => p == q,
+                    (Param::Immediate(p), Param::Immediate(q)) => {
+                        let jump = x.jump_target().is_some() && index == 1;
+                        let pushed = copied_immediate(x).is_some() && copied_immediate(y).is_some();
+                        p == q || ((jump || pushed) && address(p, q))
+                    }
+                    _ => false,
+                })
+        }
+        (Unit::Data { value: x, .. }, Unit::Data { value: y, .. }) => x == y,
+        _ => false,
+    }
+}
+
+/// ```text
+/// 1 changed, 1 only in the first program, 1 only in the second
+/// Addresses shift by -2 from 0004
+///
+/// - 0002: OUT #1
+/// - 0004: ADD #5, [rb+1] -> [rb+1]
+/// + 0002: ADD #7, [rb+1] -> [rb+1]
+/// + 0013: DATA 3
+/// ```
+impl fmt::Display for ProgramDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.changes.is_empty() && self.shifts.is_empty() {
+            return writeln!(f, "No differences");
+        }
+
+        let count = |wanted: fn(&Change) -> bool| self.changes.iter().filter(|c| wanted(c)).count();
+        writeln!(
+            f,
+            "{} changed, {} only in the first program, {} only in the second",
+            count(|change| matches!(change, Change::Changed(..))),
+            count(|change| matches!(change, Change::Removed(_))),
+            count(|change| matches!(change, Change::Added(_))),
+        )?;
+        for (address, shift) in self.shifts.iter() {
+            writeln!(f, "Addresses shift by {:+} from {:04}", shift, address)?;
+        }
+
+        if !self.changes.is_empty() {
+            writeln!(f)?;
+        }
+        for change in self.changes.iter() {
+            match change {
+                Change::Removed(unit) => writeln!(f, "- {}", unit)?,
+                Change::Added(unit) => writeln!(f, "+ {}", unit)?,
+                Change::Changed(before, after) => writeln!(f, "- {}\n+ {}", before, after)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::asm::assemble;
+    use super::*;
+
+    fn program(source: &str) -> Intcode {
+        Intcode::new(assemble(source).unwrap())
+    }
+
+    #[test]
+    fn shifted() {
+        let before = program(
+            "
+                ARB #100
+                OUT #1
+                ADD #5, [rb+1] -> [rb+1]
+                JT [rb+1], #L_END
+                OUT [L_VALUE]
+            L_END:
+                HLT
+            L_VALUE:
+                DATA 42
+            ",
+        );
+        let after = program(
+            "
+                ARB #100
+                ADD #7, [rb+1] -> [rb+1]
+                JT [rb+1], #L_END
+                OUT [L_VALUE]
+            L_END:
+                HLT
+            L_VALUE:
+                DATA 42, 3
+            ",
+        );
+
+        assert_eq!(
+            "1 changed, 1 only in the first program, 1 only in the second
+Addresses shift by -2 from 0004
+
+- 0002: OUT #1
+- 0004: ADD #5, [rb+1] -> [rb+1]
++ 0002: ADD #7, [rb+1] -> [rb+1]
++ 0013: DATA 3
+",
+            diff(&before, &after).to_string()
+        );
+        assert_eq!("No differences\n", diff(&after, &after).to_string());
+    }
+}
@@ -0,0 +1,268 @@
+use std::collections::{BTreeSet, HashSet};
+use std::fmt::Write;
+
+use super::{Instruction, Intcode, Opcode, Param};
+
+const DATA_PER_LINE: usize = 8;
+
+/// Produce a human-readable listing of the program in `Intcode::data`.
+///
+/// ```text
+/// 0000: JT #1, #L0004
+/// 0003: HLT
+/// L0004:
+/// 0004: ADD [rb+3], #5 -> [104]
+/// 0008: DATA 1, 2, 3
+/// ```
+pub fn disassemble(intcode: &Intcode) -> String {
+    let data = &intcode.data;
+    let (code, labels) = find_code(data);
+    let mut output = String::new();
+    let mut address = 0;
+
+    while address < data.len() {
+        if labels.contains(&address) {
+            writeln!(output, "{}:", label(address)).unwrap();
+        }
+
+        if code.contains(&address) {
+            let instruction = Instruction::decode(address, &data[address..]).unwrap();
+            writeln!(
+                output,
+                "{:04}: {}",
+                address,
+                format_instruction(&instruction, &labels)
+            )
+            .unwrap();
+            address += instruction.size();
+        } else {
+            let start = address;
+            address += 1;
+
+            while address < data.len()
+                && address - start < DATA_PER_LINE
+                && !code.contains(&address)
+                && !labels.contains(&address)
+            {
+                address += 1;
+            }
+
+            writeln!(
+                output,
+                "{:04}: DATA {}",
+                start,
+                data[start..address]
+                    .iter()
+                    .map(|word| word.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            )
+            .unwrap();
+        }
+    }
+
+    output
+}
+
+/// The synthetic label given to a jump target.
+pub fn label(address: usize) -> String {
+    format!("L{:04}", address)
+}
+
+/// Determine which addresses start an instruction by following control flow from address 0.
+/// Anything that can't be reached is assumed to be data. Returns the instruction addresses and
+/// the jump targets that need labels.
+///
+/// Flow stops after a halt or an unconditional jump. The address following an unconditional jump
+/// is only treated as code if some instruction copies it into memory as an immediate value, since
+/// that's how programs push a return address before calling a subroutine.
+pub(super) fn find_code(data: &[i64]) -> (HashSet<usize>, BTreeSet<usize>) {
+    let mut flow = Flow::default();
+    let mut pending = vec![0];
+
+    loop {
+        while let Some(address) = pending.pop() {
+            flow.trace(data, address, &mut pending);
+        }
+
+        let (covered, immediates) = (&flow.covered, &flow.immediates);
+        let (accepted, rejected) = flow
+            .return_candidates
+            .drain(..)
+            .filter(|address| !covered.contains(address))
+            .partition(|&address| immediates.contains(&(address as i64)));
+
+        pending = accepted;
+        flow.return_candidates = rejected;
+
+        if pending.is_empty() {
+            break;
+        }
+    }
+
+    (flow.code, flow.labels)
+}
+
+#[derive(Default)]
+struct Flow {
+    code: HashSet<usize>,
+    covered: HashSet<usize>,
+    labels: BTreeSet<usize>,
+    immediates: HashSet<i64>,
+    return_candidates: Vec<usize>,
+}
+
+impl Flow {
+    /// Follow straight-line execution from `address`, queueing up any jump targets found on the
+    /// way.
+    fn trace(&mut self, data: &[i64], mut address: usize, pending: &mut Vec<usize>) {
+        while address < data.len() {
+            let instruction = match Instruction::decode(address, &data[address..]) {
+                Ok(instruction) => instruction,
+                Err(_) => break,
+            };
+            let range = address..address + instruction.size();
+
+            if !instruction.is_canonical()
+                || range.end > data.len()
+                || range.clone().any(|a| self.covered.contains(&a))
+            {
+                break;
+            }
+
+            self.code.insert(address);
+            self.covered.extend(range);
+
+            if let Some(value) = copied_immediate(&instruction) {
+                self.immediates.insert(value);
+            }
+
+            if let Some(target) = instruction.jump_target() {
+                if target >= 0 && (target as usize) < data.len() {
+                    self.labels.insert(target as usize);
+                    pending.push(target as usize);
+                }
+            }
+
+            address += instruction.size();
+
+            if instruction.is_terminal() {
+                self.return_candidates.push(address);
+                break;
+            }
+        }
+    }
+}
+
+/// The value stored by the idioms `ADD #0, #n` and `MUL #1, #n`, which copy a constant into memory.
+pub(super) fn copied_immediate(instruction: &Instruction) -> Option<i64> {
+    match (instruction.opcode, instruction.inputs()) {
+        (Opcode::Add, [Param::Immediate(0), Param::Immediate(value)])
+        | (Opcode::Add, [Param::Immediate(value), Param::Immediate(0)])
+        | (Opcode::Mul, [Param::Immediate(1), Param::Immediate(value)])
+        | (Opcode::Mul, [Param::Immediate(value), Param::Immediate(1)]) => Some(*value),
+        _ => None,
+    }
+}
+
+fn format_instruction(instruction: &Instruction, labels: &BTreeSet<usize>) -> String {
+    let param = |param: Param| match param {
+        Param::Immediate(target)
+            if instruction.jump_target() == Some(target)
+                && target >= 0
+                && labels.contains(&(target as usize)) =>
+        {
+            format!("#{}", label(target as usize))
+        }
+        param => param.to_string(),
+    };
+
+    let mut output = instruction.opcode.mnemonic().to_string();
+
+    for (i, input) in instruction.inputs().iter().enumerate() {
+        output.push_str(if i == 0 { " " } else { ", " });
+        output.push_str(&param(*input));
+    }
+
+    if let Some(target) = instruction.output() {
+        output.push_str(" -> ");
+        output.push_str(&param(target));
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn disassemble_day5_example() {
+        let intcode = Intcode::new(vec![
+            3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9,
+        ]);
+
+        assert_eq!(
+            "0000: IN -> [12]
+0002: JF [12], [15]
+0005: ADD [13], [14] -> [13]
+0009: OUT [13]
+0011: HLT
+0012: DATA -1, 0, 1, 9
+",
+            disassemble(&intcode),
+        );
+    }
+
+    #[test]
+    fn disassemble_labels() {
+        let intcode = Intcode::new(vec![3, 12, 1005, 12, 9, 1101, 0, 0, 13, 4, 13, 99, 0, 1]);
+
+        assert_eq!(
+            "0000: IN -> [12]
+0002: JT [12], #L0009
+0005: ADD #0, #0 -> [13]
+L0009:
+0009: OUT [13]
+0011: HLT
+0012: DATA 0, 1
+",
+            disassemble(&intcode),
+        );
+    }
+
+    #[test]
+    fn disassemble_subroutine() {
+        // Push a return address, call a subroutine, then halt. The subroutine returns through an
+        // indirect jump.
+        let intcode = Intcode::new(vec![
+            109, 20, 21101, 0, 9, 0, 1105, 1, 10, 99, 204, 1, 2106, 0, 0,
+        ]);
+
+        assert_eq!(
+            "0000: ARB #20
+0002: ADD #0, #9 -> [rb+0]
+0006: JT #1, #L0010
+0009: HLT
+L0010:
+0010: OUT [rb+1]
+0012: JF #0, [rb+0]
+",
+            disassemble(&intcode),
+        );
+    }
+
+    #[test]
+    fn disassemble_inputs() {
+        for day in &[
+            include_str!("../day2/input.txt"),
+            include_str!("../day9/input.txt"),
+            include_str!("../day25/input.txt"),
+        ] {
+            let intcode: Intcode = day.parse().unwrap();
+            let listing = disassemble(&intcode);
+            assert!(listing.starts_with("0000: "));
+            assert!(listing.contains("ADD"));
+        }
+    }
+}
@@ -0,0 +1,384 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::ops::Range;
+use std::path::PathBuf;
+
+use super::disasm::find_code;
+use super::profile::Profiler;
+use super::{Intcode, Response, VmError};
+
+/// Instructions each run may execute before it's counted as a timeout.
+pub const DEFAULT_STEP_LIMIT: u64 = 1_000_000;
+
+/// The longest input sequence tried, in tokens.
+const MAX_TOKENS: usize = 64;
+
+/// A small xorshift generator, so fuzzing runs can be reproduced from their seed.
+#[derive(Clone, Debug)]
+pub(super) struct Rng(u64);
+
+impl Rng {
+    pub(super) fn new(seed: u64) -> Self {
+        Self(seed.max(1))
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// A number in `range`, which must not be empty.
+    pub(super) fn between(&mut self, range: Range<i64>) -> i64 {
+        let span = range.end.wrapping_sub(range.start) as u64;
+        range.start.wrapping_add((self.next_u64() % span) as i64)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+/// What the fuzzer builds input sequences from. Each token is one or more input values, and is
+/// the unit inputs are mutated in.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Alphabet {
+    /// Single numbers from a range.
+    Numbers(Range<i64>),
+
+    /// Lines of ASCII text, each followed by a newline, for programs such as day 25's that read
+    /// commands.
+    Lines(Vec<String>),
+}
+
+/// An input sequence that reached new code, along with the output it produced.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Finding {
+    pub input: Vec<Vec<i64>>,
+    pub output: Vec<i64>,
+}
+
+/// An input sequence that made the machine fault.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Crash {
+    pub input: Vec<Vec<i64>>,
+    pub error: VmError,
+}
+
+/// Feeds generated input sequences to a program, using the addresses each run executes to steer
+/// towards unexplored code. Sequences are built by mutating ones that found new code before:
+/// appending, inserting, replacing or removing tokens, or splicing two together.
+///
+/// Inputs that reach new code are kept in the corpus, and those that crash the machine are kept
+/// once for each distinct fault. Both are trimmed to the tokens the program actually read.
+pub struct Fuzzer {
+    program: Intcode,
+    alphabet: Alphabet,
+    seed: u64,
+    rng: Rng,
+    step_limit: u64,
+    out: Option<PathBuf>,
+    code: usize,
+    coverage: HashSet<usize>,
+    faults: HashSet<String>,
+    runs: u64,
+    timeouts: u64,
+    pub corpus: Vec<Finding>,
+    pub crashes: Vec<Crash>,
+}
+
+impl Fuzzer {
+    pub fn new(program: Intcode, alphabet: Alphabet, seed: u64) -> Result<Self, String> {
+        let empty = match &alphabet {
+            Alphabet::Numbers(range) => range.is_empty(),
+            Alphabet::Lines(lines) => lines.is_empty(),
+        };
+        if empty {
+            return Err("Nothing to build inputs from".to_string());
+        }
+
+        Ok(Self {
+            code: find_code(&program.data).0.len(),
+            program,
+            alphabet,
+            seed,
+            rng: Rng::new(seed),
+            step_limit: DEFAULT_STEP_LIMIT,
+            out: None,
+            coverage: HashSet::new(),
+            faults: HashSet::new(),
+            runs: 0,
+            timeouts: 0,
+            corpus: Vec::new(),
+            crashes: Vec::new(),
+        })
+    }
+
+    pub fn with_step_limit(mut self, step_limit: u64) -> Self {
+        self.step_limit = step_limit;
+        self
+    }
+
+    /// Save each new finding in `dir/corpus` and each crash in `dir/crashes`, as it's found.
+    pub fn with_output_dir(mut self, dir: &str) -> Result<Self, String> {
+        let dir = PathBuf::from(dir);
+        for subdir in &["corpus", "crashes"] {
+            fs::create_dir_all(dir.join(subdir))
+                .map_err(|e| format!("{}: {}", dir.display(), e))?;
+        }
+
+        self.out = Some(dir);
+        Ok(self)
+    }
+
+    /// Try `runs` more input sequences.
+    pub fn run(&mut self, runs: u64) -> Result<(), String> {
+        for _ in 0..runs {
+            let input = self.mutate();
+            self.try_input(input)?;
+        }
+
+        Ok(())
+    }
+
+    /// Run the program on `input`, keeping it if it finds anything new.
+    fn try_input(&mut self, mut input: Vec<Vec<i64>>) -> Result<(), String> {
+        let profiler = Profiler::new();
+        let values: Vec<(usize, i64)> = input
+            .iter()
+            .enumerate()
+            .flat_map(|(index, token)| token.iter().map(move |&value| (index, value)))
+            .collect();
+        let mut read = 0;
+        let mut output = Vec::new();
+
+        let result = self
+            .program
+            .clone()
+            .with_profiler(profiler.clone())
+            .with_io(
+                || {
+                    let value = values.get(read).map(|&(_, value)| value);
+                    read += value.is_some() as usize;
+                    value
+                },
+                |value| output.push(value),
+            )
+            .run_for(self.step_limit);
+
+        self.runs += 1;
+        input.truncate(match read {
+            0 => 0,
+            read => values[read - 1].0 + 1,
+        });
+
+        match result {
+            Ok(Response::BudgetExhausted) => self.timeouts += 1,
+            Ok(_) => {}
+            Err(error) => {
+                if self.faults.insert(error.to_string()) {
+                    self.save("crashes", self.crashes.len(), &input, None)?;
+                    self.crashes.push(Crash { input, error });
+                }
+                return Ok(());
+            }
+        }
+
+        let before = self.coverage.len();
+        self.coverage
+            .extend(profiler.profile().addresses.keys().copied());
+
+        if self.coverage.len() > before {
+            self.save("corpus", self.corpus.len(), &input, Some(&output))?;
+            self.corpus.push(Finding { input, output });
+        }
+
+        Ok(())
+    }
+
+    fn mutate(&mut self) -> Vec<Vec<i64>> {
+        let mut input = match self.corpus.len() {
+            0 => Vec::new(),
+            len => self.corpus[self.rng.below(len)].input.clone(),
+        };
+
+        for _ in 0..1 + self.rng.below(3) {
+            let token = self.token();
+
+            match self.rng.below(6) {
+                // Programs read their input in order, so most of what's left to explore lies past
+                // the end of the inputs found so far.
+                0..=2 => input.push(token),
+                3 if !input.is_empty() => {
+                    let index = self.rng.below(input.len());
+                    input[index] = token;
+                }
+                4 if !input.is_empty() => {
+                    input.remove(self.rng.below(input.len()));
+                }
+                5 if !self.corpus.is_empty() => {
+                    let other = &self.corpus[self.rng.below(self.corpus.len())].input;
+                    let split = self.rng.below(input.len().min(other.len()) + 1);
+                    input.truncate(split);
+                    input.extend(other[split..].iter().cloned());
+                }
+                _ => {
+                    let index = self.rng.below(input.len() + 1);
+                    input.insert(index, token);
+                }
+            }
+        }
+
+        input.truncate(MAX_TOKENS);
+        input
+    }
+
+    fn token(&mut self) -> Vec<i64> {
+        match &self.alphabet {
+            Alphabet::Numbers(range) => vec![self.rng.between(range.clone())],
+            Alphabet::Lines(lines) => {
+                let line = &lines[self.rng.below(lines.len())];
+                line.chars().chain("\n".chars()).map(|c| c as i64).collect()
+            }
+        }
+    }
+
+    /// Write out a finding or crash, if an output directory was given.
+    fn save(
+        &self,
+        kind: &str,
+        index: usize,
+        input: &[Vec<i64>],
+        output: Option<&[i64]>,
+    ) -> Result<(), String> {
+        let dir = match &self.out {
+            Some(dir) => dir.join(kind),
+            None => return Ok(()),
+        };
+
+        let path = dir.join(format!("{:04}.txt", index));
+        fs::write(&path, self.format(input)).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        if let Some(output) = output {
+            let path = dir.join(format!("{:04}.out", index));
+            fs::write(&path, self.format(&[output.to_vec()]))
+                .map_err(|e| format!("{}: {}", path.display(), e))?;
+        }
+
+        Ok(())
+    }
+
+    /// Tokens as a comma-separated list of numbers, or as text for `Alphabet::Lines`.
+    pub fn format(&self, tokens: &[Vec<i64>]) -> String {
+        let values = tokens.iter().flatten();
+
+        match self.alphabet {
+            Alphabet::Numbers(_) => {
+                let values: Vec<String> = values.map(|value| value.to_string()).collect();
+                format!("{}\n", values.join(","))
+            }
+            Alphabet::Lines(_) => values.map(|&value| (value as u8) as char).collect(),
+        }
+    }
+}
+
+/// ```text
+/// 2000 runs from seed 1, 7 of 7 instructions covered
+/// 2 inputs in the corpus, 1 crash, 0 timeouts
+///
+/// Unknown opcode in instruction 42 at offset 19!
+///   3,7
+/// ```
+impl fmt::Display for Fuzzer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{} runs from seed {}, {} of {} instructions covered",
+            self.runs,
+            self.seed,
+            self.coverage.len(),
+            self.code
+        )?;
+        writeln!(
+            f,
+            "{} inputs in the corpus, {} crash{}, {} timeouts",
+            self.corpus.len(),
+            self.crashes.len(),
+            if self.crashes.len() == 1 { "" } else { "es" },
+            self.timeouts
+        )?;
+
+        for crash in self.crashes.iter() {
+            writeln!(f, "\n{}", crash.error)?;
+            for line in self.format(&crash.input).lines() {
+                writeln!(f, "  {}", line)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Crashes if its first input is 3 and its second is 7.
+    fn lock() -> Intcode {
+        Intcode::new(vec![
+            3, 20, 1008, 20, 3, 21, 1006, 21, 18, 3, 20, 1008, 20, 7, 21, 1005, 21, 19, 99, 42, 0,
+            0,
+        ])
+    }
+
+    #[test]
+    fn finds_crash() {
+        let mut fuzzer = Fuzzer::new(lock(), Alphabet::Numbers(0..10), 1).unwrap();
+        fuzzer.run(2_000).unwrap();
+
+        assert_eq!(
+            vec![Crash {
+                input: vec![vec![3], vec![7]],
+                error: VmError::UnknownOpcode {
+                    cursor: 19,
+                    instruction: 42
+                },
+            }],
+            fuzzer.crashes
+        );
+        assert_eq!(
+            "2000 runs from seed 1, 7 of 7 instructions covered
+2 inputs in the corpus, 1 crash, 0 timeouts
+
+Unknown opcode in instruction 42 at offset 19!
+  3,7
+",
+            fuzzer.to_string()
+        );
+    }
+
+    #[test]
+    fn lines() {
+        // Echoes its input until it reads a line starting with "q".
+        let program = Intcode::new(vec![
+            3, 100, 4, 100, 1008, 100, 10, 101, 1005, 101, 14, 1105, 1, 0, 3, 100, 4, 100, 1008,
+            100, 113, 101, 1005, 101, 28, 1105, 1, 0, 99,
+        ]);
+        let alphabet = Alphabet::Lines(vec!["go".to_string(), "quit".to_string()]);
+        let mut fuzzer = Fuzzer::new(program, alphabet, 7).unwrap();
+        fuzzer.run(200).unwrap();
+
+        let last = fuzzer.corpus.last().unwrap();
+        assert_eq!("go\ngo\nquit\n", fuzzer.format(&last.input));
+        assert_eq!(
+            "go\ngo\nq",
+            fuzzer.format(std::slice::from_ref(&last.output))
+        );
+        assert_eq!(
+            Err("Nothing to build inputs from".to_string()),
+            Fuzzer::new(lock(), Alphabet::Numbers(0..0), 1).map(|_| ())
+        );
+    }
+}
@@ -0,0 +1,434 @@
+use std::collections::VecDeque;
+use std::mem;
+
+use super::{Intcode, Param, Response, VmError};
+
+/// Stop recording undo entries for the oldest steps once the history takes more than this many
+/// bytes.
+pub const DEFAULT_BUDGET: usize = 64 << 20;
+
+/// Steps between full checkpoints of the machine.
+pub const DEFAULT_CHECKPOINT_INTERVAL: usize = 100_000;
+
+/// Everything needed to reverse a single step.
+#[derive(Clone, Debug)]
+struct Undo {
+    steps: u64,
+    cursor: usize,
+    relative_base: i64,
+    data_len: usize,
+
+    /// The address written by the step and the value it held before.
+    write: Option<(usize, i64)>,
+    input: Option<i64>,
+    output: Option<i64>,
+}
+
+/// A full copy of the machine, followed by undo entries for every step taken after it.
+#[derive(Debug)]
+struct Segment {
+    checkpoint: Intcode,
+    undo: Vec<Undo>,
+}
+
+/// An undo log of the steps taken by a machine, allowing it to be wound back to an earlier step.
+///
+/// Steps are undone one at a time, except when winding back a long way, where it is quicker to
+/// restore the nearest full checkpoint and execute forwards from there. When the log outgrows its
+/// memory budget, the oldest checkpoint and the steps following it are forgotten.
+///
+/// What an instruction defined by a `Dialect` writes is up to its handler, so a checkpoint is
+/// taken before each one, and memory is restored from it when the instruction is undone.
+///
+/// Input and output are wound back by returning consumed input to the front of the queue and
+/// removing produced output from the end, so anything queued since is kept, but output that has
+/// been cleared can't be restored.
+#[derive(Debug)]
+pub struct History {
+    segments: VecDeque<Segment>,
+    budget: usize,
+    checkpoint_interval: usize,
+    bytes: usize,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new(DEFAULT_BUDGET, DEFAULT_CHECKPOINT_INTERVAL)
+    }
+}
+
+impl History {
+    pub fn new(budget: usize, checkpoint_interval: usize) -> Self {
+        Self {
+            segments: VecDeque::new(),
+            budget,
+            checkpoint_interval: checkpoint_interval.max(1),
+            bytes: 0,
+        }
+    }
+
+    /// Forget every step recorded so far.
+    pub fn clear(&mut self) {
+        self.segments.clear();
+        self.bytes = 0;
+    }
+
+    /// The earliest step the machine can be wound back to, if any steps have been recorded.
+    pub fn earliest(&self) -> Option<u64> {
+        self.segments
+            .front()
+            .map(|segment| segment.checkpoint.steps)
+    }
+
+    /// An estimate of the memory used by the history, in bytes.
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Execute one instruction, recording how to undo it.
+    pub fn step(&mut self, intcode: &mut Intcode) -> Result<Option<Response>, VmError> {
+        let relative_base = intcode.relative_base();
+        let write = intcode
+            .instruction(intcode.cursor())
+            .ok()
+            .and_then(|instruction| instruction.output())
+            .map(|param| match param {
+                Param::Relative(offset) => offset.wrapping_add(relative_base),
+                param => param.value(),
+            })
+            .filter(|&address| address >= 0)
+            .map(|address| (address as usize, intcode.get(address as usize)));
+
+        let mut undo = Undo {
+            steps: intcode.steps,
+            cursor: intcode.cursor(),
+            relative_base,
+            data_len: intcode.data.len(),
+            write,
+            input: intcode.input.front().copied(),
+            output: None,
+        };
+        let (input_len, output_len) = (intcode.input.len(), intcode.output.len());
+
+        let custom = intcode
+            .dialect
+            .as_ref()
+            .is_some_and(|dialect| dialect.defines(intcode.get(intcode.cursor())));
+        if self.segments.back().is_none_or(|segment| {
+            segment.undo.len() >= self.checkpoint_interval || (custom && !segment.undo.is_empty())
+        }) {
+            self.bytes += checkpoint_bytes(intcode);
+            self.segments.push_back(Segment {
+                checkpoint: intcode.clone(),
+                undo: Vec::new(),
+            });
+        }
+
+        let result = intcode.step();
+
+        if intcode.input.len() == input_len {
+            undo.input = None;
+        }
+        if intcode.output.len() > output_len {
+            undo.output = intcode.output.last().copied();
+        }
+
+        self.segments.back_mut().unwrap().undo.push(undo);
+        self.bytes += mem::size_of::<Undo>();
+
+        while self.bytes > self.budget && self.segments.len() > 1 {
+            let segment = self.segments.pop_front().unwrap();
+            self.bytes -=
+                checkpoint_bytes(&segment.checkpoint) + segment.undo.len() * mem::size_of::<Undo>();
+        }
+
+        result
+    }
+
+    /// Undo the most recent step. Returns false if there is nothing left to undo.
+    pub fn step_back(&mut self, intcode: &mut Intcode) -> bool {
+        loop {
+            let segment = match self.segments.back_mut() {
+                Some(segment) => segment,
+                None => return false,
+            };
+
+            match segment.undo.pop() {
+                Some(undo) => {
+                    self.bytes -= mem::size_of::<Undo>();
+                    let checkpoint = Some(&segment.checkpoint).filter(|_| segment.undo.is_empty());
+                    apply(intcode, undo, checkpoint);
+                    return true;
+                }
+                None => {
+                    let segment = self.segments.pop_back().unwrap();
+                    self.bytes -= checkpoint_bytes(&segment.checkpoint);
+                }
+            }
+        }
+    }
+
+    /// Wind the machine back to the point just before it executed step number `step`, ie. when
+    /// `intcode.steps` was `step`.
+    pub fn goto(&mut self, intcode: &mut Intcode, step: u64) -> Result<(), String> {
+        match self.earliest() {
+            Some(earliest) if step >= earliest => {}
+            Some(earliest) => {
+                return Err(format!(
+                    "Step {} is no longer recorded, the earliest is {}",
+                    step, earliest
+                ))
+            }
+            None => return Err("No steps have been recorded".to_string()),
+        }
+        if step > intcode.steps {
+            return Err(format!("Step {} hasn't been executed yet", step));
+        }
+
+        let index = self
+            .segments
+            .iter()
+            .rposition(|segment| segment.checkpoint.steps <= step)
+            .unwrap();
+        let checkpoint_steps = self.segments[index].checkpoint.steps;
+
+        if intcode.steps - step <= step - checkpoint_steps {
+            while intcode.steps > step && self.step_back(intcode) {}
+            return Ok(());
+        }
+
+        // Replay forwards from the checkpoint. Only memory and registers are taken from the
+        // replayed machine; input and output are wound back from the log, as `step_back` would.
+        let kept = (step - checkpoint_steps) as usize;
+        let undone: Vec<Undo> = self
+            .segments
+            .iter()
+            .skip(index)
+            .flat_map(|segment| segment.undo.iter())
+            .skip(kept)
+            .cloned()
+            .collect();
+
+        // The checkpoint shares the tracer, profiler and recorder with the machine, which
+        // shouldn't see the replayed steps a second time.
+        let mut replay = self.segments[index].checkpoint.clone();
+        replay.tracer = None;
+        replay.profiler = None;
+        replay.recorder = None;
+        replay.input = self.segments[index]
+            .undo
+            .iter()
+            .filter_map(|undo| undo.input)
+            .collect();
+        while replay.steps < step {
+            // Faults are replayed along with everything else.
+            let _ = replay.step();
+        }
+
+        for undo in undone.iter().rev() {
+            if let Some(value) = undo.input {
+                intcode.input.push_front(value);
+            }
+            if undo.output.is_some() && intcode.output.last().copied() == undo.output {
+                intcode.output.pop();
+            }
+        }
+        replay.input = mem::take(&mut intcode.input);
+        replay.output = mem::take(&mut intcode.output);
+        replay.tracer = intcode.tracer.take();
+        replay.profiler = intcode.profiler.take();
+        replay.recorder = intcode.recorder.take();
+        *intcode = replay;
+
+        while self.segments.len() > index + 1 {
+            let segment = self.segments.pop_back().unwrap();
+            self.bytes -=
+                checkpoint_bytes(&segment.checkpoint) + segment.undo.len() * mem::size_of::<Undo>();
+        }
+        let segment = &mut self.segments[index];
+        self.bytes -= (segment.undo.len() - kept) * mem::size_of::<Undo>();
+        segment.undo.truncate(kept);
+
+        Ok(())
+    }
+}
+
+/// Reverse a step. When it was the first step after `checkpoint`, memory is copied back from the
+/// checkpoint instead, which also undoes writes the entry doesn't know about.
+fn apply(intcode: &mut Intcode, undo: Undo, checkpoint: Option<&Intcode>) {
+    if let Some(checkpoint) = checkpoint {
+        intcode.data = checkpoint.data.clone();
+        intcode.pages = checkpoint.pages.clone();
+    } else {
+        if let Some((address, value)) = undo.write {
+            intcode.set(address, value);
+        }
+        // Words the step moved out of the pages as dense memory grew go back into them.
+        intcode.pages.shrink(&mut intcode.data, undo.data_len);
+    }
+
+    if let Some(value) = undo.input {
+        intcode.input.push_front(value);
+    }
+    if undo.output.is_some() && intcode.output.last().copied() == undo.output {
+        intcode.output.pop();
+    }
+
+    intcode.steps = undo.steps;
+    intcode.set_cursor(undo.cursor);
+    intcode.set_relative_base(undo.relative_base);
+}
+
+fn checkpoint_bytes(intcode: &Intcode) -> usize {
+    mem::size_of::<Segment>()
+        + intcode.memory_stats().bytes()
+        + (intcode.input.len() + intcode.output.len()) * mem::size_of::<i64>()
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::dialect::Dialect;
+    use super::*;
+
+    fn countdown() -> Intcode {
+        // Reads a number, then counts it down to zero, printing each value.
+        Intcode::new(vec![3, 12, 4, 12, 1001, 12, -1, 12, 1005, 12, 2, 99, 0]).with_input(&[3])
+    }
+
+    fn run(history: &mut History, intcode: &mut Intcode) -> Vec<Intcode> {
+        let mut states = vec![intcode.clone()];
+        while history.step(intcode).unwrap().is_none() {
+            states.push(intcode.clone());
+        }
+        states
+    }
+
+    fn assert_state(expected: &Intcode, actual: &Intcode) {
+        assert_eq!(expected.data, actual.data);
+        assert_eq!(expected.input, actual.input);
+        assert_eq!(expected.output, actual.output);
+        assert_eq!(expected.steps, actual.steps);
+        assert_eq!(expected.cursor(), actual.cursor());
+        assert_eq!(expected.relative_base(), actual.relative_base());
+    }
+
+    #[test]
+    fn step_back() {
+        let mut history = History::default();
+        let mut intcode = countdown();
+        let mut states = run(&mut history, &mut intcode);
+
+        assert_eq!(vec![3, 2, 1], intcode.output);
+        assert_eq!(Some(0), history.earliest());
+
+        // The halt itself is undone first.
+        assert!(history.step_back(&mut intcode));
+        while let Some(state) = states.pop() {
+            assert_state(&state, &intcode);
+            history.step_back(&mut intcode);
+        }
+        assert!(!history.step_back(&mut intcode));
+    }
+
+    #[test]
+    fn goto() {
+        // A checkpoint every three steps, so that some targets are reached by undoing steps and
+        // others by replaying from a checkpoint.
+        let history = || History::new(DEFAULT_BUDGET, 3);
+        let states = run(&mut history(), &mut countdown());
+
+        for &step in &[10, 9, 7, 4, 1, 0] {
+            let mut history = history();
+            let mut intcode = countdown();
+            run(&mut history, &mut intcode);
+
+            history.goto(&mut intcode, step).unwrap();
+            assert_state(&states[step as usize], &intcode);
+        }
+
+        let mut history = history();
+        let mut intcode = countdown();
+        run(&mut history, &mut intcode);
+
+        history.goto(&mut intcode, 5).unwrap();
+        assert_eq!(
+            Err("Step 8 hasn't been executed yet".to_string()),
+            history.goto(&mut intcode, 8),
+        );
+
+        // Execution continues from the earlier step as if nothing had happened since.
+        run(&mut history, &mut intcode);
+        assert_eq!(vec![3, 2, 1], intcode.output);
+        history.goto(&mut intcode, 2).unwrap();
+        assert_state(&states[2], &intcode);
+    }
+
+    #[test]
+    fn goto_detaches_profiler() {
+        let profiler = super::super::profile::Profiler::new();
+        let mut history = History::new(DEFAULT_BUDGET, 3);
+        let mut intcode = countdown().with_profiler(profiler.clone());
+        run(&mut history, &mut intcode);
+        let steps = profiler.profile().steps;
+
+        // Replayed from the checkpoint at step 6.
+        history.goto(&mut intcode, 7).unwrap();
+        assert_eq!(steps, profiler.profile().steps);
+
+        intcode.step().unwrap();
+        assert_eq!(steps + 1, profiler.profile().steps);
+    }
+
+    #[test]
+    fn undo_growth() {
+        // Writes to 1600, growing dense memory over the page holding 1500: first straight after
+        // a checkpoint, then after a JF #1 that doesn't jump.
+        for program in [
+            vec![1101, 0, 7, 1600, 99],
+            vec![1106, 1, 0, 1101, 0, 7, 1600, 99],
+        ] {
+            let mut intcode = Intcode::new(program);
+            intcode.set(1500, 5);
+            intcode.set(900, 1);
+
+            let mut history = History::default();
+            while intcode.get(1600) == 0 {
+                history.step(&mut intcode).unwrap();
+            }
+
+            assert!(history.step_back(&mut intcode));
+            assert_eq!(5, intcode.get(1500));
+            assert_eq!(0, intcode.get(1600));
+            assert_eq!(901, intcode.data.len());
+        }
+    }
+
+    #[test]
+    fn undo_custom() {
+        // POLL -> [5], then halt.
+        let mut intcode = Intcode::new(vec![93, 5, 99, 0, 0, 7])
+            .with_dialect(Dialect::extended())
+            .with_input(&[42]);
+        let mut history = History::default();
+        let states = run(&mut history, &mut intcode);
+        assert_eq!(42, intcode.get(5));
+
+        assert!(history.step_back(&mut intcode));
+        assert!(history.step_back(&mut intcode));
+        assert_state(&states[0], &intcode);
+        assert_eq!(7, intcode.get(5));
+    }
+
+    #[test]
+    fn budget() {
+        let mut history = History::new(1, 2);
+        let mut intcode = countdown();
+        run(&mut history, &mut intcode);
+
+        assert_eq!(Some(10), history.earliest());
+        assert_eq!(
+            Err("Step 3 is no longer recorded, the earliest is 10".to_string()),
+            history.goto(&mut intcode, 3),
+        );
+    }
+}
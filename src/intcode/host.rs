@@ -0,0 +1,313 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::dialect::{Action, Dialect, Machine, Role};
+use super::fuzz::Rng;
+
+/// The opcode `Host::dialect` uses for host calls: `SYS f, a, b, c -> r` calls host function `f`
+/// with the arguments `a`, `b` and `c`, and stores what it returns at `r`.
+pub const SYSCALL: i64 = 80;
+
+/// `PRINT address, length`: print the ASCII string held one character per word at `address`.
+/// Returns the number of characters printed.
+pub const PRINT: i64 = 1;
+
+/// `RANDOM low, high`: a random number from `low` up to but not including `high`.
+pub const RANDOM: i64 = 2;
+
+/// `CLOCK`: the time in milliseconds since the Unix epoch.
+pub const CLOCK: i64 = 3;
+
+/// `READ_FILE address, length, destination`: read the file whose path is the string at `address`
+/// into memory one byte per word from `destination`. Returns the number of bytes read, or -1 if
+/// the file couldn't be read.
+pub const READ_FILE: i64 = 4;
+
+/// A host function, given the machine and the three arguments of the host call.
+pub type HostFn = dyn Fn(&mut dyn Machine, [i64; 3]) -> Result<i64, String> + Send + Sync;
+
+/// A table of Rust functions Intcode programs can call, using a `SYS` instruction added to the
+/// machine's dialect. This saves programs and their callers from agreeing on a protocol over input
+/// and output for anything other than plain numbers.
+#[derive(Clone, Default)]
+pub struct Host {
+    functions: BTreeMap<i64, (String, Arc<HostFn>)>,
+}
+
+impl Host {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The standard host functions, with `PRINT` writing to `output` and `RANDOM` seeded with
+    /// `seed`.
+    pub fn standard(output: impl Write + Send + 'static, seed: u64) -> Self {
+        let output = Mutex::new(output);
+        let rng = Mutex::new(Rng::new(seed));
+        let mut host = Self::new();
+
+        host.register(PRINT, "print", move |machine, [address, length, _]| {
+            let text = string(machine, address, length)?;
+            let mut output = output.lock().unwrap();
+            output
+                .write_all(text.as_bytes())
+                .and_then(|_| output.flush())
+                .map_err(|e| e.to_string())?;
+            Ok(text.len() as i64)
+        })
+        .unwrap();
+
+        host.register(RANDOM, "random", move |_, [low, high, _]| {
+            if high <= low {
+                return Err(format!("Empty range {}..{}", low, high));
+            }
+
+            Ok(rng.lock().unwrap().between(low..high))
+        })
+        .unwrap();
+
+        host.register(CLOCK, "clock", |_, _| {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_err(|e| e.to_string())?;
+            Ok(now.as_millis() as i64)
+        })
+        .unwrap();
+
+        host.register(
+            READ_FILE,
+            "read_file",
+            |machine, [address, length, destination]| {
+                let path = string(machine, address, length)?;
+                let start = word_address(destination)?;
+
+                Ok(match fs::read(&path) {
+                    Ok(bytes) => {
+                        destination.checked_add(bytes.len() as i64).ok_or_else(|| {
+                            format!(
+                                "{} bytes from {} don't fit in memory at {}",
+                                bytes.len(),
+                                path,
+                                destination
+                            )
+                        })?;
+                        for (offset, &byte) in bytes.iter().enumerate() {
+                            machine.set(start + offset, byte as i64);
+                        }
+                        bytes.len() as i64
+                    }
+                    Err(_) => -1,
+                })
+            },
+        )
+        .unwrap();
+
+        host
+    }
+
+    /// Make `function` available to programs as host function number `number`.
+    pub fn register(
+        &mut self,
+        number: i64,
+        name: &str,
+        function: impl Fn(&mut dyn Machine, [i64; 3]) -> Result<i64, String> + Send + Sync + 'static,
+    ) -> Result<(), String> {
+        if let Some((existing, _)) = self.functions.get(&number) {
+            return Err(format!(
+                "Host function {} is already registered as {}",
+                number, existing
+            ));
+        }
+
+        self.functions
+            .insert(number, (name.to_string(), Arc::new(function)));
+        Ok(())
+    }
+
+    /// Define `code` in `dialect` as the host call instruction.
+    pub fn install(self, dialect: &mut Dialect, code: i64) -> Result<(), String> {
+        dialect.define(
+            code,
+            "SYS",
+            &[Role::Read, Role::Read, Role::Read, Role::Read, Role::Write],
+            move |machine, args| {
+                let (_, function) = self
+                    .functions
+                    .get(&args[0])
+                    .ok_or_else(|| format!("Unknown host function {}", args[0]))?;
+
+                function(machine, [args[1], args[2], args[3]])
+                    .map(|result| Action::Next(vec![result]))
+            },
+        )
+    }
+
+    /// A dialect of standard Intcode plus host calls using `SYSCALL`.
+    pub fn dialect(self) -> Dialect {
+        let mut dialect = Dialect::new();
+        self.install(&mut dialect, SYSCALL).unwrap();
+        dialect
+    }
+}
+
+/// Lists the registered functions, eg. `Host(1 print, 3 clock)`.
+impl fmt::Debug for Host {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let functions: Vec<String> = self
+            .functions
+            .iter()
+            .map(|(number, (name, _))| format!("{} {}", number, name))
+            .collect();
+        write!(f, "Host({})", functions.join(", "))
+    }
+}
+
+fn word_address(address: i64) -> Result<usize, String> {
+    if address < 0 {
+        Err(format!("Negative address {}", address))
+    } else {
+        Ok(address as usize)
+    }
+}
+
+/// Read `length` ASCII characters stored one per word from `address`.
+fn string(machine: &dyn Machine, address: i64, length: i64) -> Result<String, String> {
+    let start = word_address(address)?;
+    word_address(length).map_err(|_| format!("Negative length {}", length))?;
+    let end = address.checked_add(length).ok_or_else(|| {
+        format!(
+            "String of length {} at {} doesn't fit in memory",
+            length, address
+        )
+    })?;
+
+    (start..end as usize)
+        .map(|a| match machine.get(a) {
+            c @ 0..=127 => Ok(c as u8 as char),
+            c => Err(format!("Non-ASCII character {} at {}", c, a)),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{Intcode, Response};
+    use super::*;
+    use std::env;
+    use std::io;
+
+    /// A writer that can be inspected after the host has finished with it.
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn standard() {
+        let output = Shared::default();
+        let host = Host::standard(output.clone(), 42);
+        assert_eq!(
+            "Host(1 print, 2 random, 3 clock, 4 read_file)",
+            format!("{:?}", host)
+        );
+
+        let path = env::temp_dir().join(format!("intcode-host-{}", std::process::id()));
+        fs::write(&path, "hi").unwrap();
+        let path = path.to_str().unwrap();
+
+        // Print "Hello\n", pick a random number from 1 to 6, read the clock, and read the file at
+        // the path stored at 50.
+        let mut program = vec![
+            111180, 1, 40, 6, 0, 30, // SYS #PRINT, #40, #6, #0 -> [30]
+            111180, 2, 1, 7, 0, 31, // SYS #RANDOM, #1, #7, #0 -> [31]
+            111180, 3, 0, 0, 0, 32, // SYS #CLOCK -> [32]
+            111180, 4, 50, 0, 200, 33, // SYS #READ_FILE, #50, #length, #200 -> [33]
+            99, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        ];
+        program[21] = path.len() as i64;
+        program.extend("Hello\n".chars().map(|c| c as i64));
+        program.extend(vec![0; 4]);
+        program.extend(path.chars().map(|c| c as i64));
+
+        let mut intcode = Intcode::new(program).with_dialect(host.dialect());
+        assert_eq!(Ok(Response::Terminated), intcode.run());
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(b"Hello\n".to_vec(), *output.0.lock().unwrap());
+        assert_eq!(6, intcode.get(30));
+        assert!((1..7).contains(&intcode.get(31)));
+        assert!(intcode.get(32) > 1_500_000_000_000);
+        assert_eq!(2, intcode.get(33));
+        assert_eq!(
+            vec!['h' as i64, 'i' as i64],
+            intcode.data[200..202].to_vec()
+        );
+    }
+
+    #[test]
+    fn overflow() {
+        let path = env::temp_dir().join(format!("intcode-host-overflow-{}", std::process::id()));
+        fs::write(&path, "hi").unwrap();
+        let path = path.to_str().unwrap();
+
+        // SYS #READ_FILE, #7, #length, #MAX-1 -> [0]
+        let mut program = vec![111180, 4, 7, 0, i64::MAX - 1, 0, 99];
+        program[3] = path.len() as i64;
+        program.extend(path.chars().map(|c| c as i64));
+
+        let mut intcode =
+            Intcode::new(program).with_dialect(Host::standard(io::sink(), 1).dialect());
+        let result = intcode.run().map_err(String::from);
+        fs::remove_file(path).unwrap();
+        assert_eq!(
+            Err(format!(
+                "2 bytes from {} don't fit in memory at {} in instruction 111180 at offset 0!",
+                path,
+                i64::MAX - 1
+            )),
+            result
+        );
+
+        // SYS #PRINT, #MAX, #2 -> [0]
+        let mut intcode = Intcode::new(vec![111180, 1, i64::MAX, 2, 0, 0])
+            .with_dialect(Host::standard(io::sink(), 1).dialect());
+        assert_eq!(
+            Err(format!(
+                "String of length 2 at {} doesn't fit in memory in instruction 111180 at offset 0!",
+                i64::MAX
+            )),
+            intcode.run().map_err(String::from)
+        );
+    }
+
+    #[test]
+    fn custom() {
+        let mut host = Host::new();
+        host.register(7, "add", |_, [a, b, c]| Ok(a + b + c))
+            .unwrap();
+        assert_eq!(
+            Err("Host function 7 is already registered as add".to_string()),
+            host.register(7, "sub", |_, [a, b, _]| Ok(a - b))
+        );
+
+        let mut intcode = Intcode::new(vec![111180, 7, 1, 2, 3, 12, 111180, 8, 0, 0, 0, 12, 0])
+            .with_dialect(host.dialect());
+        assert_eq!(
+            Err("Unknown host function 8 in instruction 111180 at offset 6!".to_string()),
+            intcode.run().map_err(String::from)
+        );
+        assert_eq!(6, intcode.get(12));
+    }
+}
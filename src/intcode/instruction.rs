@@ -0,0 +1,364 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+use super::{InstructionMode, Intcode, VmError};
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Opcode {
+    Add,
+    Mul,
+    Input,
+    Output,
+    JumpIfTrue,
+    JumpIfFalse,
+    LessThan,
+    Equals,
+    AdjustRelativeBase,
+    Halt,
+}
+
+impl Opcode {
+    pub const ALL: &'static [Opcode] = &[
+        Opcode::Add,
+        Opcode::Mul,
+        Opcode::Input,
+        Opcode::Output,
+        Opcode::JumpIfTrue,
+        Opcode::JumpIfFalse,
+        Opcode::LessThan,
+        Opcode::Equals,
+        Opcode::AdjustRelativeBase,
+        Opcode::Halt,
+    ];
+
+    pub fn code(self) -> i64 {
+        match self {
+            Opcode::Add => 1,
+            Opcode::Mul => 2,
+            Opcode::Input => 3,
+            Opcode::Output => 4,
+            Opcode::JumpIfTrue => 5,
+            Opcode::JumpIfFalse => 6,
+            Opcode::LessThan => 7,
+            Opcode::Equals => 8,
+            Opcode::AdjustRelativeBase => 9,
+            Opcode::Halt => 99,
+        }
+    }
+
+    pub fn mnemonic(self) -> &'static str {
+        match self {
+            Opcode::Add => "ADD",
+            Opcode::Mul => "MUL",
+            Opcode::Input => "IN",
+            Opcode::Output => "OUT",
+            Opcode::JumpIfTrue => "JT",
+            Opcode::JumpIfFalse => "JF",
+            Opcode::LessThan => "LT",
+            Opcode::Equals => "EQ",
+            Opcode::AdjustRelativeBase => "ARB",
+            Opcode::Halt => "HLT",
+        }
+    }
+
+    pub fn param_count(self) -> usize {
+        match self {
+            Opcode::Add | Opcode::Mul | Opcode::LessThan | Opcode::Equals => 3,
+            Opcode::JumpIfTrue | Opcode::JumpIfFalse => 2,
+            Opcode::Input | Opcode::Output | Opcode::AdjustRelativeBase => 1,
+            Opcode::Halt => 0,
+        }
+    }
+
+    /// The index of the parameter this opcode writes to, if any.
+    pub fn output_param(self) -> Option<usize> {
+        match self {
+            Opcode::Add | Opcode::Mul | Opcode::LessThan | Opcode::Equals => Some(2),
+            Opcode::Input => Some(0),
+            _ => None,
+        }
+    }
+}
+
+impl TryFrom<i64> for Opcode {
+    type Error = i64;
+
+    fn try_from(value: i64) -> Result<Self, Self::Error> {
+        Opcode::ALL
+            .iter()
+            .find(|opcode| opcode.code() == value)
+            .copied()
+            .ok_or(value)
+    }
+}
+
+impl fmt::Display for Opcode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.mnemonic())
+    }
+}
+
+/// A single decoded parameter, carrying the raw value found in memory.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Param {
+    Position(i64),
+    Immediate(i64),
+    Relative(i64),
+}
+
+impl Param {
+    pub fn value(self) -> i64 {
+        match self {
+            Param::Position(value) | Param::Immediate(value) | Param::Relative(value) => value,
+        }
+    }
+
+    /// The mode digit used to encode this parameter.
+    pub fn mode(self) -> i64 {
+        match self {
+            Param::Position(_) => 0,
+            Param::Immediate(_) => 1,
+            Param::Relative(_) => 2,
+        }
+    }
+}
+
+/// ```text
+/// #5       immediate
+/// [104]    position
+/// [rb+3]   relative
+/// ```
+impl fmt::Display for Param {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Param::Position(value) => write!(f, "[{}]", value),
+            Param::Immediate(value) => write!(f, "#{}", value),
+            Param::Relative(value) if *value < 0 => write!(f, "[rb{}]", value),
+            Param::Relative(value) => write!(f, "[rb+{}]", value),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Instruction {
+    pub address: usize,
+    pub raw: i64,
+    pub opcode: Opcode,
+    params: [Param; 3],
+}
+
+impl Instruction {
+    /// Decode the instruction at `address`. `words` holds the memory starting at that address;
+    /// anything past its end reads as zero, as it would in the machine.
+    pub fn decode(address: usize, words: &[i64]) -> Result<Self, VmError> {
+        let raw = words.first().copied().unwrap_or(0);
+        let opcode = Opcode::try_from(raw % 100).map_err(|_| VmError::UnknownOpcode {
+            cursor: address,
+            instruction: raw,
+        })?;
+
+        let mut params = [Param::Immediate(0); 3];
+
+        for (param_index, param) in params.iter_mut().enumerate().take(opcode.param_count()) {
+            let value = words.get(param_index + 1).copied().unwrap_or(0);
+
+            *param = match Intcode::get_mode(raw, param_index) {
+                Ok(InstructionMode::Position) => Param::Position(value),
+                Ok(InstructionMode::Immediate) if opcode.output_param() == Some(param_index) => {
+                    return Err(VmError::ImmediateWrite {
+                        cursor: address,
+                        instruction: raw,
+                        param_index,
+                    })
+                }
+                Ok(InstructionMode::Immediate) => Param::Immediate(value),
+                Ok(InstructionMode::Relative) => Param::Relative(value),
+                Err(mode) => {
+                    return Err(VmError::InvalidMode {
+                        cursor: address,
+                        instruction: raw,
+                        param_index,
+                        mode,
+                    })
+                }
+            };
+        }
+
+        Ok(Self {
+            address,
+            raw,
+            opcode,
+            params,
+        })
+    }
+
+    /// Encode an opcode and its parameters as they would appear in memory.
+    pub fn encode(opcode: Opcode, params: &[Param]) -> Vec<i64> {
+        let mut words = Vec::with_capacity(params.len() + 1);
+
+        words.push(
+            params
+                .iter()
+                .enumerate()
+                .fold(opcode.code(), |raw, (param_index, param)| {
+                    raw + param.mode() * 10i64.pow(param_index as u32 + 2)
+                }),
+        );
+        words.extend(params.iter().map(|param| param.value()));
+        words
+    }
+
+    /// True if the raw instruction is exactly what `encode` would produce, ie. it has no stray mode
+    /// digits for parameters the opcode doesn't take.
+    pub fn is_canonical(&self) -> bool {
+        Self::encode(self.opcode, self.params())[0] == self.raw
+    }
+
+    pub fn params(&self) -> &[Param] {
+        &self.params[..self.opcode.param_count()]
+    }
+
+    /// The number of words the instruction occupies, including the opcode itself.
+    pub fn size(&self) -> usize {
+        self.opcode.param_count() + 1
+    }
+
+    /// The parameters that are read from, excluding any output parameter.
+    pub fn inputs(&self) -> &[Param] {
+        match self.opcode.output_param() {
+            Some(index) => &self.params[..index],
+            None => self.params(),
+        }
+    }
+
+    /// The parameter that is written to, if any.
+    pub fn output(&self) -> Option<Param> {
+        self.opcode.output_param().map(|index| self.params[index])
+    }
+
+    /// The target of a jump instruction, if it is known without running the program.
+    pub fn jump_target(&self) -> Option<i64> {
+        match (self.opcode, self.params[1]) {
+            (Opcode::JumpIfTrue, Param::Immediate(target))
+            | (Opcode::JumpIfFalse, Param::Immediate(target)) => Some(target),
+            _ => None,
+        }
+    }
+
+    /// True if execution never continues to the following instruction: halts, and jumps whose
+    /// condition is an immediate value that always takes the branch.
+    pub fn is_terminal(&self) -> bool {
+        match (self.opcode, self.params[0]) {
+            (Opcode::Halt, _) => true,
+            (Opcode::JumpIfTrue, Param::Immediate(value)) => value != 0,
+            (Opcode::JumpIfFalse, Param::Immediate(value)) => value == 0,
+            _ => false,
+        }
+    }
+}
+
+/// ```text
+/// ADD [rb+3], #5 -> [104]
+/// ```
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.opcode.mnemonic())?;
+
+        for (i, param) in self.inputs().iter().enumerate() {
+            write!(f, "{}{}", if i == 0 { " " } else { ", " }, param)?;
+        }
+
+        if let Some(output) = self.output() {
+            write!(f, " -> {}", output)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn decode() {
+        let instruction = Instruction::decode(10, &[21101, 3, 5, 104]).unwrap();
+        assert_eq!(Opcode::Add, instruction.opcode);
+        assert_eq!(
+            &[
+                Param::Immediate(3),
+                Param::Immediate(5),
+                Param::Relative(104)
+            ],
+            instruction.params(),
+        );
+        assert_eq!(4, instruction.size());
+        assert_eq!("ADD #3, #5 -> [rb+104]", instruction.to_string());
+    }
+
+    #[test]
+    fn encode() {
+        assert_eq!(
+            vec![21101, 3, 5, 104],
+            Instruction::encode(
+                Opcode::Add,
+                &[
+                    Param::Immediate(3),
+                    Param::Immediate(5),
+                    Param::Relative(104)
+                ],
+            ),
+        );
+        assert!(Instruction::decode(0, &[1005, 7, 9])
+            .unwrap()
+            .is_canonical());
+        assert!(!Instruction::decode(0, &[10099]).unwrap().is_canonical());
+    }
+
+    #[test]
+    fn decode_short() {
+        let instruction = Instruction::decode(0, &[1005, 7]).unwrap();
+        assert_eq!(
+            &[Param::Position(7), Param::Immediate(0)],
+            instruction.params()
+        );
+        assert_eq!(Some(0), instruction.jump_target());
+    }
+
+    #[test]
+    fn decode_errors() {
+        assert_eq!(
+            Err(VmError::UnknownOpcode {
+                cursor: 3,
+                instruction: 42
+            }),
+            Instruction::decode(3, &[42]),
+        );
+        assert_eq!(
+            Err(VmError::ImmediateWrite {
+                cursor: 0,
+                instruction: 103,
+                param_index: 0,
+            }),
+            Instruction::decode(0, &[103, 0]),
+        );
+        assert_eq!(
+            Err(VmError::InvalidMode {
+                cursor: 0,
+                instruction: 304,
+                param_index: 0,
+                mode: 3,
+            }),
+            Instruction::decode(0, &[304, 0]),
+        );
+    }
+
+    #[test]
+    fn is_terminal() {
+        assert!(Instruction::decode(0, &[99]).unwrap().is_terminal());
+        assert!(Instruction::decode(0, &[1105, 1, 9]).unwrap().is_terminal());
+        assert!(Instruction::decode(0, &[1106, 0, 9]).unwrap().is_terminal());
+        assert!(!Instruction::decode(0, &[1005, 7, 9]).unwrap().is_terminal());
+        assert!(!Instruction::decode(0, &[1106, 1, 9]).unwrap().is_terminal());
+    }
+}
@@ -0,0 +1,158 @@
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Write};
+use std::sync::mpsc::{Receiver, Sender};
+
+/// Where an `Intcode` machine reads its input from. Returning `None` means no value is available
+/// yet: the machine stops with `Response::InputRequired` and retries the same instruction when it
+/// is next run.
+pub trait IntcodeInput {
+    fn read(&mut self) -> Option<i64>;
+}
+
+/// Where an `Intcode` machine sends its output.
+pub trait IntcodeOutput {
+    fn write(&mut self, value: i64);
+}
+
+impl IntcodeInput for VecDeque<i64> {
+    fn read(&mut self) -> Option<i64> {
+        self.pop_front()
+    }
+}
+
+impl IntcodeOutput for VecDeque<i64> {
+    fn write(&mut self, value: i64) {
+        self.push_back(value);
+    }
+}
+
+impl IntcodeOutput for Vec<i64> {
+    fn write(&mut self, value: i64) {
+        self.push(value);
+    }
+}
+
+/// Collects output as ASCII text.
+impl IntcodeOutput for String {
+    fn write(&mut self, value: i64) {
+        self.push((value as u8) as char);
+    }
+}
+
+impl<F: FnMut() -> Option<i64>> IntcodeInput for F {
+    fn read(&mut self) -> Option<i64> {
+        self()
+    }
+}
+
+impl<F: FnMut(i64)> IntcodeOutput for F {
+    fn write(&mut self, value: i64) {
+        self(value)
+    }
+}
+
+/// Never blocks: an empty channel is reported as `InputRequired`, so machines connected by
+/// channels can be run in turn on a single thread. Use a closure calling `recv` to block instead.
+impl IntcodeInput for Receiver<i64> {
+    fn read(&mut self) -> Option<i64> {
+        self.try_recv().ok()
+    }
+}
+
+/// Values sent after the receiving end has hung up are dropped.
+impl IntcodeOutput for Sender<i64> {
+    fn write(&mut self, value: i64) {
+        self.send(value).ok();
+    }
+}
+
+/// Encode text as ASCII input.
+pub fn ascii(text: &str) -> VecDeque<i64> {
+    text.chars().map(|c| c as i64).collect()
+}
+
+/// Reads lines from stdin as ASCII input, including the trailing newline. Stops supplying input
+/// at the end of the stream.
+#[derive(Debug, Default)]
+pub struct Stdin {
+    pending: VecDeque<i64>,
+}
+
+impl IntcodeInput for Stdin {
+    fn read(&mut self) -> Option<i64> {
+        if self.pending.is_empty() {
+            let mut line = String::new();
+            io::stdin().lock().read_line(&mut line).ok()?;
+            self.pending = ascii(&line);
+        }
+
+        self.pending.pop_front()
+    }
+}
+
+/// Prints ASCII output to stdout as text, and anything outside the ASCII range as a number on a
+/// line of its own.
+#[derive(Debug, Default)]
+pub struct Stdout;
+
+impl IntcodeOutput for Stdout {
+    fn write(&mut self, value: i64) {
+        let mut stdout = io::stdout();
+
+        if (0..128).contains(&value) {
+            write!(stdout, "{}", value as u8 as char).ok();
+            if value == '\n' as i64 {
+                stdout.flush().ok();
+            }
+        } else {
+            writeln!(stdout, "{}", value).ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{Intcode, Response};
+    use super::*;
+    use std::sync::mpsc;
+
+    /// Reads two numbers and outputs their sum.
+    fn sum() -> Intcode {
+        Intcode::new(vec![3, 11, 3, 12, 1, 11, 12, 11, 4, 11, 99])
+    }
+
+    #[test]
+    fn closures() {
+        let mut inputs = vec![5, 4];
+        let mut outputs = Vec::new();
+
+        let mut intcode = sum().with_io(|| inputs.pop(), |value| outputs.push(value));
+        assert_eq!(Ok(Response::Terminated), intcode.run());
+        drop(intcode);
+
+        assert_eq!(vec![9], outputs);
+    }
+
+    #[test]
+    fn channels() {
+        let (input, receiver) = mpsc::channel();
+        let (sender, output) = mpsc::channel();
+        let mut intcode = sum().with_io(receiver, sender);
+
+        input.send(1).unwrap();
+        assert_eq!(Ok(Response::InputRequired), intcode.run());
+        input.send(2).unwrap();
+        assert_eq!(Ok(Response::Terminated), intcode.run());
+        assert_eq!(vec![3], output.try_iter().collect::<Vec<i64>>());
+    }
+
+    #[test]
+    fn ascii_text() {
+        // Echoes its input.
+        let mut intcode =
+            Intcode::new(vec![3, 100, 4, 100, 1105, 1, 0]).with_io(ascii("hi"), String::new());
+
+        assert_eq!(Ok(Response::InputRequired), intcode.run());
+        assert_eq!("hi", intcode.output);
+    }
+}
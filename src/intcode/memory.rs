@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::mem;
+
+use super::Intcode;
+
+/// Words per page of sparse memory.
+pub(super) const PAGE_SIZE: usize = 1024;
+
+/// Memory beyond the end of `Intcode::data`, allocated a page at a time as it is written. Writes
+/// close to the end of `data` grow it instead, so programs that use the memory just past their
+/// image as a stack or heap stay dense, while a stray write to a far address costs a single page.
+#[derive(Clone, Debug, Default)]
+pub(super) struct Pages {
+    pages: HashMap<usize, Box<[i64; PAGE_SIZE]>>,
+}
+
+impl Pages {
+    #[inline]
+    pub(super) fn get(&self, address: usize) -> i64 {
+        self.pages
+            .get(&(address / PAGE_SIZE))
+            .map_or(0, |page| page[address % PAGE_SIZE])
+    }
+
+    pub(super) fn set(&mut self, address: usize, value: i64) {
+        self.pages
+            .entry(address / PAGE_SIZE)
+            .or_insert_with(|| Box::new([0; PAGE_SIZE]))[address % PAGE_SIZE] = value;
+    }
+
+    /// Grow `dense` to `len` words, moving anything already written to those addresses out of
+    /// the pages.
+    pub(super) fn grow(&mut self, dense: &mut Vec<i64>, len: usize) {
+        let start = dense.len();
+        dense.resize(len, 0);
+
+        if self.pages.is_empty() || len <= start {
+            return;
+        }
+
+        for index in start / PAGE_SIZE..=(len - 1) / PAGE_SIZE {
+            let base = index * PAGE_SIZE;
+
+            if let Some(page) = self.pages.get_mut(&index) {
+                for address in start.max(base)..len.min(base + PAGE_SIZE) {
+                    dense[address] = mem::take(&mut page[address - base]);
+                }
+
+                if base + PAGE_SIZE <= len {
+                    self.pages.remove(&index);
+                }
+            }
+        }
+    }
+
+    /// Shrink `dense` to `len` words, moving anything written past the new end into the pages.
+    pub(super) fn shrink(&mut self, dense: &mut Vec<i64>, len: usize) {
+        for (offset, &value) in dense.iter().enumerate().skip(len) {
+            if value != 0 {
+                self.set(offset, value);
+            }
+        }
+
+        dense.truncate(len);
+    }
+
+    /// Every non-zero word, in address order.
+    pub(super) fn words(&self) -> Vec<(usize, i64)> {
+        let mut indices: Vec<&usize> = self.pages.keys().collect();
+        indices.sort_unstable();
+
+        indices
+            .into_iter()
+            .flat_map(|index| {
+                let page = &self.pages[index];
+                (0..PAGE_SIZE)
+                    .filter(move |offset| page[*offset] != 0)
+                    .map(move |offset| (index * PAGE_SIZE + offset, page[offset]))
+            })
+            .collect()
+    }
+}
+
+/// How much memory a machine is using.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MemoryStats {
+    /// Words stored contiguously from address 0, including the program image.
+    pub dense_words: usize,
+
+    /// Pages allocated for addresses beyond the dense words.
+    pub pages: usize,
+}
+
+impl MemoryStats {
+    pub fn bytes(&self) -> usize {
+        (self.dense_words + self.pages * PAGE_SIZE) * mem::size_of::<i64>()
+    }
+}
+
+impl fmt::Display for MemoryStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} dense words, {} pages of {} words, {} bytes",
+            self.dense_words,
+            self.pages,
+            PAGE_SIZE,
+            self.bytes()
+        )
+    }
+}
+
+impl<I, O> Intcode<I, O> {
+    pub fn memory_stats(&self) -> MemoryStats {
+        MemoryStats {
+            dense_words: self.data.len(),
+            pages: self.pages.pages.len(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn far_writes() {
+        let mut intcode = Intcode::new(vec![1, 2, 3]);
+        intcode.set(1_000_000_000, 7);
+        intcode.set(1_000_000_001, 8);
+
+        assert_eq!(7, intcode.get(1_000_000_000));
+        assert_eq!(8, intcode.get(1_000_000_001));
+        assert_eq!(0, intcode.get(999_999_999));
+        assert_eq!(
+            MemoryStats {
+                dense_words: 3,
+                pages: 1,
+            },
+            intcode.memory_stats(),
+        );
+        assert_eq!(
+            "3 dense words, 1 pages of 1024 words, 8216 bytes",
+            intcode.memory_stats().to_string(),
+        );
+    }
+
+    #[test]
+    fn near_writes_stay_dense() {
+        let mut intcode = Intcode::new(vec![1, 2, 3]);
+        intcode.set(1500, 5);
+        intcode.set(3000, 6);
+        assert_eq!(2, intcode.memory_stats().pages);
+
+        // Growing the dense words into a page moves what was written there.
+        intcode.set(1000, 3);
+        intcode.set(2020, 4);
+        intcode.set(2100, 7);
+        assert_eq!(2101, intcode.data.len());
+        assert_eq!(1, intcode.memory_stats().pages);
+        assert_eq!(
+            (3, 5, 4, 7, 6),
+            (
+                intcode.get(1000),
+                intcode.get(1500),
+                intcode.get(2020),
+                intcode.get(2100),
+                intcode.get(3000)
+            ),
+        );
+    }
+
+    #[test]
+    fn far_program() {
+        // Jumps to a far address, outputs 42 from there and halts.
+        let mut intcode = Intcode::new(vec![1105, 1, 1_000_000]);
+        for (offset, &value) in [104, 42, 99].iter().enumerate() {
+            intcode.set(1_000_000 + offset, value);
+        }
+
+        intcode.run().unwrap();
+        assert_eq!(vec![42], intcode.output);
+        assert_eq!(3, intcode.data.len());
+    }
+}
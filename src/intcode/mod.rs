@@ -0,0 +1,998 @@
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::fmt;
+use std::str;
+use std::sync::Arc;
+use std::time::Instant;
+
+pub mod asm;
+pub mod bench;
+#[cfg(feature = "bigint")]
+pub mod big;
+pub mod cfg;
+pub mod compile;
+pub mod debugger;
+mod decoded;
+pub mod decompile;
+pub mod dialect;
+pub mod diff;
+pub mod disasm;
+pub mod fuzz;
+pub mod history;
+pub mod host;
+mod instruction;
+pub mod io;
+pub mod memory;
+pub mod profile;
+pub mod runtime;
+pub mod scheduler;
+pub mod session;
+mod snapshot;
+pub mod symbolic;
+pub mod terminal;
+pub mod trace;
+
+use decoded::DecodeCache;
+pub use decoded::Engine;
+use dialect::Dialect;
+pub use instruction::{Instruction, Opcode, Param};
+use io::{IntcodeInput, IntcodeOutput};
+use memory::{Pages, PAGE_SIZE};
+use profile::Profiler;
+use session::Recorder;
+use trace::Tracer;
+
+const DEADLINE_CHECK_STEPS: u64 = 10_000;
+
+/// An Intcode machine. Input and output default to in-memory queues, but can be replaced with
+/// anything implementing `IntcodeInput` and `IntcodeOutput` using `with_io`.
+///
+/// `data` holds memory from address 0 up to the highest address written so far, as long as writes
+/// don't skip too far ahead. Anything further out is kept in sparse pages.
+#[derive(Clone, Debug)]
+pub struct Intcode<I = VecDeque<i64>, O = Vec<i64>> {
+    pub data: Vec<i64>,
+    pages: Pages,
+    pub input: I,
+    pub output: O,
+    cursor: usize,
+    relative_base: i64,
+    pub steps: u64,
+    tracer: Option<Tracer>,
+    profiler: Option<Profiler>,
+    recorder: Option<Recorder>,
+    decoded: Option<DecodeCache>,
+    arithmetic: Arithmetic,
+    dialect: Option<Arc<Dialect>>,
+}
+
+impl Intcode {
+    pub fn new(data: Vec<i64>) -> Self {
+        Self {
+            data,
+            pages: Pages::default(),
+            input: VecDeque::new(),
+            output: Vec::new(),
+            cursor: 0,
+            relative_base: 0,
+            steps: 0,
+            tracer: None,
+            profiler: None,
+            recorder: None,
+            decoded: None,
+            arithmetic: Arithmetic::Wrapping,
+            dialect: None,
+        }
+    }
+
+    pub fn output_string(&self) -> String {
+        self.output.iter().map(|c| (*c as u8) as char).collect()
+    }
+
+    fn get_mode(instruction: i64, param_index: usize) -> Result<InstructionMode, i64> {
+        InstructionMode::try_from(instruction / 10i64.pow(param_index as u32 + 2) % 10)
+    }
+}
+
+impl<I: Extend<i64>, O> Intcode<I, O> {
+    pub fn with_input(mut self, data: &[i64]) -> Self {
+        self.input.extend(data.iter().copied());
+        self
+    }
+
+    pub fn input_str(&mut self, data: &str) {
+        self.input.extend(data.chars().map(|c| c as i64));
+    }
+}
+
+impl<I, O> Intcode<I, O> {
+    /// Replace the machine's input and output, keeping the rest of its state.
+    pub fn with_io<I2, O2>(self, input: I2, output: O2) -> Intcode<I2, O2> {
+        Intcode {
+            data: self.data,
+            pages: self.pages,
+            input,
+            output,
+            cursor: self.cursor,
+            relative_base: self.relative_base,
+            steps: self.steps,
+            tracer: self.tracer,
+            profiler: self.profiler,
+            recorder: self.recorder,
+            decoded: self.decoded,
+            arithmetic: self.arithmetic,
+            dialect: self.dialect,
+        }
+    }
+
+    pub fn with_engine(mut self, engine: Engine) -> Self {
+        self.decoded = match engine {
+            Engine::Interpreter => None,
+            Engine::Decoded => Some(DecodeCache::default()),
+        };
+        self
+    }
+
+    pub fn with_arithmetic(mut self, arithmetic: Arithmetic) -> Self {
+        self.arithmetic = arithmetic;
+        self
+    }
+
+    /// Execute the opcodes defined by `dialect` in place of, or as well as, the standard ones.
+    pub fn with_dialect(mut self, dialect: Dialect) -> Self {
+        self.dialect = Some(Arc::new(dialect));
+        self.decoded = self.decoded.map(|_| DecodeCache::default());
+        self
+    }
+
+    /// Record every instruction executed from now on. Clones of this machine share the tracer.
+    pub fn with_tracer(mut self, tracer: Tracer) -> Self {
+        self.tracer = Some(tracer);
+        self
+    }
+
+    /// Count every instruction executed from now on. Clones of this machine share the profiler.
+    pub fn with_profiler(mut self, profiler: Profiler) -> Self {
+        self.profiler = Some(profiler);
+        self
+    }
+
+    /// Record every value read or written from now on, for replaying with `Session::replay`.
+    pub fn with_recorder(mut self, recorder: Recorder) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    pub fn set(&mut self, offset: usize, value: i64) {
+        if offset < self.data.len() {
+            self.data[offset] = value;
+        } else if offset < self.data.len() + PAGE_SIZE {
+            self.pages.grow(&mut self.data, offset + 1);
+            self.data[offset] = value;
+        } else {
+            self.pages.set(offset, value);
+        }
+
+        if let Some(cache) = &mut self.decoded {
+            cache.invalidate(offset);
+        }
+    }
+
+    pub fn get(&self, offset: usize) -> i64 {
+        match self.data.get(offset) {
+            Some(value) => *value,
+            None => self.pages.get(offset),
+        }
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn relative_base(&self) -> i64 {
+        self.relative_base
+    }
+
+    pub fn set_cursor(&mut self, cursor: usize) {
+        self.cursor = cursor;
+    }
+
+    pub fn set_relative_base(&mut self, relative_base: i64) {
+        self.relative_base = relative_base;
+    }
+
+    /// Add two words the way the machine's `Arithmetic` says to, blaming any overflow on the
+    /// instruction at the cursor.
+    pub(crate) fn add_words(&self, a: i64, b: i64) -> Result<i64, VmError> {
+        match self.arithmetic {
+            Arithmetic::Wrapping => Ok(a.wrapping_add(b)),
+            Arithmetic::Checked => a.checked_add(b).ok_or_else(|| self.overflow()),
+        }
+    }
+
+    /// Multiply two words the way the machine's `Arithmetic` says to.
+    pub(crate) fn mul_words(&self, a: i64, b: i64) -> Result<i64, VmError> {
+        match self.arithmetic {
+            Arithmetic::Wrapping => Ok(a.wrapping_mul(b)),
+            Arithmetic::Checked => a.checked_mul(b).ok_or_else(|| self.overflow()),
+        }
+    }
+
+    fn overflow(&self) -> VmError {
+        VmError::Overflow {
+            cursor: self.cursor,
+            instruction: self.get(self.cursor),
+        }
+    }
+
+    /// Decode the instruction at the given address without executing it.
+    pub fn instruction(&self, address: usize) -> Result<Instruction, VmError> {
+        Instruction::decode(
+            address,
+            &[
+                self.get(address),
+                self.get(address + 1),
+                self.get(address + 2),
+                self.get(address + 3),
+            ],
+        )
+    }
+}
+
+impl<I: IntcodeInput, O: IntcodeOutput> Intcode<I, O> {
+    pub fn run(&mut self) -> Result<Response, VmError> {
+        loop {
+            if let Some(result) = self.step()? {
+                break Ok(result);
+            }
+        }
+    }
+
+    /// Like `run`, but give up with `Response::BudgetExhausted` after executing `max_steps`
+    /// instructions.
+    pub fn run_for(&mut self, max_steps: u64) -> Result<Response, VmError> {
+        for _ in 0..max_steps {
+            if let Some(result) = self.step()? {
+                return Ok(result);
+            }
+        }
+
+        Ok(Response::BudgetExhausted)
+    }
+
+    /// Like `run`, but give up with `Response::BudgetExhausted` once `deadline` has passed. The
+    /// clock is only checked every `DEADLINE_CHECK_STEPS` instructions.
+    pub fn run_until(&mut self, deadline: Instant) -> Result<Response, VmError> {
+        while Instant::now() < deadline {
+            match self.run_for(DEADLINE_CHECK_STEPS)? {
+                Response::BudgetExhausted => {}
+                response => return Ok(response),
+            }
+        }
+
+        Ok(Response::BudgetExhausted)
+    }
+
+    pub fn step(&mut self) -> Result<Option<Response>, VmError> {
+        let profiled = self.profiler.as_ref().map(|_| {
+            let raw = self.get(self.cursor);
+            let custom = self.dialect.as_ref().is_some_and(|d| d.defines(raw));
+            (self.cursor, raw, custom)
+        });
+        let pending_io = self.recorder.as_ref().and_then(|_| self.pending_io());
+
+        let result = match self.tracer.take() {
+            None => self.execute(),
+            Some(tracer) => {
+                let result = tracer.step(self);
+                self.tracer = Some(tracer);
+                result
+            }
+        };
+
+        if let (Some(profiler), Some((cursor, raw, custom))) = (&self.profiler, profiled) {
+            profiler.record(cursor, raw, custom, &result);
+        }
+        if let Some(recorder) = &self.recorder {
+            recorder.record(self, pending_io, &result);
+        }
+
+        result
+    }
+
+    fn execute(&mut self) -> Result<Option<Response>, VmError> {
+        match self.decoded {
+            None => self.interpret(),
+            Some(_) => self.execute_decoded(),
+        }
+    }
+
+    fn interpret(&mut self) -> Result<Option<Response>, VmError> {
+        self.steps += 1;
+        if let Some(result) = self.execute_custom() {
+            return result;
+        }
+
+        let opcode = Opcode::try_from(self.get(self.cursor) % 100);
+
+        match opcode {
+            Ok(Opcode::Add) => self.do_add(),
+            Ok(Opcode::Mul) => self.do_mul(),
+            Ok(Opcode::Input) => self.do_input(),
+            Ok(Opcode::Output) => self.do_output(),
+            Ok(Opcode::JumpIfTrue) => self.do_jump_if_true(),
+            Ok(Opcode::JumpIfFalse) => self.do_jump_if_false(),
+            Ok(Opcode::LessThan) => self.do_less_than(),
+            Ok(Opcode::Equals) => self.do_equals(),
+            Ok(Opcode::AdjustRelativeBase) => self.do_adjust_relative_base(),
+            Ok(Opcode::Halt) => self.do_halt(),
+            Err(_) => Err(VmError::UnknownOpcode {
+                cursor: self.cursor,
+                instruction: self.get(self.cursor),
+            }),
+        }
+    }
+
+    /// Opcode 1 adds together numbers read from two positions and stores the result in a third
+    /// position. The three integers immediately after the opcode tell you these three
+    /// positions - the first two indicate the positions from which you should read the input
+    /// values, and the third indicates the position at which the output should be stored.
+    fn do_add(&mut self) -> Result<Option<Response>, VmError> {
+        let value = self.add_words(self.get_param(0)?, self.get_param(1)?)?;
+        self.set_pos(2, value)?;
+        self.cursor += 4;
+        Ok(None)
+    }
+
+    /// Opcode 2 works exactly like opcode 1, except it multiplies the two inputs instead of
+    /// adding them. Again, the three integers after the opcode indicate where the inputs and
+    /// outputs are, not their values.
+    fn do_mul(&mut self) -> Result<Option<Response>, VmError> {
+        let value = self.mul_words(self.get_param(0)?, self.get_param(1)?)?;
+        self.set_pos(2, value)?;
+        self.cursor += 4;
+        Ok(None)
+    }
+
+    /// Opcode 3 takes a single integer as input and saves it to the position given
+    /// by its only parameter. For example, the instruction 3,50 would take an
+    /// input value and store it at address 50.
+    fn do_input(&mut self) -> Result<Option<Response>, VmError> {
+        match self.input.read() {
+            None => Ok(Some(Response::InputRequired)),
+            Some(value) => {
+                self.set_pos(0, value)?;
+                self.cursor += 2;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Opcode 4 outputs the value of its only parameter. For example, the
+    /// instruction 4,50 would output the value at address 50.
+    fn do_output(&mut self) -> Result<Option<Response>, VmError> {
+        let value = self.get_param(0)?;
+        self.output.write(value);
+        self.cursor += 2;
+        Ok(None)
+    }
+
+    /// Opcode 5 is jump-if-true: if the first parameter is non-zero, it sets the instruction
+    /// pointer to the value from the second parameter. Otherwise, it does nothing.
+    fn do_jump_if_true(&mut self) -> Result<Option<Response>, VmError> {
+        self.cursor = if self.get_param(0)? != 0 {
+            self.address(self.get_param(1)?)?
+        } else {
+            self.cursor + 3
+        };
+
+        Ok(None)
+    }
+
+    /// Opcode 6 is jump-if-false: if the first parameter is zero, it sets the instruction pointer
+    /// to the value from the second parameter. Otherwise, it does nothing.
+    fn do_jump_if_false(&mut self) -> Result<Option<Response>, VmError> {
+        self.cursor = if self.get_param(0)? == 0 {
+            self.address(self.get_param(1)?)?
+        } else {
+            self.cursor + 3
+        };
+
+        Ok(None)
+    }
+
+    /// Opcode 7 is less than: if the first parameter is less than the second parameter, it stores
+    /// 1 in the position given by the third parameter. Otherwise, it stores 0.
+    fn do_less_than(&mut self) -> Result<Option<Response>, VmError> {
+        self.set_pos(
+            2,
+            if self.get_param(0)? < self.get_param(1)? {
+                1
+            } else {
+                0
+            },
+        )?;
+        self.cursor += 4;
+        Ok(None)
+    }
+
+    /// Opcode 8 is equals: if the first parameter is equal to the second parameter, it stores 1 in
+    /// the position given by the third parameter. Otherwise, it stores 0.
+    fn do_equals(&mut self) -> Result<Option<Response>, VmError> {
+        self.set_pos(
+            2,
+            if self.get_param(0)? == self.get_param(1)? {
+                1
+            } else {
+                0
+            },
+        )?;
+        self.cursor += 4;
+        Ok(None)
+    }
+
+    /// Opcode 9 adjusts the relative base by the value of its only parameter. The relative
+    /// base increases (or decreases, if the value is negative) by the value of the parameter.
+    fn do_adjust_relative_base(&mut self) -> Result<Option<Response>, VmError> {
+        let value = self.get_param(0)?;
+        self.relative_base = self.add_words(self.relative_base, value)?;
+        self.cursor += 2;
+        Ok(None)
+    }
+
+    /// 99 means that the program is finished and should immediately halt.
+    fn do_halt(&mut self) -> Result<Option<Response>, VmError> {
+        Ok(Some(Response::Terminated))
+    }
+
+    fn get_param(&self, param_index: usize) -> Result<i64, VmError> {
+        let param = self.get(self.cursor + param_index + 1);
+
+        Ok(match self.mode(param_index)? {
+            // Position mode - interpret as pointer.
+            InstructionMode::Position => self.get(self.address(param)?),
+
+            // Immediate mode - interpret as value.
+            InstructionMode::Immediate => param,
+
+            // Relative mode - interpret as relative to the defined base.
+            InstructionMode::Relative => {
+                self.get(self.address(self.add_words(param, self.relative_base)?)?)
+            }
+        })
+    }
+
+    fn set_pos(&mut self, param_index: usize, value: i64) -> Result<(), VmError> {
+        let param = self.get(self.cursor + param_index + 1);
+
+        let pos = match self.mode(param_index)? {
+            // Position mode - interpret as pointer.
+            InstructionMode::Position => self.address(param)?,
+
+            // Immediate mode - output parameters must never be in immediate mode.
+            InstructionMode::Immediate => {
+                return Err(VmError::ImmediateWrite {
+                    cursor: self.cursor,
+                    instruction: self.get(self.cursor),
+                    param_index,
+                })
+            }
+
+            // Relative mode - interpret as relative to the defined base.
+            InstructionMode::Relative => {
+                self.address(self.add_words(param, self.relative_base)?)?
+            }
+        };
+
+        self.set(pos, value);
+        Ok(())
+    }
+
+    fn mode(&self, param_index: usize) -> Result<InstructionMode, VmError> {
+        let instruction = self.get(self.cursor);
+
+        Intcode::get_mode(instruction, param_index).map_err(|mode| VmError::InvalidMode {
+            cursor: self.cursor,
+            instruction,
+            param_index,
+            mode,
+        })
+    }
+
+    /// Convert a resolved parameter into a memory address, rejecting negative values rather than
+    /// letting them wrap around to the far end of memory.
+    fn address(&self, address: i64) -> Result<usize, VmError> {
+        if address < 0 {
+            Err(VmError::NegativeAddress {
+                cursor: self.cursor,
+                instruction: self.get(self.cursor),
+                address,
+            })
+        } else {
+            Ok(address as usize)
+        }
+    }
+}
+
+impl str::FromStr for Intcode {
+    type Err = &'static str;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        raw.split(',')
+            .map(|input| input.trim().parse())
+            .collect::<Result<_, _>>()
+            .map(Self::new)
+            .map_err(|_| "Invalid Intcode program.")
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Response {
+    Terminated,
+    InputRequired,
+
+    /// A step or time limit given to `run_for` or `run_until` ran out first.
+    BudgetExhausted,
+}
+
+/// What a machine does when adding or multiplying words, or adjusting the relative base, overflows
+/// 64 bits.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Arithmetic {
+    /// Wrap around, as two's complement hardware would. The default, and what the puzzle inputs
+    /// assume, since none of them overflow.
+    Wrapping,
+
+    /// Stop with `VmError::Overflow`. A machine stopped this way can be resumed with big integer
+    /// words using `big::BigIntcode::from`, if the `bigint` feature is enabled.
+    Checked,
+}
+
+/// A fault raised by the machine while executing a program. Each variant records the cursor and the
+/// raw instruction at fault so the caller can report or recover from a crash.
+#[derive(Clone, Debug, PartialEq)]
+pub enum VmError {
+    /// The last two digits of the instruction are not a recognized opcode.
+    UnknownOpcode { cursor: usize, instruction: i64 },
+
+    /// A parameter mode digit is something other than 0, 1 or 2.
+    InvalidMode {
+        cursor: usize,
+        instruction: i64,
+        param_index: usize,
+        mode: i64,
+    },
+
+    /// An output parameter was given in immediate mode.
+    ImmediateWrite {
+        cursor: usize,
+        instruction: i64,
+        param_index: usize,
+    },
+
+    /// A read, write or jump resolved to an address below zero.
+    NegativeAddress {
+        cursor: usize,
+        instruction: i64,
+        address: i64,
+    },
+
+    /// The result of an instruction didn't fit in a word, under `Arithmetic::Checked`.
+    Overflow { cursor: usize, instruction: i64 },
+
+    /// The handler of an opcode defined by a `Dialect` failed.
+    Handler {
+        cursor: usize,
+        instruction: i64,
+        message: String,
+    },
+}
+
+impl VmError {
+    pub fn cursor(&self) -> usize {
+        match self {
+            VmError::UnknownOpcode { cursor, .. }
+            | VmError::InvalidMode { cursor, .. }
+            | VmError::ImmediateWrite { cursor, .. }
+            | VmError::NegativeAddress { cursor, .. }
+            | VmError::Overflow { cursor, .. }
+            | VmError::Handler { cursor, .. } => *cursor,
+        }
+    }
+
+    pub fn instruction(&self) -> i64 {
+        match self {
+            VmError::UnknownOpcode { instruction, .. }
+            | VmError::InvalidMode { instruction, .. }
+            | VmError::ImmediateWrite { instruction, .. }
+            | VmError::NegativeAddress { instruction, .. }
+            | VmError::Overflow { instruction, .. }
+            | VmError::Handler { instruction, .. } => *instruction,
+        }
+    }
+}
+
+impl fmt::Display for VmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VmError::UnknownOpcode { .. } => write!(f, "Unknown opcode"),
+            VmError::InvalidMode {
+                param_index, mode, ..
+            } => write!(f, "Invalid mode {} for parameter {}", mode, param_index),
+            VmError::ImmediateWrite { param_index, .. } => write!(
+                f,
+                "Output parameter {} must never be in immediate mode",
+                param_index
+            ),
+            VmError::NegativeAddress { address, .. } => {
+                write!(f, "Negative address {}", address)
+            }
+            VmError::Overflow { .. } => write!(f, "Arithmetic overflow"),
+            VmError::Handler { message, .. } => f.write_str(message),
+        }?;
+
+        write!(
+            f,
+            " in instruction {} at offset {}!",
+            self.instruction(),
+            self.cursor()
+        )
+    }
+}
+
+impl From<VmError> for String {
+    fn from(error: VmError) -> Self {
+        error.to_string()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum InstructionMode {
+    Position,
+    Immediate,
+    Relative,
+}
+
+impl TryFrom<i64> for InstructionMode {
+    type Error = i64;
+
+    fn try_from(value: i64) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(InstructionMode::Position),
+            1 => Ok(InstructionMode::Immediate),
+            2 => Ok(InstructionMode::Relative),
+            x => Err(x),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn get_mode() {
+        assert_eq!(Ok(InstructionMode::Position), Intcode::get_mode(12001, 0));
+        assert_eq!(Ok(InstructionMode::Immediate), Intcode::get_mode(12001, 2));
+        assert_eq!(Ok(InstructionMode::Position), Intcode::get_mode(12001, 3));
+        assert_eq!(Err(3), Intcode::get_mode(3001, 1));
+    }
+
+    #[test]
+    fn day2_example1() {
+        let mut intcode = Intcode::new(vec![1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50]);
+
+        assert_eq!(Ok(None), intcode.step());
+        assert_eq!(70, intcode.data[3]);
+
+        assert_eq!(Ok(None), intcode.step());
+        assert_eq!(3500, intcode.data[0]);
+
+        assert_eq!(Ok(Some(Response::Terminated)), intcode.step());
+    }
+
+    #[test]
+    fn day2_example2() {
+        let mut intcode = Intcode::new(vec![1, 0, 0, 0, 99]);
+        intcode.run().unwrap();
+        assert_eq!(vec![2, 0, 0, 0, 99], intcode.data);
+    }
+
+    #[test]
+    fn day2_example3() {
+        let mut intcode = Intcode::new(vec![2, 3, 0, 3, 99]);
+        intcode.run().unwrap();
+        assert_eq!(vec![2, 3, 0, 6, 99], intcode.data);
+    }
+
+    #[test]
+    fn day2_example4() {
+        let mut intcode = Intcode::new(vec![2, 4, 4, 5, 99, 0]);
+        intcode.run().unwrap();
+        assert_eq!(vec![2, 4, 4, 5, 99, 9801], intcode.data);
+    }
+
+    #[test]
+    fn day2_example5() {
+        let mut intcode = Intcode::new(vec![1, 1, 1, 4, 99, 5, 6, 0, 99]);
+        intcode.run().unwrap();
+        assert_eq!(vec![30, 1, 1, 4, 2, 5, 6, 0, 99], intcode.data);
+    }
+
+    #[test]
+    fn day5_example1() {
+        let mut intcode = Intcode::new(vec![3, 0, 4, 0, 99]);
+        intcode.input.push_back(123);
+        intcode.run().unwrap();
+        assert_eq!(vec![123], intcode.output);
+    }
+
+    #[test]
+    fn day5_example2() {
+        let mut intcode = Intcode::new(vec![1002, 4, 3, 4, 33]);
+
+        assert_eq!(Ok(None), intcode.step());
+        assert_eq!(99, intcode.data[4]);
+
+        assert_eq!(Ok(Some(Response::Terminated)), intcode.step());
+    }
+
+    #[test]
+    fn day5_example3() {
+        let mut intcode = Intcode::new(vec![1101, 100, -1, 4, 0]);
+
+        assert_eq!(Ok(None), intcode.step());
+        assert_eq!(99, intcode.data[4]);
+
+        assert_eq!(Ok(Some(Response::Terminated)), intcode.step());
+    }
+
+    #[test]
+    fn day5_example4() {
+        for i in 6..=10 {
+            let mut intcode = Intcode::new(vec![3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8]);
+            intcode.input.push_back(i);
+            intcode.run().unwrap();
+            assert_eq!(vec![if i == 8 { 1 } else { 0 }], intcode.output);
+        }
+    }
+
+    #[test]
+    fn day5_example5() {
+        for i in 6..=10 {
+            let mut intcode = Intcode::new(vec![3, 9, 7, 9, 10, 9, 4, 9, 99, -1, 8]);
+            intcode.input.push_back(i);
+            intcode.run().unwrap();
+            assert_eq!(vec![if i < 8 { 1 } else { 0 }], intcode.output);
+        }
+    }
+
+    #[test]
+    fn day5_example6() {
+        for i in 6..=10 {
+            let mut intcode = Intcode::new(vec![3, 3, 1108, -1, 8, 3, 4, 3, 99]);
+            intcode.input.push_back(i);
+            intcode.run().unwrap();
+            assert_eq!(vec![if i == 8 { 1 } else { 0 }], intcode.output);
+        }
+    }
+
+    #[test]
+    fn day5_example7() {
+        for i in 6..=10 {
+            let mut intcode = Intcode::new(vec![3, 3, 1107, -1, 8, 3, 4, 3, 99]);
+            intcode.input.push_back(i);
+            intcode.run().unwrap();
+            assert_eq!(vec![if i < 8 { 1 } else { 0 }], intcode.output);
+        }
+    }
+
+    #[test]
+    fn day5_example8() {
+        for i in -2..=2 {
+            let mut intcode = Intcode::new(vec![
+                3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9,
+            ]);
+            intcode.input.push_back(i);
+            intcode.run().unwrap();
+            assert_eq!(vec![if i == 0 { 0 } else { 1 }], intcode.output);
+        }
+    }
+
+    #[test]
+    fn day5_example9() {
+        for i in -2..=2 {
+            let mut intcode = Intcode::new(vec![3, 3, 1105, -1, 9, 1101, 0, 0, 12, 4, 12, 99, 1]);
+            intcode.input.push_back(i);
+            intcode.run().unwrap();
+            assert_eq!(vec![if i == 0 { 0 } else { 1 }], intcode.output);
+        }
+    }
+
+    #[test]
+    fn day5_example10() {
+        for i in 6..=10 {
+            let mut intcode = Intcode::new(vec![
+                3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36,
+                98, 0, 0, 1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000,
+                1, 20, 4, 20, 1105, 1, 46, 98, 99,
+            ]);
+            intcode.input.push_back(i);
+            intcode.run().unwrap();
+            assert_eq!(
+                vec![if i < 8 {
+                    999
+                } else if i == 8 {
+                    1000
+                } else {
+                    1001
+                }],
+                intcode.output
+            );
+        }
+    }
+
+    #[test]
+    fn day9_example1() {
+        let mut intcode = Intcode::new(vec![
+            109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+        ]);
+        intcode.run().unwrap();
+        assert_eq!(
+            vec![109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99],
+            intcode.output,
+        );
+    }
+
+    #[test]
+    fn day9_example2() {
+        let mut intcode = Intcode::new(vec![1102, 34915192, 34915192, 7, 4, 7, 99, 0]);
+        intcode.run().unwrap();
+        assert_eq!(1, intcode.output.len());
+        assert_eq!(16, format!("{}", intcode.output[0]).len());
+    }
+
+    #[test]
+    fn day9_example3() {
+        let mut intcode = Intcode::new(vec![104, 1125899906842624, 99]);
+        intcode.run().unwrap();
+        assert_eq!(vec![1125899906842624], intcode.output);
+    }
+
+    #[test]
+    fn test_input_relative() {
+        let mut intcode = Intcode::new(vec![109, -1, 203, 1, 99]);
+        intcode.input.push_back(123);
+        intcode.run().unwrap();
+        assert_eq!(vec![123, -1, 203, 1, 99], intcode.data);
+    }
+
+    #[test]
+    fn error_unknown_opcode() {
+        let mut intcode = Intcode::new(vec![1101, 1, 1, 5, 42, 0]);
+        assert_eq!(
+            Err(VmError::UnknownOpcode {
+                cursor: 4,
+                instruction: 42,
+            }),
+            intcode.run(),
+        );
+    }
+
+    #[test]
+    fn error_invalid_mode() {
+        let mut intcode = Intcode::new(vec![3101, 1, 1, 0, 99]);
+        assert_eq!(
+            Err(VmError::InvalidMode {
+                cursor: 0,
+                instruction: 3101,
+                param_index: 1,
+                mode: 3,
+            }),
+            intcode.run(),
+        );
+    }
+
+    #[test]
+    fn error_immediate_write() {
+        let mut intcode = Intcode::new(vec![1, 0, 0, 0, 11101, 1, 1, 0, 99]);
+        assert_eq!(Ok(None), intcode.step());
+        assert_eq!(
+            Err(VmError::ImmediateWrite {
+                cursor: 4,
+                instruction: 11101,
+                param_index: 2,
+            }),
+            intcode.run(),
+        );
+    }
+
+    #[test]
+    fn error_negative_address() {
+        let mut intcode = Intcode::new(vec![109, -5, 204, 1, 99]);
+        let error = intcode.run().unwrap_err();
+        assert_eq!(
+            VmError::NegativeAddress {
+                cursor: 2,
+                instruction: 204,
+                address: -4,
+            },
+            error,
+        );
+        assert_eq!(
+            "Negative address -4 in instruction 204 at offset 2!",
+            error.to_string(),
+        );
+    }
+
+    #[test]
+    fn error_negative_jump() {
+        let mut intcode = Intcode::new(vec![1105, 1, -1]);
+        assert_eq!(
+            Err(VmError::NegativeAddress {
+                cursor: 0,
+                instruction: 1105,
+                address: -1,
+            }),
+            intcode.run(),
+        );
+    }
+
+    #[test]
+    fn run_for() {
+        // Loops forever.
+        let mut intcode = Intcode::new(vec![1105, 1, 0]);
+        assert_eq!(Ok(Response::BudgetExhausted), intcode.run_for(10));
+        assert_eq!(10, intcode.steps);
+
+        let mut intcode = Intcode::new(vec![1, 0, 0, 0, 99]);
+        assert_eq!(Ok(Response::Terminated), intcode.run_for(10));
+        assert_eq!(2, intcode.steps);
+    }
+
+    #[test]
+    fn run_until() {
+        let mut intcode = Intcode::new(vec![1105, 1, 0]);
+        assert_eq!(
+            Ok(Response::BudgetExhausted),
+            intcode.run_until(Instant::now())
+        );
+        assert_eq!(0, intcode.steps);
+    }
+
+    #[test]
+    fn arithmetic() {
+        // Doubles the word at 7, which is 2^62, and outputs the result.
+        let program = vec![1002, 7, 2, 7, 4, 7, 99, 1 << 62];
+
+        for &engine in &[Engine::Interpreter, Engine::Decoded] {
+            let mut intcode = Intcode::new(program.clone()).with_engine(engine);
+            assert_eq!(Ok(Response::Terminated), intcode.run());
+            assert_eq!(vec![i64::MIN], intcode.output);
+
+            let mut intcode = Intcode::new(program.clone())
+                .with_engine(engine)
+                .with_arithmetic(Arithmetic::Checked);
+            assert_eq!(
+                Err(VmError::Overflow {
+                    cursor: 0,
+                    instruction: 1002
+                }),
+                intcode.run()
+            );
+            assert_eq!(1 << 62, intcode.get(7));
+        }
+
+        let mut intcode =
+            Intcode::new(vec![109, i64::MAX, 109, 1, 99]).with_arithmetic(Arithmetic::Checked);
+        assert_eq!(
+            Err("Arithmetic overflow in instruction 109 at offset 2!".to_string()),
+            intcode.run().map_err(String::from)
+        );
+    }
+
+    #[test]
+    fn from_str_invalid() {
+        assert!("1,2,three".parse::<Intcode>().is_err());
+    }
+}
@@ -0,0 +1,362 @@
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::fmt::{self, Write};
+use std::sync::{Arc, Mutex};
+
+use super::cfg::{self, Cfg};
+use super::{Intcode, Opcode, Response, VmError};
+
+/// Counts the instructions executed by the machines it's attached to. Clones share the same
+/// counts, so a profiler can be attached to every machine in a network and read once at the end.
+#[derive(Clone, Default)]
+pub struct Profiler {
+    profile: Arc<Mutex<Profile>>,
+}
+
+/// Execution counts gathered by a `Profiler`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Profile {
+    /// Instructions executed, not counting input instructions that found no input.
+    pub steps: u64,
+    pub addresses: HashMap<usize, u64>,
+    pub opcodes: HashMap<Opcode, u64>,
+
+    /// How many times execution stopped for input at each address.
+    pub input_waits: HashMap<usize, u64>,
+
+    /// Instructions defined by a `Dialect`, by address. These are included in `steps`, but not in
+    /// `addresses` or `opcodes`, since they don't mean what the standard opcodes do.
+    pub custom: HashMap<usize, u64>,
+}
+
+/// The counts for one basic block found by `cfg::analyze`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlockProfile {
+    pub start: usize,
+    pub end: usize,
+
+    /// How many times the block was entered.
+    pub entries: u64,
+
+    /// Instructions executed within the block.
+    pub steps: u64,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A copy of the counts so far.
+    pub fn profile(&self) -> Profile {
+        self.profile.lock().unwrap().clone()
+    }
+
+    /// Count the instruction `raw` found at `cursor`, given what happened when it was executed.
+    /// `custom` is whether the machine's dialect defines it.
+    pub(super) fn record(
+        &self,
+        cursor: usize,
+        raw: i64,
+        custom: bool,
+        result: &Result<Option<Response>, VmError>,
+    ) {
+        let mut profile = self.profile.lock().unwrap();
+
+        match result {
+            Ok(Some(Response::InputRequired)) => {
+                *profile.input_waits.entry(cursor).or_insert(0) += 1;
+            }
+            Ok(_) if custom => {
+                profile.steps += 1;
+                *profile.custom.entry(cursor).or_insert(0) += 1;
+            }
+            Ok(_) => {
+                profile.steps += 1;
+                *profile.addresses.entry(cursor).or_insert(0) += 1;
+                if let Ok(opcode) = Opcode::try_from(raw % 100) {
+                    *profile.opcodes.entry(opcode).or_insert(0) += 1;
+                }
+            }
+            Err(_) => {}
+        }
+    }
+}
+
+impl fmt::Debug for Profiler {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Profiler({} steps)", self.profile.lock().unwrap().steps)
+    }
+}
+
+impl Profile {
+    /// Counts for each block in the control-flow graph of the program, hottest first.
+    pub fn blocks(&self, cfg: &Cfg) -> Vec<BlockProfile> {
+        let mut blocks: Vec<BlockProfile> = cfg
+            .blocks
+            .iter()
+            .map(|(&start, block)| BlockProfile {
+                start,
+                end: block.end(),
+                entries: self.hits(start),
+                steps: block
+                    .instructions
+                    .iter()
+                    .map(|instruction| self.hits(instruction.address))
+                    .sum(),
+            })
+            .filter(|block| block.steps > 0)
+            .collect();
+
+        blocks.sort_by(|a, b| b.steps.cmp(&a.steps).then(a.start.cmp(&b.start)));
+        blocks
+    }
+
+    /// A ranked report of where `intcode`'s program spent its time, listing at most `limit`
+    /// addresses and blocks.
+    pub fn report(&self, intcode: &Intcode, limit: usize) -> String {
+        let cfg = cfg::analyze(intcode);
+        let mut output = String::new();
+        let percent = |count: u64| 100.0 * count as f64 / self.steps.max(1) as f64;
+
+        writeln!(output, "{} instructions", self.steps).unwrap();
+
+        writeln!(output, "\nopcode        count").unwrap();
+        let mut opcodes: Vec<(&Opcode, &u64)> = self.opcodes.iter().collect();
+        opcodes.sort_by(|a, b| b.1.cmp(a.1).then(a.0.code().cmp(&b.0.code())));
+        for (opcode, &count) in opcodes {
+            writeln!(
+                output,
+                "{:<6} {:>10} {:>5.1}%",
+                opcode.mnemonic(),
+                count,
+                percent(count)
+            )
+            .unwrap();
+        }
+        let custom: u64 = self.custom.values().sum();
+        if custom > 0 {
+            writeln!(
+                output,
+                "{:<6} {:>10} {:>5.1}%",
+                "custom",
+                custom,
+                percent(custom)
+            )
+            .unwrap();
+        }
+
+        writeln!(output, "\naddress       count").unwrap();
+        let mut addresses: Vec<(&usize, &u64)> = self.addresses.iter().collect();
+        addresses.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        for (&address, &count) in addresses.into_iter().take(limit) {
+            let instruction = intcode
+                .instruction(address)
+                .map_or_else(|e| e.to_string(), |i| i.to_string());
+            writeln!(
+                output,
+                "{:04}   {:>10} {:>5.1}%  {}",
+                address,
+                count,
+                percent(count),
+                instruction
+            )
+            .unwrap();
+        }
+
+        writeln!(output, "\nblock         count   entries").unwrap();
+        for block in self.blocks(&cfg).into_iter().take(limit) {
+            writeln!(
+                output,
+                "{:04}..{:04} {:>8} {:>5.1}% {:>8}",
+                block.start,
+                block.end,
+                block.steps,
+                percent(block.steps),
+                block.entries
+            )
+            .unwrap();
+        }
+
+        if !self.input_waits.is_empty() {
+            writeln!(output, "\ninput wait    count").unwrap();
+            let waits: BTreeMap<&usize, &u64> = self.input_waits.iter().collect();
+            for (address, count) in waits {
+                writeln!(output, "{:04}   {:>10}", address, count).unwrap();
+            }
+        }
+
+        output
+    }
+
+    /// The counts in the folded stack format read by flame graph tools such as `flamegraph.pl` and
+    /// `inferno-flamegraph`. Stacks are found statically rather than by following calls at run
+    /// time: each block is placed under the subroutine it belongs to, or `main`, instructions
+    /// outside any known block under `unknown`, and instructions defined by a dialect under
+    /// `custom`.
+    pub fn folded(&self, intcode: &Intcode) -> String {
+        let cfg = cfg::analyze(intcode);
+        let mut functions = HashMap::new();
+
+        for subroutine in cfg.subroutines.iter() {
+            for block in subroutine.blocks.iter() {
+                functions
+                    .entry(*block)
+                    .or_insert_with(|| format!("sub_{:04}", subroutine.entry));
+            }
+        }
+
+        let mut stacks: BTreeMap<String, u64> = BTreeMap::new();
+        let mut known = 0;
+
+        for block in self.blocks(&cfg) {
+            let function = functions
+                .get(&block.start)
+                .map_or("main", |name| name.as_str());
+            stacks.insert(
+                format!("{};{:04}..{:04}", function, block.start, block.end),
+                block.steps,
+            );
+            known += block.steps;
+        }
+
+        if known < self.steps {
+            for (&address, &count) in self.addresses.iter() {
+                if !cfg
+                    .blocks
+                    .range(..=address)
+                    .next_back()
+                    .is_some_and(|(_, block)| {
+                        block.instructions.iter().any(|i| i.address == address)
+                    })
+                {
+                    stacks.insert(format!("unknown;{:04}", address), count);
+                }
+            }
+        }
+
+        for (&address, &count) in self.custom.iter() {
+            stacks.insert(format!("custom;{:04}", address), count);
+        }
+
+        stacks
+            .into_iter()
+            .map(|(stack, count)| format!("{} {}\n", stack, count))
+            .collect()
+    }
+
+    fn hits(&self, address: usize) -> u64 {
+        self.addresses.get(&address).copied().unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::dialect::{Action, Dialect, Role};
+    use super::*;
+
+    fn countdown() -> (Intcode, Profiler) {
+        // Reads a number, then counts it down to zero, printing each value.
+        let profiler = Profiler::new();
+        let intcode = Intcode::new(vec![3, 12, 4, 12, 1001, 12, -1, 12, 1005, 12, 2, 99, 0])
+            .with_profiler(profiler.clone());
+
+        (intcode, profiler)
+    }
+
+    #[test]
+    fn counts() {
+        let (mut intcode, profiler) = countdown();
+        assert_eq!(Ok(Response::InputRequired), intcode.run());
+        intcode.input.push_back(3);
+        assert_eq!(Ok(Response::Terminated), intcode.run());
+
+        let profile = profiler.profile();
+        assert_eq!(11, profile.steps);
+        assert_eq!(Some(&3), profile.addresses.get(&2));
+        assert_eq!(Some(&3), profile.opcodes.get(&Opcode::JumpIfTrue));
+        assert_eq!(Some(&1), profile.opcodes.get(&Opcode::Halt));
+        assert_eq!(Some(&1), profile.input_waits.get(&0));
+        assert_eq!(
+            vec![
+                BlockProfile {
+                    start: 2,
+                    end: 11,
+                    entries: 3,
+                    steps: 9,
+                },
+                BlockProfile {
+                    start: 0,
+                    end: 2,
+                    entries: 1,
+                    steps: 1,
+                },
+                BlockProfile {
+                    start: 11,
+                    end: 12,
+                    entries: 1,
+                    steps: 1,
+                },
+            ],
+            profile.blocks(&cfg::analyze(&intcode)),
+        );
+    }
+
+    #[test]
+    fn report() {
+        let (mut intcode, profiler) = countdown();
+        intcode.input.push_back(2);
+        intcode.run().unwrap();
+
+        assert_eq!(
+            "8 instructions
+
+opcode        count
+ADD             2  25.0%
+OUT             2  25.0%
+JT              2  25.0%
+IN              1  12.5%
+HLT             1  12.5%
+
+address       count
+0002            2  25.0%  OUT [12]
+0004            2  25.0%  ADD [12], #-1 -> [12]
+0008            2  25.0%  JT [12], #2
+
+block         count   entries
+0002..0011        6  75.0%        2
+0000..0002        1  12.5%        1
+0011..0012        1  12.5%        1
+",
+            profiler.profile().report(&intcode, 3),
+        );
+        assert_eq!(
+            "main;0000..0002 1\nmain;0002..0011 6\nmain;0011..0012 1\n",
+            profiler.profile().folded(&intcode),
+        );
+    }
+
+    #[test]
+    fn custom() {
+        // ADD #1, #2 -> [0], with ADD replaced by an instruction that does nothing, then halt.
+        let mut dialect = Dialect::new();
+        dialect
+            .define(1, "NOP", &[Role::Read; 3], |_, _| Ok(Action::Next(vec![])))
+            .unwrap();
+        let profiler = Profiler::new();
+        let mut intcode = Intcode::new(vec![1101, 1, 2, 0, 99])
+            .with_dialect(dialect)
+            .with_profiler(profiler.clone());
+        assert_eq!(Ok(Response::Terminated), intcode.run());
+
+        let profile = profiler.profile();
+        assert_eq!(2, profile.steps);
+        assert_eq!(None, profile.opcodes.get(&Opcode::Add));
+        assert_eq!(Some(&1), profile.custom.get(&0));
+        assert_eq!(None, profile.addresses.get(&0));
+        assert!(profile
+            .report(&intcode, 3)
+            .contains("\ncustom          1  50.0%\n"));
+        assert!(profile.folded(&intcode).contains("custom;0000 1\n"));
+    }
+}
@@ -0,0 +1,687 @@
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryFrom;
+use std::mem;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+
+use super::io::{IntcodeInput, IntcodeOutput};
+use super::scheduler::{check_channel, Outcome, Route, Status};
+use super::{Intcode, Response};
+
+/// How the machines of a `Runtime` share the CPU.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    /// Every machine runs at the same time on its own thread.
+    Threaded,
+
+    /// Machines still get a thread each, but only one runs at a time: each runs until it halts or
+    /// blocks for input, then hands over to the next ready machine in the order they were added.
+    /// Values are sent in the same order on every run, as with `Policy::UntilBlocked` on a
+    /// `Scheduler`, which makes results reproducible for tests.
+    Deterministic,
+}
+
+/// A network of Intcode machines like `Scheduler` builds, but with each machine on its own OS thread
+/// and blocking when it needs input that hasn't arrived. Machines are added and connected first,
+/// then `start` launches them.
+///
+/// Programs see the same thing as they would under a `Scheduler`: their input values in the order
+/// they were sent, and the idle input, if any, whenever they find their input empty. A machine only
+/// blocks on the idle input when nothing has happened anywhere since it was last given it, which is
+/// when a `Scheduler` would find a round without progress.
+pub struct Runtime {
+    mode: Mode,
+    nodes: Vec<Node>,
+    machines: Vec<Intcode>,
+    channels: Vec<Channel>,
+}
+
+/// A running `Runtime`. Dropping it stops machines the next time they wait for input, without
+/// waiting for them.
+pub struct Handle {
+    shared: Arc<Shared>,
+    threads: Vec<JoinHandle<Intcode<Inbox, Outbox>>>,
+}
+
+struct Node {
+    name: String,
+    status: Status,
+    queue: VecDeque<i64>,
+    idle_input: Option<i64>,
+
+    /// The value of `State::activity` when the machine was last given the idle input.
+    idle_since: Option<u64>,
+
+    /// Whether the machine has been given the idle input during its current turn in deterministic
+    /// mode.
+    idle_this_turn: bool,
+}
+
+struct Channel {
+    name: String,
+    sources: Vec<usize>,
+    route: Route,
+    partial: HashMap<usize, Vec<i64>>,
+    undelivered: VecDeque<Vec<i64>>,
+}
+
+struct State {
+    nodes: Vec<Node>,
+    channels: Vec<Channel>,
+
+    /// The machine allowed to run in deterministic mode.
+    turn: Option<usize>,
+
+    /// Counts values read and written by any machine, so that those polling with the idle input can
+    /// tell when the network has gone quiet.
+    activity: u64,
+    crashes: VecDeque<String>,
+    shutdown: bool,
+}
+
+struct Shared {
+    mode: Mode,
+    state: Mutex<State>,
+
+    /// One per machine, signalled when it may be able to continue.
+    wake: Vec<Condvar>,
+
+    /// Signalled whenever a machine blocks, halts or crashes.
+    changed: Condvar,
+}
+
+/// The input end of a machine, which blocks until a value arrives.
+struct Inbox {
+    id: usize,
+    shared: Arc<Shared>,
+}
+
+/// The output end of a machine, which passes values along its channel or keeps them.
+struct Outbox {
+    id: usize,
+    shared: Arc<Shared>,
+    channel: Option<usize>,
+    kept: Vec<i64>,
+}
+
+impl Runtime {
+    pub fn new(mode: Mode) -> Self {
+        Self {
+            mode,
+            nodes: Vec::new(),
+            machines: Vec::new(),
+            channels: Vec::new(),
+        }
+    }
+
+    /// Add a machine, returning its id. Anything already in its input queue is kept.
+    pub fn add(&mut self, name: impl Into<String>, mut intcode: Intcode) -> usize {
+        self.nodes.push(Node {
+            name: name.into(),
+            status: Status::Ready,
+            queue: mem::take(&mut intcode.input),
+            idle_input: None,
+            idle_since: None,
+            idle_this_turn: false,
+        });
+        self.machines.push(intcode);
+        self.nodes.len() - 1
+    }
+
+    /// Rather than blocking when machine `id` asks for input and none is queued, give it `value`,
+    /// like the network interface controllers of day 23 do with -1. The machine only blocks if no
+    /// machine has read or written anything since it was last given `value`.
+    pub fn set_idle_input(&mut self, id: usize, value: i64) {
+        self.nodes[id].idle_input = Some(value);
+    }
+
+    /// Create a channel carrying the output of each of `sources`. Machines that aren't the source of
+    /// any channel keep their output. Every source and target must already have been added.
+    pub fn connect(&mut self, name: &str, sources: &[usize], route: Route) -> Result<(), String> {
+        check_channel(self.nodes.len(), sources, &route)?;
+        for &source in sources {
+            if let Some(channel) = self.channels.iter().find(|c| c.sources.contains(&source)) {
+                return Err(format!(
+                    "{} already sends its output to {}",
+                    self.nodes[source].name, channel.name
+                ));
+            }
+        }
+
+        self.channels.push(Channel {
+            name: name.to_string(),
+            sources: sources.to_vec(),
+            route,
+            partial: HashMap::new(),
+            undelivered: VecDeque::new(),
+        });
+        Ok(())
+    }
+
+    /// Queue input for machine `id`.
+    pub fn send(&mut self, id: usize, values: &[i64]) {
+        self.nodes[id].queue.extend(values.iter().copied());
+    }
+
+    /// Launch a thread for every machine.
+    pub fn start(self) -> Handle {
+        let machines = self.nodes.len();
+        let channels: Vec<Option<usize>> = (0..machines)
+            .map(|id| self.channels.iter().position(|c| c.sources.contains(&id)))
+            .collect();
+
+        let shared = Arc::new(Shared {
+            mode: self.mode,
+            state: Mutex::new(State {
+                nodes: self.nodes,
+                channels: self.channels,
+                turn: if machines > 0 && self.mode == Mode::Deterministic {
+                    Some(0)
+                } else {
+                    None
+                },
+                activity: 0,
+                crashes: VecDeque::new(),
+                shutdown: false,
+            }),
+            wake: (0..machines).map(|_| Condvar::new()).collect(),
+            changed: Condvar::new(),
+        });
+
+        let threads = self
+            .machines
+            .into_iter()
+            .zip(channels)
+            .enumerate()
+            .map(|(id, (intcode, channel))| {
+                let shared = shared.clone();
+                let intcode = intcode.with_io(
+                    Inbox {
+                        id,
+                        shared: shared.clone(),
+                    },
+                    Outbox {
+                        id,
+                        shared: shared.clone(),
+                        channel,
+                        kept: Vec::new(),
+                    },
+                );
+
+                thread::spawn(move || {
+                    let mut intcode = intcode;
+                    if shared.wait_turn(shared.lock(), id).shutdown {
+                        return intcode;
+                    }
+
+                    let result = intcode.run();
+                    let mut state = shared.lock();
+
+                    state.nodes[id].status = match result {
+                        Ok(Response::InputRequired) => return intcode,
+                        Ok(_) => Status::Halted,
+                        Err(e) => {
+                            let message = format!("{} crashed: {}", state.nodes[id].name, e);
+                            state.crashes.push_back(message);
+                            Status::Crashed(e)
+                        }
+                    };
+                    shared.pass_turn(&mut state, id);
+                    intcode
+                })
+            })
+            .collect();
+
+        Handle { shared, threads }
+    }
+}
+
+impl Handle {
+    /// Queue input for machine `id`, waking it if it was blocked.
+    pub fn send(&self, id: usize, values: &[i64]) {
+        let mut state = self.shared.lock();
+        self.shared.receive(&mut state, id, values);
+    }
+
+    /// Wait until every machine has halted, or every machine still running is blocked waiting for
+    /// input. Unless more input is sent, the machines are then deadlocked.
+    pub fn wait(&self) -> Result<Outcome, String> {
+        let mut state = self.shared.lock();
+
+        loop {
+            if let Some(crash) = state.crashes.pop_front() {
+                return Err(crash);
+            }
+
+            if state
+                .nodes
+                .iter()
+                .all(|node| matches!(node.status, Status::Halted | Status::Crashed(_)))
+            {
+                return Ok(Outcome::Halted);
+            }
+
+            if state.nodes.iter().all(|node| node.status != Status::Ready) {
+                return Ok(Outcome::Idle);
+            }
+
+            state = self.shared.changed.wait(state).unwrap();
+        }
+    }
+
+    /// Remove and return the packets a channel had nowhere to deliver, oldest first.
+    pub fn take_undelivered(&self, channel: &str) -> Vec<Vec<i64>> {
+        self.shared
+            .lock()
+            .channels
+            .iter_mut()
+            .find(|c| c.name == channel)
+            .map_or_else(Vec::new, |c| c.undelivered.drain(..).collect())
+    }
+
+    /// Stop every machine and return them, with any input they hadn't read yet and any output that
+    /// wasn't sent along a channel. Machines stop the next time they wait for input, so this only
+    /// returns once those that are still computing get there.
+    pub fn finish(mut self) -> Result<Vec<Intcode>, String> {
+        self.shared.stop();
+
+        let mut machines = Vec::new();
+        for (id, thread) in mem::take(&mut self.threads).into_iter().enumerate() {
+            let intcode = thread
+                .join()
+                .map_err(|_| format!("Machine {} panicked", id))?;
+            let queue = mem::take(&mut self.shared.lock().nodes[id].queue);
+            let kept = intcode.output.kept.clone();
+            machines.push(intcode.with_io(queue, kept));
+        }
+
+        Ok(machines)
+    }
+}
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        self.shared.stop();
+    }
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+
+    fn stop(&self) {
+        self.lock().shutdown = true;
+        for wake in self.wake.iter() {
+            wake.notify_all();
+        }
+    }
+
+    /// Block until machine `id` may run, or the runtime is shutting down.
+    fn wait_turn<'a>(&self, mut state: MutexGuard<'a, State>, id: usize) -> MutexGuard<'a, State> {
+        while !state.shutdown
+            && (state.nodes[id].status == Status::WaitingForInput
+                || (self.mode == Mode::Deterministic && state.turn != Some(id)))
+        {
+            state = self.wake[id].wait(state).unwrap();
+        }
+        state
+    }
+
+    /// Machine `id` has stopped running, so in deterministic mode let the next ready machine go.
+    fn pass_turn(&self, state: &mut State, id: usize) {
+        if self.mode == Mode::Deterministic {
+            let machines = state.nodes.len();
+            state.turn = (1..=machines)
+                .map(|offset| (id + offset) % machines)
+                .find(|&next| state.nodes[next].status == Status::Ready);
+
+            if let Some(next) = state.turn {
+                self.wake[next].notify_all();
+            }
+        }
+
+        self.changed.notify_all();
+    }
+
+    fn receive(&self, state: &mut State, id: usize, values: &[i64]) {
+        state.nodes[id].queue.extend(values.iter().copied());
+        self.wake_up(state, id);
+        self.active(state);
+    }
+
+    /// Let machine `id` continue if it was blocked for input.
+    fn wake_up(&self, state: &mut State, id: usize) {
+        let node = &mut state.nodes[id];
+
+        if node.status == Status::WaitingForInput {
+            node.status = Status::Ready;
+
+            if self.mode == Mode::Deterministic && state.turn.is_none() {
+                state.turn = Some(id);
+            }
+            self.wake[id].notify_all();
+        }
+    }
+
+    /// A value has been read or written, so machines blocked on the idle input may poll again.
+    fn active(&self, state: &mut State) {
+        state.activity += 1;
+
+        for id in 0..state.nodes.len() {
+            if state.nodes[id].idle_input.is_some() {
+                self.wake_up(state, id);
+            }
+        }
+    }
+}
+
+impl IntcodeInput for Inbox {
+    fn read(&mut self) -> Option<i64> {
+        let shared = &self.shared;
+        let mut state = shared.lock();
+
+        loop {
+            if state.shutdown {
+                return None;
+            }
+
+            let activity = state.activity;
+            let node = &mut state.nodes[self.id];
+            if let Some(value) = node.queue.pop_front() {
+                shared.active(&mut state);
+                return Some(value);
+            }
+
+            let quiet = node.idle_since == Some(activity);
+            match node.idle_input {
+                // In deterministic mode the idle input is given once a turn, so that the other
+                // machines run between polls, as they would in a round of a `Scheduler`.
+                Some(_) if !quiet && shared.mode == Mode::Deterministic && node.idle_this_turn => {}
+                Some(value) if !quiet => {
+                    node.idle_since = Some(activity);
+                    node.idle_this_turn = true;
+                    return Some(value);
+                }
+                _ => node.status = Status::WaitingForInput,
+            }
+
+            shared.pass_turn(&mut state, self.id);
+            state = shared.wait_turn(state, self.id);
+            state.nodes[self.id].idle_this_turn = false;
+        }
+    }
+}
+
+impl IntcodeOutput for Outbox {
+    fn write(&mut self, value: i64) {
+        let shared = &self.shared;
+        let mut state = shared.lock();
+        let state = &mut *state;
+        shared.active(state);
+
+        let index = match self.channel {
+            Some(index) => index,
+            None => return self.kept.push(value),
+        };
+        let channel = &mut state.channels[index];
+
+        match &channel.route {
+            Route::To(targets) => {
+                for target in targets.clone() {
+                    shared.receive(state, target, &[value]);
+                }
+            }
+            Route::Addressed(size) => {
+                let size = *size;
+                let partial = channel.partial.entry(self.id).or_default();
+                partial.push(value);
+
+                if partial.len() == size {
+                    let packet = mem::take(partial);
+
+                    match usize::try_from(packet[0]) {
+                        Ok(target) if target < state.nodes.len() => {
+                            shared.receive(state, target, &packet[1..])
+                        }
+                        _ => channel.undelivered.push_back(packet),
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::asm::assemble;
+    use super::super::scheduler::{Policy, Scheduler};
+    use super::*;
+
+    /// Adds one to each input and outputs it, forever.
+    fn increment() -> Intcode {
+        Intcode::new(vec![3, 11, 1001, 11, 1, 11, 4, 11, 1105, 1, 0, 0])
+    }
+
+    /// Two machines passing a value back and forth, each adding one, until one of them receives
+    /// 100 and halts.
+    fn ping_pong(mode: Mode) -> Vec<Intcode> {
+        let player = Intcode::new(vec![
+            3, 20, 1008, 20, 100, 21, 1005, 21, 18, 1001, 20, 1, 20, 4, 20, 1105, 1, 0, 99, 0, 0, 0,
+        ]);
+
+        let mut runtime = Runtime::new(mode);
+        let a = runtime.add("a", player.clone().with_input(&[0]));
+        let b = runtime.add("b", player);
+        runtime.connect("a to b", &[a], Route::To(vec![b])).unwrap();
+        runtime.connect("b to a", &[b], Route::To(vec![a])).unwrap();
+
+        let handle = runtime.start();
+        assert_eq!(Ok(Outcome::Idle), handle.wait());
+        handle.finish().unwrap()
+    }
+
+    #[test]
+    fn chain() {
+        for &mode in &[Mode::Threaded, Mode::Deterministic] {
+            let mut runtime = Runtime::new(mode);
+            let a = runtime.add("a", increment());
+            let b = runtime.add("b", increment());
+            let c = runtime.add("c", increment());
+            runtime
+                .connect("a to b and c", &[a], Route::To(vec![b, c]))
+                .unwrap();
+            runtime.connect("b to c", &[b], Route::To(vec![c])).unwrap();
+            assert_eq!(
+                Err("a already sends its output to a to b and c".to_string()),
+                runtime.connect("again", &[a], Route::To(vec![c])),
+            );
+            assert_eq!(
+                Err("No machine 99".to_string()),
+                runtime.connect("nowhere", &[c], Route::To(vec![99])),
+            );
+            assert_eq!(
+                Err("Packets need at least an address".to_string()),
+                runtime.connect("empty", &[c], Route::Addressed(0)),
+            );
+            runtime.send(a, &[1]);
+
+            let handle = runtime.start();
+            assert_eq!(Ok(Outcome::Idle), handle.wait());
+            handle.send(a, &[10]);
+            assert_eq!(Ok(Outcome::Idle), handle.wait());
+
+            let machines = handle.finish().unwrap();
+            let mut output = machines[c].output.clone();
+            if mode == Mode::Threaded {
+                // Values from a and b may arrive in either order.
+                output.sort();
+            }
+            assert_eq!(vec![3, 4, 12, 13], output);
+            assert_eq!(
+                vec![9, 9, 17],
+                machines.iter().map(|m| m.steps).collect::<Vec<u64>>()
+            );
+        }
+    }
+
+    #[test]
+    fn deterministic() {
+        // Both machines read from the same channel, so which gets each value depends only on the
+        // order they run in.
+        let mut runtime = Runtime::new(Mode::Deterministic);
+        let source = runtime.add("source", Intcode::new(vec![104, 1, 104, 2, 104, 3, 99]));
+        let a = runtime.add("a", increment());
+        let b = runtime.add("b", increment());
+        runtime
+            .connect("source to both", &[source], Route::To(vec![a, b]))
+            .unwrap();
+
+        let handle = runtime.start();
+        assert_eq!(Ok(Outcome::Idle), handle.wait());
+        let machines = handle.finish().unwrap();
+        assert_eq!(vec![2, 3, 4], machines[a].output);
+        assert_eq!(vec![2, 3, 4], machines[b].output);
+        assert_eq!(
+            Vec::<i64>::new(),
+            machines[a].input.iter().copied().collect::<Vec<i64>>()
+        );
+    }
+
+    #[test]
+    fn feedback() {
+        for &mode in &[Mode::Threaded, Mode::Deterministic] {
+            let machines = ping_pong(mode);
+            // Both last saw 100, but only a received it and halted; b sent it and is still waiting.
+            assert_eq!((100, 1), (machines[0].get(20), machines[0].get(21)));
+            assert_eq!((100, 0), (machines[1].get(20), machines[1].get(21)));
+            assert_eq!(
+                vec![304, 301],
+                machines.iter().map(|m| m.steps).collect::<Vec<u64>>()
+            );
+        }
+    }
+
+    #[test]
+    fn addressed() {
+        // Sends the packet [1, 5, 6] and then [7, 8, 9], then halts.
+        let sender = Intcode::new(vec![104, 1, 104, 5, 104, 6, 104, 7, 104, 8, 104, 9, 99]);
+        // Echoes two inputs, then halts.
+        let receiver = Intcode::new(vec![3, 9, 4, 9, 3, 9, 4, 9, 99, 0]);
+
+        let mut runtime = Runtime::new(Mode::Threaded);
+        let sender = runtime.add("sender", sender);
+        let receiver = runtime.add("receiver", receiver);
+        runtime
+            .connect("network", &[sender], Route::Addressed(3))
+            .unwrap();
+
+        let handle = runtime.start();
+        assert_eq!(Ok(Outcome::Halted), handle.wait());
+        assert_eq!(vec![vec![7, 8, 9]], handle.take_undelivered("network"));
+        assert!(handle.take_undelivered("network").is_empty());
+        assert_eq!(vec![5, 6], handle.finish().unwrap()[receiver].output);
+    }
+
+    #[test]
+    fn idle_input() {
+        // Outputs 7 unless its input is -1, then halts.
+        let intcode = Intcode::new(vec![
+            3, 13, 1008, 13, -1, 14, 1005, 14, 11, 104, 7, 99, 0, 0, 0,
+        ]);
+        let mut runtime = Runtime::new(Mode::Threaded);
+        let id = runtime.add("nic", intcode);
+        runtime.set_idle_input(id, -1);
+
+        let handle = runtime.start();
+        assert_eq!(Ok(Outcome::Halted), handle.wait());
+        assert_eq!(Vec::<i64>::new(), handle.finish().unwrap()[id].output);
+    }
+
+    #[test]
+    fn polling() {
+        // Sends 7 on once it has read the idle input three times.
+        let poller = assemble(
+            "
+            L_READ:
+                IN -> [L_VALUE]
+                EQ [L_VALUE], #-1 -> [L_IDLE]
+                JF [L_IDLE], #L_READ
+                ADD [L_COUNT], #1 -> [L_COUNT]
+                LT [L_COUNT], #3 -> [L_IDLE]
+                JT [L_IDLE], #L_READ
+                OUT #7
+                HLT
+            L_VALUE:
+                DATA 0
+            L_IDLE:
+                DATA 0
+            L_COUNT:
+                DATA 0
+            ",
+        )
+        .unwrap();
+        // Outputs how many times it has read its input, halting after five.
+        let ticker = assemble(
+            "
+            L_READ:
+                IN -> [L_VALUE]
+                ADD [L_COUNT], #1 -> [L_COUNT]
+                OUT [L_COUNT]
+                LT [L_COUNT], #5 -> [L_MORE]
+                JT [L_MORE], #L_READ
+                HLT
+            L_VALUE:
+                DATA 0
+            L_COUNT:
+                DATA 0
+            L_MORE:
+                DATA 0
+            ",
+        )
+        .unwrap();
+        // Echoes one value.
+        let echo = Intcode::new(vec![3, 5, 4, 5, 99, 0]);
+
+        let mut scheduler = Scheduler::new(Policy::UntilBlocked);
+        let mut runtime = Runtime::new(Mode::Deterministic);
+        for (name, program) in [("poller", &poller), ("ticker", &ticker)] {
+            let id = scheduler.add(name, Intcode::new(program.clone()));
+            scheduler.set_idle_input(id, -1);
+            let id = runtime.add(name, Intcode::new(program.clone()));
+            runtime.set_idle_input(id, -1);
+        }
+        let receiver = scheduler.add("echo", echo.clone());
+        runtime.add("echo", echo);
+        scheduler
+            .connect("poller to echo", &[0], Route::To(vec![receiver]))
+            .unwrap();
+        runtime
+            .connect("poller to echo", &[0], Route::To(vec![receiver]))
+            .unwrap();
+
+        assert_eq!(Ok(Outcome::Halted), scheduler.run());
+        let handle = runtime.start();
+        assert_eq!(Ok(Outcome::Halted), handle.wait());
+        let machines = handle.finish().unwrap();
+
+        assert_eq!(vec![1, 2, 3, 4, 5], scheduler.machine(1).output);
+        assert_eq!(vec![7], scheduler.machine(receiver).output);
+        for (id, machine) in machines.iter().enumerate() {
+            assert_eq!(scheduler.machine(id).output, machine.output);
+        }
+    }
+
+    #[test]
+    fn crash() {
+        let mut runtime = Runtime::new(Mode::Threaded);
+        runtime.add("broken", Intcode::new(vec![42]));
+
+        let handle = runtime.start();
+        assert_eq!(
+            Err("broken crashed: Unknown opcode in instruction 42 at offset 0!".to_string()),
+            handle.wait(),
+        );
+        assert_eq!(Ok(Outcome::Halted), handle.wait());
+    }
+}
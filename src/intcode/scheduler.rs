@@ -0,0 +1,412 @@
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryFrom;
+use std::fmt;
+
+use super::{Intcode, Response, VmError};
+
+/// How long each machine runs for when it's given a turn.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Policy {
+    /// Until it halts or needs input that hasn't arrived.
+    UntilBlocked,
+
+    /// For at most this many instructions, so that machines that rarely block share time fairly.
+    RoundRobin(u64),
+}
+
+/// Where a channel delivers the values output by its sources.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Route {
+    /// Copy every value to the input of each of these machines.
+    To(Vec<usize>),
+
+    /// Split the output of each source into packets of this many values, the first of which is an
+    /// address. The rest of the packet goes to the machine with that id. Packets for any other
+    /// address are kept with the channel, see `Scheduler::take_undelivered`.
+    Addressed(usize),
+}
+
+/// Check that a channel from `sources` along `route` only refers to the first `machines` machines,
+/// and that its packets have room for an address.
+pub(super) fn check_channel(
+    machines: usize,
+    sources: &[usize],
+    route: &Route,
+) -> Result<(), String> {
+    let targets = match route {
+        Route::To(targets) => &targets[..],
+        Route::Addressed(0) => return Err("Packets need at least an address".to_string()),
+        Route::Addressed(_) => &[],
+    };
+
+    match sources.iter().chain(targets).find(|&&id| id >= machines) {
+        Some(id) => Err(format!("No machine {}", id)),
+        None => Ok(()),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Status {
+    Ready,
+    WaitingForInput,
+    Halted,
+    Crashed(VmError),
+}
+
+/// Why `Scheduler::run` returned.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Outcome {
+    /// Every machine has halted.
+    Halted,
+
+    /// Every machine still running is waiting for input, and a whole round passed without any
+    /// values being sent or consumed. Unless something outside the scheduler sends more input, the
+    /// machines are deadlocked.
+    Idle,
+}
+
+struct Machine {
+    name: String,
+    intcode: Intcode,
+    status: Status,
+    idle_input: Option<i64>,
+}
+
+impl Machine {
+    fn receive(&mut self, values: &[i64]) {
+        self.intcode.input.extend(values.iter().copied());
+
+        if self.status == Status::WaitingForInput {
+            self.status = Status::Ready;
+        }
+    }
+}
+
+struct Channel {
+    name: String,
+    sources: Vec<usize>,
+    route: Route,
+    delivered: u64,
+    partial: HashMap<usize, Vec<i64>>,
+    undelivered: VecDeque<Vec<i64>>,
+}
+
+/// Runs a group of Intcode machines that talk to each other, passing output along channels to the
+/// input of other machines. Machines take turns in the order they were added.
+pub struct Scheduler {
+    policy: Policy,
+    machines: Vec<Machine>,
+    channels: Vec<Channel>,
+}
+
+impl Scheduler {
+    pub fn new(policy: Policy) -> Self {
+        Self {
+            policy,
+            machines: Vec::new(),
+            channels: Vec::new(),
+        }
+    }
+
+    /// Add a machine, returning its id.
+    pub fn add(&mut self, name: impl Into<String>, intcode: Intcode) -> usize {
+        self.machines.push(Machine {
+            name: name.into(),
+            intcode,
+            status: Status::Ready,
+            idle_input: None,
+        });
+        self.machines.len() - 1
+    }
+
+    /// Rather than blocking when machine `id` asks for input and none is queued, give it `value`,
+    /// like the network interface controllers of day 23 do with -1.
+    pub fn set_idle_input(&mut self, id: usize, value: i64) {
+        self.machines[id].idle_input = Some(value);
+    }
+
+    /// Create a channel carrying the output of each of `sources`. Machines that aren't the source of
+    /// any channel keep their output. Every source and target must already have been added.
+    pub fn connect(&mut self, name: &str, sources: &[usize], route: Route) -> Result<(), String> {
+        check_channel(self.machines.len(), sources, &route)?;
+        for &source in sources {
+            if let Some(channel) = self.channels.iter().find(|c| c.sources.contains(&source)) {
+                return Err(format!(
+                    "{} already sends its output to {}",
+                    self.machines[source].name, channel.name
+                ));
+            }
+        }
+
+        self.channels.push(Channel {
+            name: name.to_string(),
+            sources: sources.to_vec(),
+            route,
+            delivered: 0,
+            partial: HashMap::new(),
+            undelivered: VecDeque::new(),
+        });
+        Ok(())
+    }
+
+    /// Queue input for machine `id`.
+    pub fn send(&mut self, id: usize, values: &[i64]) {
+        self.machines[id].receive(values);
+    }
+
+    pub fn machine(&self, id: usize) -> &Intcode {
+        &self.machines[id].intcode
+    }
+
+    /// Remove and return the packets a channel had nowhere to deliver, oldest first.
+    pub fn take_undelivered(&mut self, channel: &str) -> Vec<Vec<i64>> {
+        self.channels
+            .iter_mut()
+            .find(|c| c.name == channel)
+            .map_or_else(Vec::new, |c| c.undelivered.drain(..).collect())
+    }
+
+    /// Give every machine that can run a turn. Returns false if nothing happened: no machine
+    /// consumed or produced a value, halted, or was cut off while still running.
+    pub fn round(&mut self) -> Result<bool, String> {
+        let mut progress = false;
+
+        for id in 0..self.machines.len() {
+            let machine = &mut self.machines[id];
+            let (queued, produced) = (machine.intcode.input.len(), machine.intcode.output.len());
+
+            match (&machine.status, machine.idle_input) {
+                (Status::Halted, _) | (Status::Crashed(_), _) => continue,
+                (_, Some(value)) if queued == 0 => machine.intcode.input.push_back(value),
+                (Status::WaitingForInput, None) if queued == 0 => continue,
+                _ => {}
+            }
+
+            let result = match self.policy {
+                Policy::UntilBlocked => machine.intcode.run(),
+                Policy::RoundRobin(steps) => machine.intcode.run_for(steps),
+            };
+
+            machine.status = match result {
+                Ok(Response::Terminated) => Status::Halted,
+                Ok(Response::InputRequired) => Status::WaitingForInput,
+                Ok(Response::BudgetExhausted) => Status::Ready,
+                Err(e) => {
+                    let message = format!("{} crashed: {}", machine.name, e);
+                    machine.status = Status::Crashed(e);
+                    return Err(message);
+                }
+            };
+
+            progress |= machine.status != Status::WaitingForInput
+                || machine.intcode.input.len() < queued
+                || machine.intcode.output.len() > produced;
+
+            self.deliver(id);
+        }
+
+        Ok(progress)
+    }
+
+    /// Run rounds until every machine has halted or nothing more can happen.
+    pub fn run(&mut self) -> Result<Outcome, String> {
+        loop {
+            if self
+                .machines
+                .iter()
+                .all(|machine| matches!(machine.status, Status::Halted | Status::Crashed(_)))
+            {
+                return Ok(Outcome::Halted);
+            }
+
+            if !self.round()? {
+                return Ok(Outcome::Idle);
+            }
+        }
+    }
+
+    /// Pass the output of machine `id` along its channel, if it has one.
+    fn deliver(&mut self, id: usize) {
+        let channel = match self.channels.iter_mut().find(|c| c.sources.contains(&id)) {
+            Some(channel) => channel,
+            None => return,
+        };
+        let output: Vec<i64> = self.machines[id].intcode.output.drain(..).collect();
+        let machines = &mut self.machines;
+
+        match &channel.route {
+            Route::To(targets) => {
+                for &target in targets {
+                    machines[target].receive(&output);
+                }
+                channel.delivered += output.len() as u64;
+            }
+            Route::Addressed(size) => {
+                let partial = channel.partial.entry(id).or_default();
+                partial.extend(output);
+
+                while partial.len() >= *size {
+                    let packet: Vec<i64> = partial.drain(..*size).collect();
+
+                    match usize::try_from(packet[0]) {
+                        Ok(target) if target < machines.len() => {
+                            machines[target].receive(&packet[1..]);
+                            channel.delivered += packet.len() as u64 - 1;
+                        }
+                        _ => channel.undelivered.push_back(packet),
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// ```text
+/// machine      status            steps  input output
+/// amplifier 0  halted              412      1      0
+///
+/// channel          delivered undelivered
+/// 0 to 1                  11           0
+/// ```
+impl fmt::Display for Scheduler {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "machine      status            steps  input output")?;
+        for machine in self.machines.iter() {
+            let status = match &machine.status {
+                Status::Ready => "ready".to_string(),
+                Status::WaitingForInput => "waiting".to_string(),
+                Status::Halted => "halted".to_string(),
+                Status::Crashed(e) => format!("crashed: {}", e),
+            };
+            writeln!(
+                f,
+                "{:<12} {:<12} {:>10} {:>6} {:>6}",
+                machine.name,
+                status,
+                machine.intcode.steps,
+                machine.intcode.input.len(),
+                machine.intcode.output.len()
+            )?;
+        }
+
+        if !self.channels.is_empty() {
+            writeln!(f, "\nchannel          delivered undelivered")?;
+            for channel in self.channels.iter() {
+                writeln!(
+                    f,
+                    "{:<16} {:>9} {:>11}",
+                    channel.name,
+                    channel.delivered,
+                    channel.undelivered.len()
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Adds one to each input and outputs it, forever.
+    fn increment() -> Intcode {
+        Intcode::new(vec![3, 11, 1001, 11, 1, 11, 4, 11, 1105, 1, 0, 0])
+    }
+
+    #[test]
+    fn chain() {
+        let mut scheduler = Scheduler::new(Policy::UntilBlocked);
+        let a = scheduler.add("a", increment());
+        let b = scheduler.add("b", increment());
+        let c = scheduler.add("c", increment());
+        scheduler
+            .connect("a to b and c", &[a], Route::To(vec![b, c]))
+            .unwrap();
+        scheduler
+            .connect("b to c", &[b], Route::To(vec![c]))
+            .unwrap();
+        assert_eq!(
+            Err("a already sends its output to a to b and c".to_string()),
+            scheduler.connect("again", &[a], Route::To(vec![c])),
+        );
+        assert_eq!(
+            Err("No machine 99".to_string()),
+            scheduler.connect("nowhere", &[c], Route::To(vec![99])),
+        );
+        assert_eq!(
+            Err("No machine 3".to_string()),
+            scheduler.connect("nobody", &[3], Route::To(vec![a])),
+        );
+        assert_eq!(
+            Err("Packets need at least an address".to_string()),
+            scheduler.connect("empty", &[c], Route::Addressed(0)),
+        );
+
+        scheduler.send(a, &[1, 10]);
+        assert_eq!(Ok(Outcome::Idle), scheduler.run());
+        assert_eq!(vec![3, 12, 4, 13], scheduler.machine(c).output);
+        assert_eq!(
+            "machine      status            steps  input output
+a            waiting               9      0      0
+b            waiting               9      0      0
+c            waiting              17      0      4
+
+channel          delivered undelivered
+a to b and c             2           0
+b to c                   2           0
+",
+            scheduler.to_string(),
+        );
+    }
+
+    #[test]
+    fn addressed() {
+        // Sends the packet [1, 5, 6] and then [7, 8, 9], then halts.
+        let sender = Intcode::new(vec![104, 1, 104, 5, 104, 6, 104, 7, 104, 8, 104, 9, 99]);
+        // Echoes two inputs, then halts.
+        let receiver = Intcode::new(vec![3, 9, 4, 9, 3, 9, 4, 9, 99, 0]);
+
+        let mut scheduler = Scheduler::new(Policy::RoundRobin(2));
+        let sender = scheduler.add("sender", sender);
+        let receiver = scheduler.add("receiver", receiver);
+        scheduler
+            .connect("network", &[sender], Route::Addressed(3))
+            .unwrap();
+
+        assert_eq!(Ok(Outcome::Halted), scheduler.run());
+        assert_eq!(vec![5, 6], scheduler.machine(receiver).output);
+        assert_eq!(vec![vec![7, 8, 9]], scheduler.take_undelivered("network"));
+        assert!(scheduler.take_undelivered("network").is_empty());
+    }
+
+    #[test]
+    fn idle_input() {
+        // Outputs 7 unless its input is -1, then halts.
+        let intcode = Intcode::new(vec![
+            3, 13, 1008, 13, -1, 14, 1005, 14, 11, 104, 7, 99, 0, 0, 0,
+        ]);
+        let mut scheduler = Scheduler::new(Policy::UntilBlocked);
+        let id = scheduler.add("nic", intcode);
+        scheduler.set_idle_input(id, -1);
+
+        assert_eq!(Ok(Outcome::Halted), scheduler.run());
+        assert_eq!(Vec::<i64>::new(), scheduler.machine(id).output);
+    }
+
+    #[test]
+    fn crash() {
+        let mut scheduler = Scheduler::new(Policy::UntilBlocked);
+        scheduler.add("broken", Intcode::new(vec![42]));
+
+        assert_eq!(
+            Err("broken crashed: Unknown opcode in instruction 42 at offset 0!".to_string()),
+            scheduler.run(),
+        );
+        assert!(scheduler
+            .to_string()
+            .contains("broken       crashed: Unknown opcode"));
+        assert_eq!(Ok(Outcome::Halted), scheduler.run());
+    }
+}
@@ -0,0 +1,328 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::str;
+use std::sync::{Arc, Mutex};
+
+use super::io::{IntcodeInput, IntcodeOutput};
+use super::{Intcode, Opcode, Param, Response, VmError};
+
+const MAGIC: &str = "intcode-session";
+const VERSION: u32 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+    Input,
+    Output,
+}
+
+/// A value read or written by a machine, and the step it was read or written on. Steps are counted
+/// from when recording started, and don't include input instructions that found no input, so that
+/// they don't depend on how often the program was left waiting.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Exchange {
+    pub step: u64,
+    pub direction: Direction,
+    pub value: i64,
+}
+
+/// `120 in 5` or `121 out 10`.
+impl fmt::Display for Exchange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let direction = match self.direction {
+            Direction::Input => "in",
+            Direction::Output => "out",
+        };
+        write!(f, "{} {} {}", self.step, direction, self.value)
+    }
+}
+
+impl str::FromStr for Exchange {
+    type Err = String;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid session line: {}", raw);
+        let fields: Vec<&str> = raw.split(' ').collect();
+
+        match fields[..] {
+            [step, direction, value] => Ok(Exchange {
+                step: step.parse().map_err(|_| invalid())?,
+                direction: match direction {
+                    "in" => Direction::Input,
+                    "out" => Direction::Output,
+                    _ => return Err(invalid()),
+                },
+                value: value.parse().map_err(|_| invalid())?,
+            }),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Every value a machine read and wrote, in order, so that a run can be replayed and checked.
+///
+/// Sessions are saved as text, a header line followed by one `Exchange` per line:
+///
+/// ```text
+/// intcode-session 1
+/// 1 in 5
+/// 4 out 10
+/// ```
+///
+/// Blank lines are skipped when loading. Instructions from a `Dialect` that read or write values
+/// aren't recorded.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Session {
+    pub exchanges: Vec<Exchange>,
+}
+
+impl Session {
+    pub fn inputs(&self) -> impl Iterator<Item = i64> + '_ {
+        self.exchanges
+            .iter()
+            .filter(|exchange| exchange.direction == Direction::Input)
+            .map(|exchange| exchange.value)
+    }
+
+    pub fn save(&self, mut writer: impl Write) -> Result<(), String> {
+        writeln!(writer, "{} {}", MAGIC, VERSION)
+            .and_then(|_| {
+                self.exchanges
+                    .iter()
+                    .try_for_each(|exchange| writeln!(writer, "{}", exchange))
+            })
+            .and_then(|_| writer.flush())
+            .map_err(|e| format!("Unable to write session: {}", e))
+    }
+
+    pub fn load(reader: impl BufRead) -> Result<Self, String> {
+        let mut lines = reader.lines();
+
+        let header = lines
+            .next()
+            .ok_or_else(|| "Empty session".to_string())?
+            .map_err(|e| e.to_string())?;
+        match header.strip_prefix(MAGIC).map(str::trim) {
+            Some(version) if version == VERSION.to_string() => {}
+            Some(version) => return Err(format!("Unsupported session version {}.", version)),
+            None => return Err(format!("Not a session: {}", header)),
+        }
+
+        let exchanges = lines
+            .map(|line| line.map_err(|e| e.to_string()))
+            .filter(|line| line.as_ref().map_or(true, |line| !line.trim().is_empty()))
+            .map(|line| line?.parse())
+            .collect::<Result<_, _>>()?;
+        Ok(Session { exchanges })
+    }
+
+    pub fn save_file(&self, path: impl AsRef<Path>) -> Result<(), String> {
+        let path = path.as_ref();
+        let file = File::create(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        self.save(BufWriter::new(file))
+    }
+
+    pub fn load_file(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Self::load(BufReader::new(file))
+    }
+
+    /// Run `intcode` on the recorded inputs, checking that it reads and writes the same values
+    /// on the same steps as it did when the session was recorded. The machine runs until it halts
+    /// or wants more input than was recorded, or for as many steps as the recording took.
+    pub fn replay(&self, intcode: Intcode) -> Result<(), String> {
+        let recorder = Recorder::new();
+        let mut inputs = self.inputs();
+        let mut intcode = intcode
+            .with_recorder(recorder.clone())
+            .with_io(|| inputs.next(), |_| {});
+
+        let last_step = self.exchanges.last().map_or(0, |exchange| exchange.step);
+        let result = intcode.run_for(last_step);
+        let replayed = recorder.session().exchanges;
+
+        let divergence = self
+            .exchanges
+            .iter()
+            .zip(replayed.iter())
+            .position(|(expected, actual)| expected != actual);
+        match (divergence, replayed.len()) {
+            (Some(index), _) => Err(format!(
+                "Exchange {} differs: expected {}, got {}",
+                index, self.exchanges[index], replayed[index]
+            )),
+            (None, len) if len < self.exchanges.len() => {
+                result?;
+                Err(format!(
+                    "Stopped after {} of {} exchanges, expected {}",
+                    len,
+                    self.exchanges.len(),
+                    self.exchanges[len]
+                ))
+            }
+            (None, _) => Ok(()),
+        }
+    }
+}
+
+/// A value an instruction is about to read into memory or write out.
+#[derive(Clone, Copy, Debug)]
+pub(super) enum Pending {
+    Input(usize),
+    Output(i64),
+}
+
+/// Records the values read and written by the machine it's attached to as a `Session`. Clones share
+/// the same session, so a recorder should only be attached to one machine.
+#[derive(Clone, Default)]
+pub struct Recorder {
+    log: Arc<Mutex<Log>>,
+}
+
+#[derive(Default)]
+struct Log {
+    session: Session,
+    steps: u64,
+}
+
+impl Recorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A copy of the session so far.
+    pub fn session(&self) -> Session {
+        self.log.lock().unwrap().session.clone()
+    }
+
+    /// Count the instruction just executed, and record the value it read or wrote, if any.
+    pub(super) fn record<I, O>(
+        &self,
+        intcode: &Intcode<I, O>,
+        pending: Option<Pending>,
+        result: &Result<Option<Response>, VmError>,
+    ) {
+        let mut log = self.log.lock().unwrap();
+
+        match result {
+            Ok(None) | Ok(Some(Response::Terminated)) => log.steps += 1,
+            _ => return,
+        }
+
+        let (direction, value) = match pending {
+            Some(Pending::Input(address)) => (Direction::Input, intcode.get(address)),
+            Some(Pending::Output(value)) => (Direction::Output, value),
+            None => return,
+        };
+        let step = log.steps;
+        log.session.exchanges.push(Exchange {
+            step,
+            direction,
+            value,
+        });
+    }
+}
+
+impl fmt::Debug for Recorder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Recorder({} exchanges)",
+            self.log.lock().unwrap().session.exchanges.len()
+        )
+    }
+}
+
+impl<I: IntcodeInput, O: IntcodeOutput> Intcode<I, O> {
+    /// What the instruction at the cursor will read or write, if it's an input or output
+    /// instruction.
+    pub(super) fn pending_io(&self) -> Option<Pending> {
+        let raw = self.get(self.cursor);
+        if self.dialect.as_ref().is_some_and(|d| d.defines(raw)) {
+            return None;
+        }
+
+        let instruction = self.instruction(self.cursor).ok()?;
+        match (instruction.opcode, instruction.params()) {
+            (Opcode::Input, [Param::Position(address)]) => {
+                Some(Pending::Input((*address).max(0) as usize))
+            }
+            (Opcode::Input, [Param::Relative(offset)]) => Some(Pending::Input(
+                offset.wrapping_add(self.relative_base).max(0) as usize,
+            )),
+            (Opcode::Output, _) => self.get_param(0).ok().map(Pending::Output),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::terminal::Terminal;
+    use super::*;
+
+    /// Reads two numbers and outputs their sum.
+    fn sum() -> Intcode {
+        Intcode::new(vec![3, 11, 3, 12, 1, 11, 12, 11, 4, 11, 99])
+    }
+
+    #[test]
+    fn record() {
+        let recorder = Recorder::new();
+        let mut intcode = sum().with_recorder(recorder.clone());
+
+        intcode.input.push_back(5);
+        assert_eq!(Ok(Response::InputRequired), intcode.run());
+        intcode.input.push_back(4);
+        assert_eq!(Ok(Response::Terminated), intcode.run());
+
+        let mut saved = Vec::new();
+        recorder.session().save(&mut saved).unwrap();
+        assert_eq!(
+            "intcode-session 1\n1 in 5\n2 in 4\n4 out 9\n",
+            String::from_utf8(saved.clone()).unwrap()
+        );
+
+        let session = Session::load(&saved[..]).unwrap();
+        assert_eq!(recorder.session(), session);
+        assert_eq!(Ok(()), session.replay(sum()));
+
+        // Multiply instead of adding.
+        let mut changed = sum();
+        changed.set(4, 2);
+        assert_eq!(
+            Err("Exchange 2 differs: expected 4 out 9, got 4 out 20".to_string()),
+            session.replay(changed)
+        );
+        assert_eq!(
+            Err("Stopped after 1 of 2 exchanges, expected 4 out 9".to_string()),
+            Session {
+                exchanges: vec![session.exchanges[0], session.exchanges[2]]
+            }
+            .replay(sum())
+        );
+        assert_eq!(
+            Err("Invalid session line: 4 sideways 9".to_string()),
+            Session::load(&b"intcode-session 1\n4 sideways 9\n"[..])
+        );
+        assert_eq!(
+            Ok(session.clone()),
+            Session::load(&b"intcode-session 1\n1 in 5\n\n2 in 4\n4 out 9\n\n"[..])
+        );
+    }
+
+    #[test]
+    fn replay_adventure() {
+        let intcode: Intcode = include_str!("../day25/input.txt").parse().unwrap();
+        let recorder = Recorder::new();
+        let mut terminal = Terminal::new(intcode.clone().with_recorder(recorder.clone()));
+        terminal
+            .script(&[("Command?", "south"), ("Command?", "inv")])
+            .unwrap();
+
+        let session = recorder.session();
+        assert_eq!(Ok(()), session.replay(intcode));
+    }
+}
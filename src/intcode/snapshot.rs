@@ -0,0 +1,269 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use super::{Arithmetic, Intcode};
+
+const MAGIC: &str = "intcode-snapshot";
+const VERSION: u32 = 1;
+
+/// Machine state is saved as a small text file, one field per line, so that a snapshot can be
+/// inspected or patched by hand:
+///
+/// ```text
+/// intcode-snapshot 1
+/// cursor 4
+/// relative_base 0
+/// steps 1
+/// input 5,6
+/// output
+/// memory 3,9,4,9,99,0,0,0,0,0
+/// arithmetic checked
+/// pages 1000000=7,1000001=8
+/// ```
+///
+/// Fields must appear in this order. `arithmetic` is left out for the default `Wrapping`
+/// arithmetic. `pages` lists the non-zero words in sparse memory, and is left out when there are
+/// none. Nothing attached to the machine is saved: a loaded machine has no tracer, profiler,
+/// recorder or dialect, and runs on the default engine.
+impl Intcode {
+    pub fn save(&self, mut writer: impl Write) -> Result<(), String> {
+        write!(
+            writer,
+            "{} {}\ncursor {}\nrelative_base {}\nsteps {}\ninput {}\noutput {}\nmemory {}\n",
+            MAGIC,
+            VERSION,
+            self.cursor,
+            self.relative_base,
+            self.steps,
+            join(&self.input),
+            join(&self.output),
+            join(&self.data),
+        )
+        .and_then(|_| match self.arithmetic {
+            Arithmetic::Wrapping => Ok(()),
+            Arithmetic::Checked => writeln!(writer, "arithmetic checked"),
+        })
+        .and_then(|_| {
+            let words = self.pages.words();
+            if words.is_empty() {
+                return Ok(());
+            }
+
+            let words: Vec<String> = words
+                .iter()
+                .map(|(address, value)| format!("{}={}", address, value))
+                .collect();
+            writeln!(writer, "pages {}", words.join(","))
+        })
+        .and_then(|_| writer.flush())
+        .map_err(|e| format!("Unable to write snapshot: {}", e))
+    }
+
+    pub fn load(reader: impl BufRead) -> Result<Self, String> {
+        // Blank lines, such as one left at the end by an editor, are skipped.
+        let mut lines = reader
+            .lines()
+            .filter(|line| line.as_ref().map_or(true, |line| !line.trim().is_empty()))
+            .peekable();
+
+        let version: u32 = parse(MAGIC, &field(&mut lines, MAGIC)?)?;
+        if version != VERSION {
+            return Err(format!("Unsupported snapshot version {}.", version));
+        }
+
+        let cursor = parse("cursor", &field(&mut lines, "cursor")?)?;
+        let relative_base = parse("relative_base", &field(&mut lines, "relative_base")?)?;
+        let steps = parse("steps", &field(&mut lines, "steps")?)?;
+        let input = parse_list("input", &field(&mut lines, "input")?)?;
+        let output = parse_list("output", &field(&mut lines, "output")?)?;
+        let data = parse_list("memory", &field(&mut lines, "memory")?)?;
+        let arithmetic = match lines.peek() {
+            Some(Ok(line)) if line.starts_with("arithmetic") => {
+                match &field(&mut lines, "arithmetic")?[..] {
+                    "wrapping" => Arithmetic::Wrapping,
+                    "checked" => Arithmetic::Checked,
+                    raw => return Err(format!("Invalid arithmetic in snapshot: {}", raw)),
+                }
+            }
+            _ => Arithmetic::Wrapping,
+        };
+        let pages = match lines.peek() {
+            Some(_) => field(&mut lines, "pages")?,
+            None => String::new(),
+        };
+
+        let mut intcode = Self {
+            cursor,
+            relative_base,
+            steps,
+            input: input.into(),
+            output,
+            ..Intcode::new(data).with_arithmetic(arithmetic)
+        };
+
+        for word in pages.split(',').filter(|word| !word.is_empty()) {
+            let (address, value) = word
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid pages in snapshot: {}", word))?;
+            intcode.set(parse("pages", address)?, parse("pages", value)?);
+        }
+
+        Ok(intcode)
+    }
+
+    pub fn save_file(&self, path: impl AsRef<Path>) -> Result<(), String> {
+        let path = path.as_ref();
+        let file = File::create(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        self.save(BufWriter::new(file))
+    }
+
+    pub fn load_file(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        Self::load(BufReader::new(file))
+    }
+}
+
+fn field(
+    lines: &mut impl Iterator<Item = io::Result<String>>,
+    name: &str,
+) -> Result<String, String> {
+    let line = lines
+        .next()
+        .ok_or_else(|| format!("Snapshot is missing {}.", name))?
+        .map_err(|e| format!("Unable to read snapshot: {}", e))?;
+
+    match line.split_once(' ') {
+        Some((key, value)) if key == name => Ok(value.trim().to_string()),
+        None if line.trim() == name => Ok(String::new()),
+        _ => Err(format!("Expected {} in snapshot, found {}", name, line)),
+    }
+}
+
+fn join<'a>(values: impl IntoIterator<Item = &'a i64>) -> String {
+    values
+        .into_iter()
+        .map(|value| value.to_string())
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+fn parse<T: FromStr>(name: &str, raw: &str) -> Result<T, String> {
+    raw.parse()
+        .map_err(|_| format!("Invalid {} in snapshot: {}", name, raw))
+}
+
+fn parse_list(name: &str, raw: &str) -> Result<Vec<i64>, String> {
+    if raw.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    raw.split(',').map(|value| parse(name, value)).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::Response;
+    use super::*;
+
+    #[test]
+    fn save_and_load() {
+        // Echoes two inputs.
+        let mut intcode = Intcode::new(vec![3, 9, 4, 9, 3, 9, 4, 9, 99]).with_input(&[5]);
+        assert_eq!(Response::InputRequired, intcode.run().unwrap());
+        intcode.input.push_back(6);
+
+        let mut snapshot = Vec::new();
+        intcode.save(&mut snapshot).unwrap();
+        assert_eq!(
+            "intcode-snapshot 1
+cursor 4
+relative_base 0
+steps 3
+input 6
+output 5
+memory 3,9,4,9,3,9,4,9,99,5
+",
+            String::from_utf8(snapshot.clone()).unwrap(),
+        );
+
+        let mut restored = Intcode::load(&snapshot[..]).unwrap();
+        assert_eq!(Response::Terminated, restored.run().unwrap());
+        assert_eq!(vec![5, 6], restored.output);
+        assert_eq!(intcode.steps + 3, restored.steps);
+
+        snapshot.extend(b"\n\n");
+        let restored = Intcode::load(&snapshot[..]).unwrap();
+        assert_eq!(intcode.data, restored.data);
+    }
+
+    #[test]
+    fn save_and_load_pages() {
+        let mut intcode = Intcode::new(vec![99]);
+        intcode.set(1_000_000, 7);
+        intcode.set(1_000_001, 8);
+
+        let mut snapshot = Vec::new();
+        intcode.save(&mut snapshot).unwrap();
+        assert!(String::from_utf8(snapshot.clone())
+            .unwrap()
+            .ends_with("memory 99\npages 1000000=7,1000001=8\n"));
+
+        let restored = Intcode::load(&snapshot[..]).unwrap();
+        assert_eq!(vec![99], restored.data);
+        assert_eq!((7, 8), (restored.get(1_000_000), restored.get(1_000_001)));
+        assert_eq!(Arithmetic::Wrapping, restored.arithmetic);
+    }
+
+    #[test]
+    fn save_and_load_arithmetic() {
+        let mut intcode = Intcode::new(vec![99]).with_arithmetic(Arithmetic::Checked);
+        intcode.set(1_000_000, 7);
+
+        let mut snapshot = Vec::new();
+        intcode.save(&mut snapshot).unwrap();
+        assert!(String::from_utf8(snapshot.clone())
+            .unwrap()
+            .ends_with("memory 99\narithmetic checked\npages 1000000=7\n"));
+
+        let restored = Intcode::load(&snapshot[..]).unwrap();
+        assert_eq!(Arithmetic::Checked, restored.arithmetic);
+        assert_eq!(7, restored.get(1_000_000));
+    }
+
+    #[test]
+    fn load_errors() {
+        assert_eq!(
+            Err("Unsupported snapshot version 2.".to_string()),
+            Intcode::load("intcode-snapshot 2\n".as_bytes()).map(|_| ()),
+        );
+        assert_eq!(
+            Err("Snapshot is missing relative_base.".to_string()),
+            Intcode::load("intcode-snapshot 1\ncursor 0\n".as_bytes()).map(|_| ()),
+        );
+        assert_eq!(
+            Err("Expected cursor in snapshot, found steps 1".to_string()),
+            Intcode::load("intcode-snapshot 1\nsteps 1\n".as_bytes()).map(|_| ()),
+        );
+        assert_eq!(
+            Err("Invalid memory in snapshot: x".to_string()),
+            Intcode::load(
+                "intcode-snapshot 1\ncursor 0\nrelative_base 0\nsteps 0\ninput\noutput\nmemory 1,x\n"
+                    .as_bytes()
+            )
+            .map(|_| ()),
+        );
+        assert_eq!(
+            Err("Invalid arithmetic in snapshot: fast".to_string()),
+            Intcode::load(
+                "intcode-snapshot 1\ncursor 0\nrelative_base 0\nsteps 0\ninput\noutput\nmemory 1\narithmetic fast\n"
+                    .as_bytes()
+            )
+            .map(|_| ()),
+        );
+    }
+}
@@ -0,0 +1,952 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::ops::Range;
+use std::sync::Arc;
+
+use super::{Instruction, Intcode, Opcode, Param, Response, VmError};
+
+/// Paths are abandoned with `Response::BudgetExhausted` after executing this many instructions.
+pub const DEFAULT_STEP_LIMIT: u64 = 100_000;
+
+/// Exploration stops once this many paths have been followed to their end.
+pub const DEFAULT_PATH_LIMIT: usize = 1_000;
+
+/// Rounds of bound tightening the solver does before splitting a variable's range.
+const PROPAGATION_ROUNDS: usize = 64;
+
+/// Ranges the solver may split before it gives up on a set of constraints.
+const SOLVER_BUDGET: usize = 10_000;
+
+/// A symbolic variable, numbered in the order the variables were created.
+pub type Var = usize;
+
+/// `constant + coefficient * variable + ...`, with only non-zero coefficients stored.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Linear {
+    pub constant: i64,
+    pub terms: BTreeMap<Var, i64>,
+}
+
+impl Linear {
+    pub fn constant(value: i64) -> Self {
+        Self {
+            constant: value,
+            terms: BTreeMap::new(),
+        }
+    }
+
+    pub fn var(var: Var) -> Self {
+        Self {
+            constant: 0,
+            terms: vec![(var, 1)].into_iter().collect(),
+        }
+    }
+
+    pub fn as_constant(&self) -> Option<i64> {
+        if self.terms.is_empty() {
+            Some(self.constant)
+        } else {
+            None
+        }
+    }
+
+    /// The sum, unless a coefficient overflows.
+    fn add(&self, other: &Linear) -> Option<Linear> {
+        let mut sum = self.clone();
+        sum.constant = sum.constant.checked_add(other.constant)?;
+
+        for (&var, &coefficient) in other.terms.iter() {
+            let entry = sum.terms.entry(var).or_insert(0);
+            *entry = entry.checked_add(coefficient)?;
+            if *entry == 0 {
+                sum.terms.remove(&var);
+            }
+        }
+
+        Some(sum)
+    }
+
+    /// The product, if one side is a constant and no coefficient overflows.
+    fn mul(&self, other: &Linear) -> Option<Linear> {
+        let (factor, linear) = match (self.as_constant(), other.as_constant()) {
+            (Some(factor), _) => (factor, other),
+            (_, Some(factor)) => (factor, self),
+            _ => return None,
+        };
+
+        if factor == 0 {
+            return Some(Linear::constant(0));
+        }
+
+        Some(Linear {
+            constant: linear.constant.checked_mul(factor)?,
+            terms: linear
+                .terms
+                .iter()
+                .map(|(&var, &coefficient)| Some((var, coefficient.checked_mul(factor)?)))
+                .collect::<Option<_>>()?,
+        })
+    }
+
+    /// `self - other` as a constant and coefficients, which can't overflow an `i128`.
+    fn difference(&self, other: &Linear) -> (i128, Vec<(Var, i128)>) {
+        let mut terms: BTreeMap<Var, i128> = BTreeMap::new();
+        for (&var, &coefficient) in self.terms.iter() {
+            *terms.entry(var).or_insert(0) += coefficient as i128;
+        }
+        for (&var, &coefficient) in other.terms.iter() {
+            *terms.entry(var).or_insert(0) -= coefficient as i128;
+        }
+
+        (
+            self.constant as i128 - other.constant as i128,
+            terms
+                .into_iter()
+                .filter(|&(_, coefficient)| coefficient != 0)
+                .collect(),
+        )
+    }
+}
+
+/// The contents of a word of memory during symbolic execution.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Linear(Linear),
+
+    /// Something the engine doesn't track, such as the product of two variables or a word read
+    /// from an address that depends on a variable.
+    Unknown,
+}
+
+impl Value {
+    pub fn constant(value: i64) -> Self {
+        Value::Linear(Linear::constant(value))
+    }
+
+    pub fn as_constant(&self) -> Option<i64> {
+        match self {
+            Value::Linear(linear) => linear.as_constant(),
+            Value::Unknown => None,
+        }
+    }
+
+    /// Combine two values with `f`, or with `wrapping` if both are constants, so that concrete
+    /// arithmetic behaves exactly as it does in the machine.
+    fn combine(
+        &self,
+        other: &Value,
+        wrapping: fn(i64, i64) -> i64,
+        f: fn(&Linear, &Linear) -> Option<Linear>,
+    ) -> Value {
+        match (self, other) {
+            (Value::Linear(a), Value::Linear(b)) => match (a.as_constant(), b.as_constant()) {
+                (Some(a), Some(b)) => Value::constant(wrapping(a, b)),
+                _ => f(a, b).map_or(Value::Unknown, Value::Linear),
+            },
+            _ => Value::Unknown,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Relation {
+    Eq,
+    Ne,
+    Lt,
+    Ge,
+}
+
+/// A condition on the variables, such as one a path took at a jump or comparison.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Constraint {
+    pub left: Linear,
+    pub relation: Relation,
+    pub right: Linear,
+}
+
+impl Constraint {
+    pub fn new(left: Linear, relation: Relation, right: Linear) -> Self {
+        Self {
+            left,
+            relation,
+            right,
+        }
+    }
+
+    fn negate(&self) -> Self {
+        let relation = match self.relation {
+            Relation::Eq => Relation::Ne,
+            Relation::Ne => Relation::Eq,
+            Relation::Lt => Relation::Ge,
+            Relation::Ge => Relation::Lt,
+        };
+        Self::new(self.left.clone(), relation, self.right.clone())
+    }
+
+    /// Whether the constraint holds, if it doesn't depend on any variables.
+    fn evaluate(&self) -> Option<bool> {
+        let (left, right) = (self.left.as_constant()?, self.right.as_constant()?);
+
+        Some(match self.relation {
+            Relation::Eq => left == right,
+            Relation::Ne => left != right,
+            Relation::Lt => left < right,
+            Relation::Ge => left >= right,
+        })
+    }
+}
+
+/// Why a path ended.
+#[derive(Clone, Debug, PartialEq)]
+pub enum End {
+    /// The machine stopped as `Intcode::run` would, or with `Response::BudgetExhausted` if the path
+    /// went over the step limit.
+    Stopped(Response),
+
+    Error(VmError),
+
+    /// The path depends on the variables in a way the engine can't follow.
+    Unsupported {
+        cursor: usize,
+        reason: &'static str,
+    },
+}
+
+/// ```text
+/// Terminated
+/// Unknown opcode in instruction 42 at offset 19!
+/// Jump to an address that depends on a variable at offset 12
+/// ```
+impl fmt::Display for End {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            End::Stopped(response) => write!(f, "{:?}", response),
+            End::Error(error) => write!(f, "{}", error),
+            End::Unsupported { cursor, reason } => write!(f, "{} at offset {}", reason, cursor),
+        }
+    }
+}
+
+/// One way through the program, and the constraints on the variables that lead along it.
+#[derive(Clone, Debug)]
+pub struct Path {
+    pub end: End,
+    pub constraints: Vec<Constraint>,
+    pub output: Vec<Value>,
+    pub steps: u64,
+    memory: HashMap<usize, Value>,
+    program: Arc<Intcode>,
+}
+
+impl Path {
+    /// The word at `address` when the path ended.
+    pub fn get(&self, address: usize) -> Value {
+        self.memory
+            .get(&address)
+            .cloned()
+            .unwrap_or_else(|| Value::constant(self.program.get(address)))
+    }
+}
+
+#[derive(Clone)]
+struct State {
+    memory: HashMap<usize, Value>,
+    cursor: usize,
+    relative_base: i64,
+    inputs_read: usize,
+    steps: u64,
+    output: Vec<Value>,
+    constraints: Vec<Constraint>,
+
+    /// The outcome of the branch at the cursor, for a path forked off there.
+    assume: Option<bool>,
+}
+
+/// Runs a program with some of its memory or input replaced by variables, following each way the
+/// jumps and comparisons (opcodes 5 to 8) could go and recording the conditions on the variables
+/// that lead each way. A path is only followed if a built in solver can't rule out its conditions.
+///
+/// The variables can be added, subtracted and multiplied by constants. Anything else, such as
+/// multiplying two variables or reading an address that depends on one, gives `Value::Unknown`,
+/// and a path ends with `End::Unsupported` when it needs an unknown value to continue.
+///
+/// The solver handles linear constraints over variables with bounded ranges, by tightening the
+/// bounds each constraint implies and splitting ranges in half when that gets stuck. It's quick
+/// for conditions like those in the puzzle inputs, and gives up on hard cases.
+pub struct Explorer {
+    program: Arc<Intcode>,
+    memory: HashMap<usize, Value>,
+    input: Vec<Value>,
+    names: Vec<String>,
+    domains: Vec<Range<i64>>,
+    step_limit: u64,
+    path_limit: usize,
+}
+
+impl Explorer {
+    pub fn new(program: Intcode) -> Self {
+        Self {
+            program: Arc::new(program),
+            memory: HashMap::new(),
+            input: Vec::new(),
+            names: Vec::new(),
+            domains: Vec::new(),
+            step_limit: DEFAULT_STEP_LIMIT,
+            path_limit: DEFAULT_PATH_LIMIT,
+        }
+    }
+
+    pub fn with_step_limit(mut self, step_limit: u64) -> Self {
+        self.step_limit = step_limit;
+        self
+    }
+
+    pub fn with_path_limit(mut self, path_limit: usize) -> Self {
+        self.path_limit = path_limit;
+        self
+    }
+
+    /// A new variable taking values from `domain`.
+    pub fn variable(&mut self, name: &str, domain: Range<i64>) -> Var {
+        self.names.push(name.to_string());
+        self.domains.push(domain);
+        self.names.len() - 1
+    }
+
+    /// Replace the word at `address` with a new variable.
+    pub fn set_symbolic(&mut self, address: usize, name: &str, domain: Range<i64>) -> Var {
+        let var = self.variable(name, domain);
+        self.memory.insert(address, Value::Linear(Linear::var(var)));
+        var
+    }
+
+    /// Queue a new variable as the next input value.
+    pub fn input_symbolic(&mut self, name: &str, domain: Range<i64>) -> Var {
+        let var = self.variable(name, domain);
+        self.input.push(Value::Linear(Linear::var(var)));
+        var
+    }
+
+    pub fn input(&mut self, value: i64) {
+        self.input.push(Value::constant(value));
+    }
+
+    pub fn input_str(&mut self, data: &str) {
+        self.input
+            .extend(data.chars().map(|c| Value::constant(c as i64)));
+    }
+
+    /// Follow every feasible path through the program, depth first, up to the path limit.
+    pub fn explore(&self) -> Vec<Path> {
+        let mut pending = vec![State {
+            memory: self.memory.clone(),
+            cursor: 0,
+            relative_base: 0,
+            inputs_read: 0,
+            steps: 0,
+            output: Vec::new(),
+            constraints: Vec::new(),
+            assume: None,
+        }];
+        let mut paths = Vec::new();
+
+        while let Some(mut state) = pending.pop() {
+            if paths.len() == self.path_limit {
+                break;
+            }
+
+            let end = loop {
+                if state.steps == self.step_limit {
+                    break End::Stopped(Response::BudgetExhausted);
+                }
+
+                state.steps += 1;
+                if let Err(end) = self.step(&mut state, &mut pending) {
+                    break end;
+                }
+            };
+
+            paths.push(Path {
+                end,
+                constraints: state.constraints,
+                output: state.output,
+                steps: state.steps,
+                memory: state.memory,
+                program: self.program.clone(),
+            });
+        }
+
+        paths
+    }
+
+    /// Values for the variables that lead along `path` and also meet `extra`, if the solver can
+    /// find any.
+    pub fn solve(&self, path: &Path, extra: &[Constraint]) -> Option<Vec<i64>> {
+        let constraints: Vec<Constraint> = path.constraints.iter().chain(extra).cloned().collect();
+
+        match Solver::new(&constraints).solve(&self.domains) {
+            Solution::Found(values) => Some(values),
+            Solution::Infeasible | Solution::Unknown => None,
+        }
+    }
+
+    /// ```text
+    /// Path 1: Terminated after 8 steps
+    ///   x < 10
+    ///   output 1
+    ///   for example x = 0
+    /// ```
+    pub fn report(&self, paths: &[Path]) -> String {
+        let mut out = String::new();
+
+        for (index, path) in paths.iter().enumerate() {
+            if index > 0 {
+                out.push('\n');
+            }
+            out += &format!(
+                "Path {}: {} after {} steps\n",
+                index + 1,
+                path.end,
+                path.steps
+            );
+
+            for constraint in path.constraints.iter() {
+                out += &format!("  {}\n", self.show_constraint(constraint));
+            }
+
+            if !path.output.is_empty() {
+                let output: Vec<String> = path.output.iter().map(|v| self.show(v)).collect();
+                out += &format!("  output {}\n", output.join(", "));
+            }
+
+            if let Some(values) = self.solve(path, &[]).filter(|values| !values.is_empty()) {
+                let values: Vec<String> = values
+                    .iter()
+                    .zip(self.names.iter())
+                    .map(|(value, name)| format!("{} = {}", name, value))
+                    .collect();
+                out += &format!("  for example {}\n", values.join(", "));
+            }
+        }
+
+        out
+    }
+
+    /// A value written out with the names of its variables, eg. `3*x - y + 2`.
+    pub fn show(&self, value: &Value) -> String {
+        let linear = match value {
+            Value::Linear(linear) => linear,
+            Value::Unknown => return "?".to_string(),
+        };
+
+        let mut out = String::new();
+        for (&var, &coefficient) in linear.terms.iter() {
+            let sign = if coefficient < 0 { "-" } else { "+" };
+            out += &match (out.is_empty(), sign) {
+                (true, "+") => String::new(),
+                (true, _) => sign.to_string(),
+                (false, _) => format!(" {} ", sign),
+            };
+            if coefficient.abs() != 1 {
+                out += &format!("{}*", coefficient.unsigned_abs());
+            }
+            out += &self.names[var];
+        }
+
+        match (out.is_empty(), linear.constant) {
+            (true, constant) => constant.to_string(),
+            (false, 0) => out,
+            (false, constant) if constant < 0 => format!("{} - {}", out, constant.unsigned_abs()),
+            (false, constant) => format!("{} + {}", out, constant),
+        }
+    }
+
+    pub fn show_constraint(&self, constraint: &Constraint) -> String {
+        let relation = match constraint.relation {
+            Relation::Eq => "==",
+            Relation::Ne => "!=",
+            Relation::Lt => "<",
+            Relation::Ge => ">=",
+        };
+
+        format!(
+            "{} {} {}",
+            self.show(&Value::Linear(constraint.left.clone())),
+            relation,
+            self.show(&Value::Linear(constraint.right.clone()))
+        )
+    }
+
+    fn get(&self, state: &State, address: usize) -> Value {
+        state
+            .memory
+            .get(&address)
+            .cloned()
+            .unwrap_or_else(|| Value::constant(self.program.get(address)))
+    }
+
+    fn step(&self, state: &mut State, pending: &mut Vec<State>) -> Result<(), End> {
+        let cursor = state.cursor;
+        let unsupported = |reason| End::Unsupported { cursor, reason };
+
+        // Parameters that depend on a variable are decoded as zero and read separately, since only
+        // the opcode and modes have to be known to decode the instruction.
+        let raw = self
+            .get(state, cursor)
+            .as_constant()
+            .ok_or_else(|| unsupported("Instruction that depends on a variable"))?;
+        let mut words = vec![raw];
+        words.extend(
+            (1..4).map(|offset| self.get(state, cursor + offset).as_constant().unwrap_or(0)),
+        );
+        let instruction = Instruction::decode(cursor, &words).map_err(End::Error)?;
+
+        match instruction.opcode {
+            Opcode::Add | Opcode::Mul => {
+                let (a, b) = (
+                    self.read(state, &instruction, 0)?,
+                    self.read(state, &instruction, 1)?,
+                );
+                let value = match instruction.opcode {
+                    Opcode::Add => a.combine(&b, i64::wrapping_add, Linear::add),
+                    _ => a.combine(&b, i64::wrapping_mul, Linear::mul),
+                };
+                self.write(state, &instruction, value)?;
+            }
+            Opcode::Input => match self.input.get(state.inputs_read) {
+                Some(value) => {
+                    self.write(state, &instruction, value.clone())?;
+                    state.inputs_read += 1;
+                }
+                None => return Err(End::Stopped(Response::InputRequired)),
+            },
+            Opcode::Output => {
+                let value = self.read(state, &instruction, 0)?;
+                state.output.push(value);
+            }
+            Opcode::JumpIfTrue | Opcode::JumpIfFalse => {
+                let condition = match self.read(state, &instruction, 0)? {
+                    Value::Linear(linear) => linear,
+                    Value::Unknown => return Err(unsupported("Jump on an unknown value")),
+                };
+                let relation = match instruction.opcode {
+                    Opcode::JumpIfTrue => Relation::Ne,
+                    _ => Relation::Eq,
+                };
+
+                let constraint = Constraint::new(condition, relation, Linear::constant(0));
+                if self.holds(state, pending, constraint) {
+                    let target = self.read(state, &instruction, 1)?;
+                    state.cursor = match target.as_constant() {
+                        Some(target) => self.address(&instruction, target)?,
+                        None => {
+                            return Err(unsupported(
+                                "Jump to an address that depends on a variable",
+                            ))
+                        }
+                    };
+                    return Ok(());
+                }
+            }
+            Opcode::LessThan | Opcode::Equals => {
+                let (a, b) = match (
+                    self.read(state, &instruction, 0)?,
+                    self.read(state, &instruction, 1)?,
+                ) {
+                    (Value::Linear(a), Value::Linear(b)) => (a, b),
+                    _ => return Err(unsupported("Comparison of an unknown value")),
+                };
+                let relation = match instruction.opcode {
+                    Opcode::LessThan => Relation::Lt,
+                    _ => Relation::Eq,
+                };
+
+                let holds = self.holds(state, pending, Constraint::new(a, relation, b));
+                self.write(state, &instruction, Value::constant(holds as i64))?;
+            }
+            Opcode::AdjustRelativeBase => {
+                let offset = self
+                    .read(state, &instruction, 0)?
+                    .as_constant()
+                    .ok_or_else(|| unsupported("Relative base that depends on a variable"))?;
+                state.relative_base = state.relative_base.wrapping_add(offset);
+            }
+            Opcode::Halt => return Err(End::Stopped(Response::Terminated)),
+        }
+
+        state.cursor += instruction.size();
+        Ok(())
+    }
+
+    /// Whether `state` continues as if `constraint` holds. If both outcomes are feasible, a copy
+    /// of the state taking the other one is added to `pending`.
+    fn holds(&self, state: &mut State, pending: &mut Vec<State>, constraint: Constraint) -> bool {
+        if let Some(holds) = state.assume.take().or_else(|| constraint.evaluate()) {
+            return holds;
+        }
+
+        let feasible = |constraint: &Constraint| {
+            let mut constraints = state.constraints.clone();
+            constraints.push(constraint.clone());
+            Solver::new(&constraints).solve(&self.domains) != Solution::Infeasible
+        };
+
+        let negation = constraint.negate();
+        match (feasible(&constraint), feasible(&negation)) {
+            (true, true) => {
+                let mut other = state.clone();
+                other.constraints.push(negation);
+                other.assume = Some(false);
+                other.steps -= 1;
+                pending.push(other);
+
+                state.constraints.push(constraint);
+                true
+            }
+            (false, true) => false,
+            _ => true,
+        }
+    }
+
+    /// The value of parameter `index`, read according to its mode.
+    fn read(&self, state: &State, instruction: &Instruction, index: usize) -> Result<Value, End> {
+        let word = self.get(state, instruction.address + 1 + index);
+        let param = instruction.params()[index];
+
+        if let Param::Immediate(_) = param {
+            return Ok(word);
+        }
+
+        Ok(
+            match self.param_address(state, instruction, index, &word)? {
+                Some(address) => self.get(state, address),
+                None => Value::Unknown,
+            },
+        )
+    }
+
+    fn write(&self, state: &mut State, instruction: &Instruction, value: Value) -> Result<(), End> {
+        let index = instruction.opcode.output_param().unwrap();
+        let word = self.get(state, instruction.address + 1 + index);
+
+        match self.param_address(state, instruction, index, &word)? {
+            Some(address) => {
+                state.memory.insert(address, value);
+                Ok(())
+            }
+            None => Err(End::Unsupported {
+                cursor: instruction.address,
+                reason: "Write to an address that depends on a variable",
+            }),
+        }
+    }
+
+    /// The address a position or relative parameter refers to, unless it depends on a variable.
+    fn param_address(
+        &self,
+        state: &State,
+        instruction: &Instruction,
+        index: usize,
+        word: &Value,
+    ) -> Result<Option<usize>, End> {
+        let offset = match instruction.params()[index] {
+            Param::Relative(_) => state.relative_base,
+            _ => 0,
+        };
+
+        match word.as_constant() {
+            Some(word) => self
+                .address(instruction, word.wrapping_add(offset))
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn address(&self, instruction: &Instruction, address: i64) -> Result<usize, End> {
+        if address < 0 {
+            Err(End::Error(VmError::NegativeAddress {
+                cursor: instruction.address,
+                instruction: instruction.raw,
+                address,
+            }))
+        } else {
+            Ok(address as usize)
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Solution {
+    Found(Vec<i64>),
+    Infeasible,
+
+    /// The solver ran out of budget.
+    Unknown,
+}
+
+/// Constraints rewritten as `constant + sum of coefficient * variable` compared with zero.
+struct Solver {
+    /// Each must be at most zero.
+    at_most_zero: Vec<(i128, Vec<(Var, i128)>)>,
+
+    /// Each must not be zero.
+    non_zero: Vec<(i128, Vec<(Var, i128)>)>,
+
+    budget: usize,
+}
+
+impl Solver {
+    fn new(constraints: &[Constraint]) -> Self {
+        let mut solver = Self {
+            at_most_zero: Vec::new(),
+            non_zero: Vec::new(),
+            budget: SOLVER_BUDGET,
+        };
+
+        for constraint in constraints {
+            let (constant, terms) = constraint.left.difference(&constraint.right);
+            let negated = || {
+                let terms = terms.iter().map(|&(var, c)| (var, -c)).collect();
+                (-constant, terms)
+            };
+
+            match constraint.relation {
+                Relation::Eq => {
+                    solver.at_most_zero.push(negated());
+                    solver.at_most_zero.push((constant, terms));
+                }
+                Relation::Ne => solver.non_zero.push((constant, terms)),
+                Relation::Lt => solver.at_most_zero.push((constant + 1, terms)),
+                Relation::Ge => solver.at_most_zero.push(negated()),
+            }
+        }
+
+        solver
+    }
+
+    fn solve(&mut self, domains: &[Range<i64>]) -> Solution {
+        if domains.iter().any(|domain| domain.is_empty()) {
+            return Solution::Infeasible;
+        }
+
+        self.search(
+            domains
+                .iter()
+                .map(|domain| (domain.start as i128, domain.end as i128 - 1))
+                .collect(),
+        )
+    }
+
+    /// Look for a solution with each variable within its inclusive bounds.
+    fn search(&mut self, mut bounds: Vec<(i128, i128)>) -> Solution {
+        if self.budget == 0 {
+            return Solution::Unknown;
+        }
+        self.budget -= 1;
+
+        if !self.propagate(&mut bounds) {
+            return Solution::Infeasible;
+        }
+
+        let split = self
+            .at_most_zero
+            .iter()
+            .chain(self.non_zero.iter())
+            .flat_map(|(_, terms)| terms.iter().map(|&(var, _)| var))
+            .find(|&var| bounds[var].0 < bounds[var].1);
+
+        let var = match split {
+            Some(var) => var,
+            None => return Solution::Found(bounds.iter().map(|&(low, _)| low as i64).collect()),
+        };
+
+        let (low, high) = bounds[var];
+        let middle = low + (high - low) / 2;
+        let mut upper = bounds.clone();
+        bounds[var].1 = middle;
+        upper[var].0 = middle + 1;
+
+        let lower = self.search(bounds);
+        if let Solution::Found(_) = lower {
+            return lower;
+        }
+
+        match self.search(upper) {
+            Solution::Infeasible => lower,
+            upper => upper,
+        }
+    }
+
+    /// Tighten the bounds to those the constraints imply, returning false if they can't be met.
+    fn propagate(&self, bounds: &mut [(i128, i128)]) -> bool {
+        for _ in 0..PROPAGATION_ROUNDS {
+            let mut changed = false;
+
+            for (constant, terms) in self.at_most_zero.iter() {
+                let least = terms.iter().fold(*constant, |sum, &(var, c)| {
+                    let (low, high) = bounds[var];
+                    sum.saturating_add(c.saturating_mul(if c > 0 { low } else { high }))
+                });
+                if least > 0 {
+                    return false;
+                }
+
+                // With the other terms at their least, c * x <= limit.
+                for &(var, c) in terms {
+                    let (low, high) = bounds[var];
+                    let limit =
+                        -least.saturating_sub(c.saturating_mul(if c > 0 { low } else { high }));
+
+                    let tightened = if c > 0 {
+                        (low, high.min(floor_div(limit, c)))
+                    } else {
+                        (low.max(ceil_div(limit, c)), high)
+                    };
+                    if tightened.0 > tightened.1 {
+                        return false;
+                    }
+                    if tightened != bounds[var] {
+                        bounds[var] = tightened;
+                        changed = true;
+                    }
+                }
+            }
+
+            for (constant, terms) in self.non_zero.iter() {
+                let (fixed, free): (Vec<_>, Vec<_>) = terms
+                    .iter()
+                    .partition(|&&(var, _)| bounds[var].0 == bounds[var].1);
+                let rest = fixed.iter().fold(*constant, |sum, &&(var, c)| {
+                    sum.saturating_add(c.saturating_mul(bounds[var].0))
+                });
+
+                match free[..] {
+                    [] if rest == 0 => return false,
+                    [&(var, c)] if rest % c == 0 => {
+                        // Rule out the one value of the free variable that makes it zero, if that
+                        // value is at one end of its range.
+                        let excluded = -rest / c;
+                        if excluded == bounds[var].0 {
+                            bounds[var].0 += 1;
+                            changed = true;
+                        } else if excluded == bounds[var].1 {
+                            bounds[var].1 -= 1;
+                            changed = true;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+
+            if !changed {
+                break;
+            }
+        }
+
+        true
+    }
+}
+
+fn floor_div(a: i128, b: i128) -> i128 {
+    let quotient = a / b;
+    if a % b != 0 && (a < 0) != (b < 0) {
+        quotient - 1
+    } else {
+        quotient
+    }
+}
+
+fn ceil_div(a: i128, b: i128) -> i128 {
+    let quotient = a / b;
+    if a % b != 0 && (a < 0) == (b < 0) {
+        quotient + 1
+    } else {
+        quotient
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn branches() {
+        // Reads x and y, then outputs 1 if 2x + y < 10, 2 if x == 7, and 3 otherwise.
+        let program = Intcode::new(vec![
+            3, 100, 3, 101, 1002, 100, 2, 102, 1, 102, 101, 102, 1007, 102, 10, 103, 1005, 103, 35,
+            1008, 100, 7, 103, 1005, 103, 31, 104, 3, 99, 0, 0, 104, 2, 99, 0, 104, 1, 99,
+        ]);
+        let mut explorer = Explorer::new(program);
+        explorer.input_symbolic("x", 0..100);
+        explorer.input_symbolic("y", -5..5);
+
+        let paths = explorer.explore();
+        assert_eq!(
+            "Path 1: Terminated after 8 steps
+  2*x + y < 10
+  output 1
+  for example x = 0, y = -5
+
+Path 2: Terminated after 10 steps
+  2*x + y >= 10
+  x == 7
+  output 2
+  for example x = 7, y = -4
+
+Path 3: Terminated after 10 steps
+  2*x + y >= 10
+  x != 7
+  output 3
+  for example x = 3, y = 4
+",
+            explorer.report(&paths)
+        );
+
+        let goal = Constraint::new(Linear::var(1), Relation::Eq, Linear::constant(2));
+        assert_eq!(Some(vec![3, 4]), explorer.solve(&paths[2], &[]));
+        assert_eq!(
+            Some(vec![4, 2]),
+            explorer.solve(&paths[2], std::slice::from_ref(&goal))
+        );
+        assert_eq!(None, explorer.solve(&paths[1], &[goal.negate(), goal]));
+    }
+
+    #[test]
+    fn unsupported() {
+        // Reads x, then jumps to x.
+        let mut explorer = Explorer::new(Intcode::new(vec![3, 5, 105, 1, 5, 0]));
+        explorer.input_symbolic("x", 0..10);
+
+        let paths = explorer.explore();
+        assert_eq!(1, paths.len());
+        assert_eq!(
+            End::Unsupported {
+                cursor: 2,
+                reason: "Jump to an address that depends on a variable"
+            },
+            paths[0].end
+        );
+        assert_eq!("x", explorer.show(&paths[0].get(5)));
+    }
+
+    #[test]
+    fn solver() {
+        let x = Linear::var(0);
+        let y = Linear::var(1);
+        let sum = x.add(&y.mul(&Linear::constant(3)).unwrap()).unwrap();
+        let constraints = vec![
+            Constraint::new(sum.clone(), Relation::Eq, Linear::constant(1000)),
+            Constraint::new(x.clone(), Relation::Ne, Linear::constant(1)),
+            Constraint::new(y.clone(), Relation::Lt, x.clone()),
+        ];
+
+        assert_eq!(
+            Solution::Found(vec![253, 249]),
+            Solver::new(&constraints).solve(&[0..1000, 0..1000])
+        );
+        assert_eq!(
+            Solution::Infeasible,
+            Solver::new(&constraints).solve(&[0..250, 0..1000])
+        );
+    }
+}
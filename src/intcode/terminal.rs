@@ -0,0 +1,251 @@
+use std::fmt;
+use std::io::{BufRead, Write};
+use std::mem;
+use std::time::{Duration, Instant};
+
+use super::{Intcode, Response};
+
+/// What a program printed between two reads of its input.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Screen {
+    /// Complete lines of text, without their newlines.
+    pub lines: Vec<String>,
+
+    /// Values outside the ASCII range, such as the answers days 17 and 21 print after their text.
+    pub values: Vec<i64>,
+
+    /// The line printed just before the program asked for input, such as `Command?`.
+    pub prompt: Option<String>,
+
+    pub halted: bool,
+}
+
+impl Screen {
+    /// The lines of text, each followed by a newline.
+    pub fn text(&self) -> String {
+        self.lines
+            .iter()
+            .map(|line| format!("{}\n", line))
+            .collect()
+    }
+}
+
+/// ```text
+/// == Hull Breach ==
+/// ...
+/// 19348359
+/// Command?
+/// ```
+impl fmt::Display for Screen {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.text())?;
+        for value in self.values.iter() {
+            writeln!(f, "{}", value)?;
+        }
+        if let Some(prompt) = &self.prompt {
+            writeln!(f, "{}", prompt)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A terminal for the programs that talk in ASCII: days 17, 21 and 25. Input is sent a line at a
+/// time, and output is split into lines of text and the values outside the ASCII range, with the
+/// last line printed before the program reads input taken as its prompt.
+///
+/// The terminal can be driven from code with `send` and `script`, or attached to any reader and
+/// writer, such as stdin and stdout, with `interact`.
+pub struct Terminal {
+    pub intcode: Intcode,
+
+    /// Text printed since the last newline.
+    partial: String,
+    timeout: Option<Duration>,
+}
+
+impl Terminal {
+    pub fn new(intcode: Intcode) -> Self {
+        Self {
+            intcode,
+            partial: String::new(),
+            timeout: None,
+        }
+    }
+
+    /// Give up with an error if the program runs for longer than `timeout` without asking for
+    /// input.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Run the program until it asks for input or halts.
+    pub fn run(&mut self) -> Result<Screen, String> {
+        let response = match self.timeout {
+            Some(timeout) => match self.intcode.run_until(Instant::now() + timeout)? {
+                Response::BudgetExhausted => {
+                    return Err(format!("Still running after {:?}.", timeout))
+                }
+                response => response,
+            },
+            None => self.intcode.run()?,
+        };
+
+        let mut screen = Screen::default();
+        for value in self.intcode.output.drain(..) {
+            match value {
+                10 => screen.lines.push(mem::take(&mut self.partial)),
+                0..=127 => self.partial.push(value as u8 as char),
+                _ => screen.values.push(value),
+            }
+        }
+
+        if response == Response::InputRequired {
+            screen.prompt = if self.partial.is_empty() {
+                screen.lines.pop()
+            } else {
+                Some(mem::take(&mut self.partial))
+            };
+        } else {
+            screen.halted = true;
+            if !self.partial.is_empty() {
+                screen.lines.push(mem::take(&mut self.partial));
+            }
+        }
+
+        Ok(screen)
+    }
+
+    /// Queue `text` and a newline as input, without running the program.
+    pub fn queue(&mut self, text: &str) {
+        self.intcode.input_str(text);
+        self.intcode.input.push_back('\n' as i64);
+    }
+
+    /// Send `text` and a newline, then run the program until it asks for more input or halts.
+    /// The text may hold several lines.
+    pub fn send(&mut self, text: &str) -> Result<Screen, String> {
+        self.queue(text);
+        self.run()
+    }
+
+    /// Run the program, failing unless it stops to ask for input with `prompt`.
+    pub fn expect(&mut self, prompt: &str) -> Result<Screen, String> {
+        let screen = self.run()?;
+        if screen.prompt.as_deref() != Some(prompt) {
+            return Err(format!(
+                "Expected the prompt {:?}, got {:?}",
+                prompt, screen.prompt
+            ));
+        }
+
+        Ok(screen)
+    }
+
+    /// Answer each prompt in turn with its text, then run the program until it asks for input
+    /// again or halts.
+    pub fn script(&mut self, steps: &[(&str, &str)]) -> Result<Screen, String> {
+        for (prompt, text) in steps {
+            self.expect(prompt)?;
+            self.queue(text);
+        }
+
+        self.run()
+    }
+
+    /// Print what the program outputs to `output`, and feed it the lines read from `input` until
+    /// either the program halts or the input runs out.
+    pub fn interact(
+        &mut self,
+        mut input: impl BufRead,
+        mut output: impl Write,
+    ) -> Result<(), String> {
+        loop {
+            let screen = self.run()?;
+            write!(output, "{}", screen)
+                .and_then(|_| output.flush())
+                .map_err(|e| e.to_string())?;
+            if screen.halted {
+                return Ok(());
+            }
+
+            let mut line = String::new();
+            if input.read_line(&mut line).map_err(|e| e.to_string())? == 0 {
+                return Ok(());
+            }
+            self.queue(line.trim_end_matches(&['\r', '\n'][..]));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::asm::assemble;
+    use super::*;
+
+    /// Asks "Ok?", echoes a line of input, and prints 1000.
+    fn echo() -> Intcode {
+        Intcode::new(
+            assemble(
+                "
+                    OUT #79
+                    OUT #107
+                    OUT #63
+                    OUT #10
+                L_READ:
+                    IN -> [L_CHAR]
+                    EQ [L_CHAR], #10 -> [L_DONE]
+                    JT [L_DONE], #L_END
+                    OUT [L_CHAR]
+                    JT #1, #L_READ
+                L_END:
+                    OUT #10
+                    OUT #1000
+                    HLT
+                L_CHAR:
+                    DATA 0
+                L_DONE:
+                    DATA 0
+                ",
+            )
+            .unwrap(),
+        )
+    }
+
+    #[test]
+    fn screens() {
+        let mut terminal = Terminal::new(echo());
+        assert_eq!(
+            Screen {
+                prompt: Some("Ok?".to_string()),
+                ..Screen::default()
+            },
+            terminal.run().unwrap()
+        );
+        assert_eq!(
+            Screen {
+                lines: vec!["yes".to_string()],
+                values: vec![1000],
+                prompt: None,
+                halted: true,
+            },
+            terminal.send("yes").unwrap()
+        );
+
+        assert_eq!(
+            Err("Expected the prompt \"Name?\", got Some(\"Ok?\")".to_string()),
+            Terminal::new(echo()).script(&[("Name?", "no")])
+        );
+    }
+
+    #[test]
+    fn interact() {
+        let mut output = Vec::new();
+        Terminal::new(echo())
+            .interact(&b"yes\nignored\n"[..], &mut output)
+            .unwrap();
+
+        assert_eq!("Ok?\nyes\n1000\n", String::from_utf8(output).unwrap());
+    }
+}
@@ -0,0 +1,756 @@
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::str;
+use std::sync::{Arc, Mutex};
+
+use super::io::{IntcodeInput, IntcodeOutput};
+use super::{Intcode, Opcode, Param, Response, VmError};
+
+/// Binary traces start with this header, followed by a version byte.
+const MAGIC: &[u8; 4] = b"ICTR";
+const VERSION: u8 = 1;
+
+const HAS_WRITE: u8 = 1;
+const HAS_INPUT: u8 = 2;
+const HAS_OUTPUT: u8 = 4;
+const CUSTOM: u8 = 8;
+
+/// One executed instruction.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Event {
+    pub step: u64,
+    pub cursor: usize,
+    pub instruction: i64,
+
+    /// The values read by the instruction, after resolving position and relative parameters.
+    pub operands: Vec<i64>,
+
+    /// The address and new value of any memory written.
+    pub write: Option<(usize, i64)>,
+    pub input: Option<i64>,
+    pub output: Option<i64>,
+
+    /// Whether a `Dialect` defined the instruction. What it read and wrote is up to its handler, so
+    /// only its step, cursor and raw value are recorded.
+    pub custom: bool,
+}
+
+impl Event {
+    pub fn opcode(&self) -> Option<Opcode> {
+        if self.custom {
+            return None;
+        }
+        Opcode::try_from(self.instruction % 100).ok()
+    }
+
+    pub fn mnemonic(&self) -> &'static str {
+        match self.opcode() {
+            Some(opcode) => opcode.mnemonic(),
+            None if self.custom => "CUSTOM",
+            None => "???",
+        }
+    }
+
+    fn write_binary(&self, previous_step: u64, writer: &mut impl Write) -> io::Result<()> {
+        let flags = if self.write.is_some() { HAS_WRITE } else { 0 }
+            | if self.input.is_some() { HAS_INPUT } else { 0 }
+            | if self.output.is_some() { HAS_OUTPUT } else { 0 }
+            | if self.custom { CUSTOM } else { 0 };
+
+        let mut buffer = vec![flags];
+        write_varint(&mut buffer, self.step.wrapping_sub(previous_step));
+        write_varint(&mut buffer, self.cursor as u64);
+        write_signed(&mut buffer, self.instruction);
+        write_varint(&mut buffer, self.operands.len() as u64);
+        self.operands
+            .iter()
+            .for_each(|&operand| write_signed(&mut buffer, operand));
+        if let Some((address, value)) = self.write {
+            write_varint(&mut buffer, address as u64);
+            write_signed(&mut buffer, value);
+        }
+        self.input
+            .iter()
+            .for_each(|&v| write_signed(&mut buffer, v));
+        self.output
+            .iter()
+            .for_each(|&v| write_signed(&mut buffer, v));
+
+        writer.write_all(&buffer)
+    }
+
+    fn read_binary(previous_step: u64, reader: &mut impl Read) -> Result<Option<Self>, String> {
+        let mut flags = [0];
+        if reader.read(&mut flags).map_err(|e| e.to_string())? == 0 {
+            return Ok(None);
+        }
+        let flags = flags[0];
+
+        let step = previous_step.wrapping_add(read_varint(reader)?);
+        let cursor = read_varint(reader)? as usize;
+        let instruction = read_signed(reader)?;
+        let operands = (0..read_varint(reader)?)
+            .map(|_| read_signed(reader))
+            .collect::<Result<_, _>>()?;
+        let write = if flags & HAS_WRITE != 0 {
+            Some((read_varint(reader)? as usize, read_signed(reader)?))
+        } else {
+            None
+        };
+        let input = if flags & HAS_INPUT != 0 {
+            Some(read_signed(reader)?)
+        } else {
+            None
+        };
+        let output = if flags & HAS_OUTPUT != 0 {
+            Some(read_signed(reader)?)
+        } else {
+            None
+        };
+
+        Ok(Some(Event {
+            step,
+            cursor,
+            instruction,
+            operands,
+            write,
+            input,
+            output,
+            custom: flags & CUSTOM != 0,
+        }))
+    }
+}
+
+/// ```text
+/// 17 0004 ADD 1001 args=3,-1 write=12:2
+/// 18 0008 OUT 4 args=2 out=2
+/// ```
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {:04} {} {}",
+            self.step,
+            self.cursor,
+            self.mnemonic(),
+            self.instruction
+        )?;
+
+        if !self.operands.is_empty() {
+            write!(
+                f,
+                " args={}",
+                self.operands
+                    .iter()
+                    .map(|operand| operand.to_string())
+                    .collect::<Vec<String>>()
+                    .join(",")
+            )?;
+        }
+        if let Some((address, value)) = self.write {
+            write!(f, " write={}:{}", address, value)?;
+        }
+        if let Some(input) = self.input {
+            write!(f, " in={}", input)?;
+        }
+        if let Some(output) = self.output {
+            write!(f, " out={}", output)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl str::FromStr for Event {
+    type Err = String;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid trace line: {}", raw);
+        let number = |raw: &str| raw.parse().map_err(|_| invalid());
+        let mut words = raw.split_whitespace();
+
+        let mut event = Event {
+            step: number(words.next().ok_or_else(invalid)?)? as u64,
+            cursor: number(words.next().ok_or_else(invalid)?)? as usize,
+            ..Event::default()
+        };
+        event.custom = words.next().ok_or_else(invalid)? == "CUSTOM";
+        event.instruction = number(words.next().ok_or_else(invalid)?)?;
+
+        for word in words {
+            let mut parts = word.splitn(2, '=');
+            let (key, value) = (parts.next().unwrap(), parts.next().ok_or_else(invalid)?);
+
+            match key {
+                "args" => {
+                    event.operands = value.split(',').map(number).collect::<Result<_, _>>()?
+                }
+                "write" => {
+                    let mut parts = value.splitn(2, ':');
+                    event.write = Some((
+                        number(parts.next().unwrap())? as usize,
+                        number(parts.next().ok_or_else(invalid)?)?,
+                    ));
+                }
+                "in" => event.input = Some(number(value)?),
+                "out" => event.output = Some(number(value)?),
+                _ => return Err(invalid()),
+            }
+        }
+
+        Ok(event)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// One event per line, as produced by `Event`'s `Display` implementation.
+    Text,
+
+    /// A header followed by variable-length encoded events, typically a quarter the size of text.
+    Binary,
+}
+
+/// Writes an `Event` for every instruction executed by the machines it's attached to. Write
+/// failures don't interrupt the program; the first one is reported by `finish`.
+#[derive(Clone)]
+pub struct Tracer {
+    format: Format,
+    sink: Arc<Mutex<Sink>>,
+}
+
+struct Sink {
+    writer: Box<dyn Write + Send>,
+    previous_step: u64,
+    error: Option<String>,
+}
+
+impl Tracer {
+    pub fn new(writer: impl Write + Send + 'static, format: Format) -> Self {
+        let mut sink = Sink {
+            writer: Box::new(writer),
+            previous_step: 0,
+            error: None,
+        };
+
+        if format == Format::Binary {
+            if let Err(e) = sink
+                .writer
+                .write_all(MAGIC)
+                .and_then(|_| sink.writer.write_all(&[VERSION]))
+            {
+                sink.error = Some(e.to_string());
+            }
+        }
+
+        Self {
+            format,
+            sink: Arc::new(Mutex::new(sink)),
+        }
+    }
+
+    pub fn create(path: &str, format: Format) -> Result<Self, String> {
+        let file = File::create(path).map_err(|e| format!("{}: {}", path, e))?;
+        Ok(Self::new(BufWriter::new(file), format))
+    }
+
+    pub fn record(&self, event: &Event) {
+        let mut sink = self.sink.lock().unwrap();
+
+        if sink.error.is_none() {
+            let previous_step = sink.previous_step;
+            let result = match self.format {
+                Format::Text => writeln!(sink.writer, "{}", event),
+                Format::Binary => event.write_binary(previous_step, &mut sink.writer),
+            };
+
+            sink.previous_step = event.step;
+            sink.error = result.err().map(|e| e.to_string());
+        }
+    }
+
+    /// Flush the trace, reporting the first error encountered while writing it.
+    pub fn finish(&self) -> Result<(), String> {
+        let mut sink = self.sink.lock().unwrap();
+
+        if let Some(error) = sink.error.take() {
+            return Err(error);
+        }
+
+        sink.writer.flush().map_err(|e| e.to_string())
+    }
+
+    /// Execute a single instruction, recording what it did.
+    pub(super) fn step<I: IntcodeInput, O: IntcodeOutput>(
+        &self,
+        intcode: &mut Intcode<I, O>,
+    ) -> Result<Option<Response>, VmError> {
+        let (cursor, raw) = (intcode.cursor, intcode.get(intcode.cursor));
+        if intcode.dialect.as_ref().is_some_and(|d| d.defines(raw)) {
+            let result = intcode.execute();
+            if let Ok(None) | Ok(Some(Response::Terminated)) = result {
+                self.record(&Event {
+                    step: intcode.steps,
+                    cursor,
+                    instruction: raw,
+                    custom: true,
+                    ..Event::default()
+                });
+            }
+            return result;
+        }
+
+        let instruction = match intcode.instruction(intcode.cursor) {
+            Ok(instruction) => instruction,
+            Err(_) => return intcode.execute(),
+        };
+
+        let resolve = |param: &Param| match *param {
+            Param::Position(address) => intcode.get(address.max(0) as usize),
+            Param::Immediate(value) => value,
+            Param::Relative(offset) => {
+                intcode.get(offset.wrapping_add(intcode.relative_base).max(0) as usize)
+            }
+        };
+        let operands: Vec<i64> = instruction.inputs().iter().map(resolve).collect();
+        let write_address = instruction.output().map(|param| match param {
+            Param::Relative(offset) => offset.wrapping_add(intcode.relative_base),
+            param => param.value(),
+        });
+
+        let result = intcode.execute();
+
+        if let Ok(None) | Ok(Some(Response::Terminated)) = result {
+            let write =
+                write_address.map(|address| (address as usize, intcode.get(address as usize)));
+
+            self.record(&Event {
+                step: intcode.steps,
+                cursor,
+                instruction: instruction.raw,
+                input: write
+                    .map(|(_, value)| value)
+                    .filter(|_| instruction.opcode == Opcode::Input),
+                output: operands
+                    .first()
+                    .copied()
+                    .filter(|_| instruction.opcode == Opcode::Output),
+                operands,
+                write,
+                custom: false,
+            });
+        }
+
+        result
+    }
+}
+
+impl fmt::Debug for Tracer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Tracer({:?})", self.format)
+    }
+}
+
+/// Reads events back from a trace in either format.
+pub struct TraceReader<R: BufRead> {
+    reader: R,
+    format: Format,
+    previous_step: u64,
+}
+
+impl<R: BufRead> TraceReader<R> {
+    pub fn new(mut reader: R) -> Result<Self, String> {
+        let format = if reader
+            .fill_buf()
+            .map_err(|e| e.to_string())?
+            .starts_with(MAGIC)
+        {
+            let mut header = [0; 5];
+            reader.read_exact(&mut header).map_err(|e| e.to_string())?;
+            if header[4] != VERSION {
+                return Err(format!("Unsupported trace version {}", header[4]));
+            }
+            Format::Binary
+        } else {
+            Format::Text
+        };
+
+        Ok(Self {
+            reader,
+            format,
+            previous_step: 0,
+        })
+    }
+}
+
+impl TraceReader<BufReader<File>> {
+    pub fn open(path: &str) -> Result<Self, String> {
+        Self::new(BufReader::new(
+            File::open(path).map_err(|e| format!("{}: {}", path, e))?,
+        ))
+    }
+}
+
+impl<R: BufRead> Iterator for TraceReader<R> {
+    type Item = Result<Event, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.format {
+            Format::Text => {
+                let mut line = String::new();
+                match self.reader.read_line(&mut line) {
+                    Ok(0) => None,
+                    Ok(_) => Some(line.trim_end().parse()),
+                    Err(e) => Some(Err(e.to_string())),
+                }
+            }
+            Format::Binary => match Event::read_binary(self.previous_step, &mut self.reader) {
+                Ok(Some(event)) => {
+                    self.previous_step = event.step;
+                    Some(Ok(event))
+                }
+                Ok(None) => None,
+                Err(e) => Some(Err(e)),
+            },
+        }
+    }
+}
+
+/// Criteria for selecting events from a trace. Unset fields match everything.
+#[derive(Debug, Default)]
+pub struct Filter {
+    pub cursor: Option<usize>,
+    pub opcode: Option<Opcode>,
+    pub from_step: Option<u64>,
+    pub to_step: Option<u64>,
+    pub writes_to: Option<usize>,
+    pub io_only: bool,
+}
+
+impl Filter {
+    /// Parse command line style options, eg. `--at 10 --op ADD --from 100 --to 200 --writes 104
+    /// --io`.
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut filter = Filter::default();
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("Missing value for {}", arg))
+            };
+            let number = |raw: &String| raw.parse().map_err(|_| format!("Invalid number {}", raw));
+
+            match &arg[..] {
+                "--at" => filter.cursor = Some(number(value()?)? as usize),
+                "--op" => {
+                    let mnemonic = value()?;
+                    filter.opcode = Some(
+                        Opcode::ALL
+                            .iter()
+                            .find(|opcode| opcode.mnemonic().eq_ignore_ascii_case(mnemonic))
+                            .copied()
+                            .ok_or_else(|| format!("Unknown mnemonic {}", mnemonic))?,
+                    );
+                }
+                "--from" => filter.from_step = Some(number(value()?)?),
+                "--to" => filter.to_step = Some(number(value()?)?),
+                "--writes" => filter.writes_to = Some(number(value()?)? as usize),
+                "--io" => filter.io_only = true,
+                _ => return Err(format!("Unknown filter {}", arg)),
+            }
+        }
+
+        Ok(filter)
+    }
+
+    pub fn matches(&self, event: &Event) -> bool {
+        self.cursor.is_none_or(|cursor| event.cursor == cursor)
+            && self
+                .opcode
+                .is_none_or(|opcode| event.opcode() == Some(opcode))
+            && self.from_step.is_none_or(|step| event.step >= step)
+            && self.to_step.is_none_or(|step| event.step <= step)
+            && self
+                .writes_to
+                .is_none_or(|address| event.write.map(|(a, _)| a) == Some(address))
+            && (!self.io_only || event.input.is_some() || event.output.is_some())
+    }
+}
+
+/// Aggregate statistics over a trace.
+#[derive(Debug, Default, PartialEq)]
+pub struct Summary {
+    pub events: u64,
+    pub first_step: Option<u64>,
+    pub last_step: Option<u64>,
+    pub opcodes: BTreeMap<&'static str, u64>,
+    pub addresses: BTreeMap<usize, u64>,
+    pub writes: u64,
+    pub inputs: u64,
+    pub outputs: u64,
+}
+
+impl Summary {
+    pub fn add(&mut self, event: &Event) {
+        self.events += 1;
+        self.first_step.get_or_insert(event.step);
+        self.last_step = Some(event.step);
+        *self.opcodes.entry(event.mnemonic()).or_insert(0) += 1;
+        *self.addresses.entry(event.cursor).or_insert(0) += 1;
+        self.writes += event.write.is_some() as u64;
+        self.inputs += event.input.is_some() as u64;
+        self.outputs += event.output.is_some() as u64;
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{} instructions, steps {} to {}",
+            self.events,
+            self.first_step.unwrap_or(0),
+            self.last_step.unwrap_or(0)
+        )?;
+        writeln!(
+            f,
+            "{} distinct addresses, {} memory writes, {} inputs, {} outputs",
+            self.addresses.len(),
+            self.writes,
+            self.inputs,
+            self.outputs
+        )?;
+
+        let mut opcodes: Vec<(&&str, &u64)> = self.opcodes.iter().collect();
+        opcodes.sort_by(|a, b| b.1.cmp(a.1));
+        for (mnemonic, count) in opcodes {
+            writeln!(f, "{:>5} {}", mnemonic, count)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The index of the first differing event, and the event from each trace. An event is `None` if
+/// its trace ended early.
+pub type Divergence = (u64, Option<Event>, Option<Event>);
+
+/// Find the first point where two traces diverge.
+pub fn diff<A, B>(a: A, b: B) -> Result<Option<Divergence>, String>
+where
+    A: IntoIterator<Item = Result<Event, String>>,
+    B: IntoIterator<Item = Result<Event, String>>,
+{
+    let (mut a, mut b) = (a.into_iter(), b.into_iter());
+    let mut index = 0;
+
+    loop {
+        match (a.next().transpose()?, b.next().transpose()?) {
+            (None, None) => return Ok(None),
+            (x, y) if x != y => return Ok(Some((index, x, y))),
+            _ => index += 1,
+        }
+    }
+}
+
+fn write_varint(buffer: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buffer.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buffer.push(value as u8);
+}
+
+fn write_signed(buffer: &mut Vec<u8>, value: i64) {
+    write_varint(buffer, ((value << 1) ^ (value >> 63)) as u64);
+}
+
+fn read_varint(reader: &mut impl Read) -> Result<u64, String> {
+    let mut value = 0;
+    let mut shift = 0;
+
+    loop {
+        let mut byte = [0];
+        reader
+            .read_exact(&mut byte)
+            .map_err(|_| "Truncated trace.".to_string())?;
+
+        if shift >= 64 {
+            return Err("Invalid varint in trace.".to_string());
+        }
+
+        value |= u64::from(byte[0] & 0x7f) << shift;
+        shift += 7;
+
+        if byte[0] & 0x80 == 0 {
+            break Ok(value);
+        }
+    }
+}
+
+fn read_signed(reader: &mut impl Read) -> Result<i64, String> {
+    let value = read_varint(reader)?;
+    Ok(((value >> 1) as i64) ^ -((value & 1) as i64))
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::dialect::{Action, Dialect, Role};
+    use super::*;
+
+    /// A writer that can be inspected after the tracer has finished with it.
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn trace(format: Format) -> Vec<u8> {
+        let buffer = Shared::default();
+        let tracer = Tracer::new(buffer.clone(), format);
+        let mut intcode = Intcode::new(vec![3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8])
+            .with_tracer(tracer.clone())
+            .with_input(&[8]);
+
+        intcode.run().unwrap();
+        tracer.finish().unwrap();
+
+        let bytes = buffer.0.lock().unwrap().clone();
+        bytes
+    }
+
+    #[test]
+    fn text_trace() {
+        assert_eq!(
+            "1 0000 IN 3 write=9:8 in=8
+2 0002 EQ 8 args=8,8 write=9:1
+3 0006 OUT 4 args=1 out=1
+4 0008 HLT 99
+",
+            String::from_utf8(trace(Format::Text)).unwrap(),
+        );
+    }
+
+    #[test]
+    fn custom_instructions() {
+        let mut dialect = Dialect::extended();
+        dialect
+            .define(1, "NOP", &[Role::Read; 3], |_, _| Ok(Action::Next(vec![])))
+            .unwrap();
+
+        let buffer = Shared::default();
+        let tracer = Tracer::new(buffer.clone(), Format::Text);
+        let mut intcode = Intcode::new(vec![1101, 1, 1, 0, 104, 5, 190, 7])
+            .with_tracer(tracer.clone())
+            .with_dialect(dialect);
+        assert_eq!(Ok(Response::Terminated), intcode.run());
+        tracer.finish().unwrap();
+
+        let text = buffer.0.lock().unwrap().clone();
+        assert_eq!(
+            "1 0000 CUSTOM 1101
+2 0004 OUT 104 args=5 out=5
+3 0006 CUSTOM 190
+",
+            String::from_utf8(text.clone()).unwrap(),
+        );
+
+        let events: Vec<Event> = TraceReader::new(&text[..])
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert!(events[0].custom);
+        assert_eq!(None, events[0].opcode());
+
+        let buffer = Shared::default();
+        let tracer = Tracer::new(buffer.clone(), Format::Binary);
+        events.iter().for_each(|event| tracer.record(event));
+        tracer.finish().unwrap();
+
+        let binary = buffer.0.lock().unwrap().clone();
+        let decoded: Vec<Event> = TraceReader::new(&binary[..])
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(events, decoded);
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let text: Vec<Event> = TraceReader::new(&trace(Format::Text)[..])
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let binary = trace(Format::Binary);
+        assert!(binary.starts_with(MAGIC));
+
+        let events: Vec<Event> = TraceReader::new(&binary[..])
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(4, events.len());
+        assert_eq!(text, events);
+        assert_eq!(
+            Ok(None),
+            diff(text.into_iter().map(Ok), events.into_iter().map(Ok))
+        );
+    }
+
+    #[test]
+    fn filter_and_summary() {
+        let events: Vec<Event> = TraceReader::new(&trace(Format::Text)[..])
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        let filter = Filter::parse(&["--io".to_string()]).unwrap();
+        assert_eq!(
+            vec![0, 6],
+            events
+                .iter()
+                .filter(|e| filter.matches(e))
+                .map(|e| e.cursor)
+                .collect::<Vec<usize>>()
+        );
+
+        let filter = Filter::parse(&["--writes".to_string(), "9".to_string()]).unwrap();
+        assert_eq!(2, events.iter().filter(|e| filter.matches(e)).count());
+
+        let mut summary = Summary::default();
+        events.iter().for_each(|e| summary.add(e));
+        assert_eq!(4, summary.events);
+        assert_eq!(2, summary.writes);
+        assert_eq!(Some(&1), summary.opcodes.get("EQ"));
+    }
+
+    #[test]
+    fn diff_divergence() {
+        let a = vec![Event::default(), Event::default()];
+        let mut b = a.clone();
+        b[1].cursor = 4;
+
+        assert_eq!(
+            Ok(Some((1, Some(Event::default()), Some(b[1].clone())))),
+            diff(a.clone().into_iter().map(Ok), b.into_iter().map(Ok)),
+        );
+        assert_eq!(
+            Ok(Some((2, None, Some(Event::default())))),
+            diff(
+                a.clone().into_iter().map(Ok),
+                a.iter().chain(a.iter().take(1)).cloned().map(Ok)
+            ),
+        );
+    }
+}
@@ -2,7 +2,9 @@ use std::env;
 use std::fs::File;
 use std::io;
 use std::io::prelude::*;
+use std::ops::Range;
 use std::str;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 mod day1;
 mod day10;
@@ -35,13 +37,494 @@ mod map;
 mod math;
 mod maze;
 
+use intcode::Intcode;
+
 fn main() -> Result<(), String> {
-    let puzzle: Puzzle = env::args()
-        .nth(1)
-        .ok_or_else(|| "Missing expected day.part.".to_string())
-        .and_then(|arg| arg.parse())?;
+    let mut args = env::args().skip(1);
+    let command = args
+        .next()
+        .ok_or_else(|| "Missing expected day.part.".to_string())?;
+
+    match &command[..] {
+        "asm" => {
+            let program = intcode::asm::assemble(&read_program(args.next())?)?;
+            println!(
+                "{}",
+                program
+                    .iter()
+                    .map(|word| word.to_string())
+                    .collect::<Vec<String>>()
+                    .join(",")
+            );
+            Ok(())
+        }
+        "bench" => {
+            let mut arg = args.next();
+            let network = arg.as_deref() == Some("network");
+            if network {
+                arg = args.next();
+            }
+
+            let count = match arg {
+                Some(count) => Some(
+                    count
+                        .parse()
+                        .map_err(|_| format!("Invalid number {}", count))?,
+                ),
+                None => None,
+            };
+
+            if network {
+                print!(
+                    "{}",
+                    intcode::bench::network(count.unwrap_or(16) as usize, 3)?
+                );
+            } else {
+                print!("{}", intcode::bench::benchmark(count.unwrap_or(5))?);
+            }
+            Ok(())
+        }
+        "cfg" => {
+            let intcode: Intcode = read_program(args.next())?.parse()?;
+            let cfg = intcode::cfg::analyze(&intcode);
+
+            match args.next().as_deref() {
+                Some("--dot") => print!("{}", cfg.to_dot()),
+                Some(arg) => return Err(format!("Unknown argument {}", arg)),
+                None => print!("{}", cfg),
+            }
+            Ok(())
+        }
+        "compile" => {
+            let intcode: Intcode = read_program(args.next())?.parse()?;
+            print!("{}", intcode::compile::compile(&intcode));
+            Ok(())
+        }
+        "debug" => {
+            let program = args
+                .next()
+                .ok_or_else(|| "Missing expected day or program file.".to_string())?;
+            let intcode: Intcode = read_program(Some(program))?.parse()?;
+            let mut debugger = intcode::debugger::Debugger::new(intcode);
+
+            match (args.next().as_deref(), args.next()) {
+                (None, _) => {}
+                (Some("--history"), Some(megabytes)) => {
+                    let megabytes: usize = megabytes
+                        .parse()
+                        .map_err(|_| format!("Invalid history size {}", megabytes))?;
+                    debugger = debugger.with_history(intcode::history::History::new(
+                        megabytes << 20,
+                        intcode::history::DEFAULT_CHECKPOINT_INTERVAL,
+                    ));
+                }
+                (Some(arg), _) => return Err(format!("Unknown argument {}", arg)),
+            }
+
+            let stdin = io::stdin();
+            debugger.repl(stdin.lock(), io::stdout())
+        }
+        "decompile" => {
+            let intcode: Intcode = read_program(args.next())?.parse()?;
+            print!("{}", intcode::decompile::decompile(&intcode));
+            Ok(())
+        }
+        "diff" => {
+            let mut program = || -> Result<Intcode, String> {
+                let program = args
+                    .next()
+                    .ok_or_else(|| "Missing expected day or program file.".to_string())?;
+                let program = read_program(Some(program))?;
+
+                // Snapshots saved from the debugger, to compare a program after it has run.
+                if program.starts_with("intcode-snapshot") {
+                    Intcode::load(program.as_bytes())
+                } else {
+                    Ok(program.parse()?)
+                }
+            };
+            let (a, b) = (program()?, program()?);
+
+            print!("{}", intcode::diff::diff(&a, &b));
+            Ok(())
+        }
+        "disasm" => {
+            let intcode: Intcode = read_program(args.next())?.parse()?;
+            print!("{}", intcode::disasm::disassemble(&intcode));
+            Ok(())
+        }
+        "explore" => explore(&args.collect::<Vec<String>>()),
+        "fuzz" => {
+            let program = args
+                .next()
+                .ok_or_else(|| "Missing expected day or program file.".to_string())?;
+            let intcode: Intcode = read_program(Some(program))?.parse()?;
+            let mut alphabet = intcode::fuzz::Alphabet::Numbers(-10..10);
+            let mut runs = 10_000;
+            let mut steps = intcode::fuzz::DEFAULT_STEP_LIMIT;
+            let mut out = None;
+            let mut seed = None;
+
+            while let Some(arg) = args.next() {
+                let mut value = || {
+                    args.next()
+                        .ok_or_else(|| format!("Missing value for {}", arg))
+                };
+                match &arg[..] {
+                    "--numbers" => {
+                        alphabet = intcode::fuzz::Alphabet::Numbers(parse_range(&value()?)?);
+                    }
+                    "--lines" => {
+                        let lines = read_program(Some(value()?))?;
+                        alphabet = intcode::fuzz::Alphabet::Lines(
+                            lines.lines().map(|line| line.to_string()).collect(),
+                        );
+                    }
+                    "--runs" => {
+                        let count = value()?;
+                        runs = count
+                            .parse()
+                            .map_err(|_| format!("Invalid number {}", count))?;
+                    }
+                    "--steps" => {
+                        let count = value()?;
+                        steps = count
+                            .parse()
+                            .map_err(|_| format!("Invalid number {}", count))?;
+                    }
+                    "--seed" => {
+                        let number = value()?;
+                        seed = Some(
+                            number
+                                .parse()
+                                .map_err(|_| format!("Invalid number {}", number))?,
+                        );
+                    }
+                    "--out" => out = Some(value()?),
+                    _ => return Err(format!("Unknown argument {}", arg)),
+                }
+            }
+
+            let seed = match seed {
+                Some(seed) => seed,
+                None => SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_err(|e| e.to_string())?
+                    .as_nanos() as u64,
+            };
+            let mut fuzzer =
+                intcode::fuzz::Fuzzer::new(intcode, alphabet, seed)?.with_step_limit(steps);
+            if let Some(out) = out {
+                fuzzer = fuzzer.with_output_dir(&out)?;
+            }
+
+            fuzzer.run(runs)?;
+            print!("{}", fuzzer);
+            Ok(())
+        }
+        "play" => {
+            let program = args
+                .next()
+                .ok_or_else(|| "Missing expected day or program file.".to_string())?;
+            let mut intcode: Intcode = read_program(Some(program))?.parse()?;
+            let mut script = None;
+            let mut record = None;
+
+            while let Some(arg) = args.next() {
+                let value = args
+                    .next()
+                    .ok_or_else(|| format!("Missing value for {}", arg))?;
+                match &arg[..] {
+                    "--script" => {
+                        script = Some(File::open(&value).map_err(|e| format!("{}: {}", value, e))?)
+                    }
+                    "--record" => record = Some(value),
+                    _ => return Err(format!("Unknown argument {}", arg)),
+                }
+            }
+
+            let recorder = intcode::session::Recorder::new();
+            if record.is_some() {
+                intcode = intcode.with_recorder(recorder.clone());
+            }
+
+            let mut terminal = intcode::terminal::Terminal::new(intcode);
+            let stdin = io::stdin();
+            let result = match script {
+                Some(script) => {
+                    terminal.interact(io::BufReader::new(script).chain(stdin.lock()), io::stdout())
+                }
+                None => terminal.interact(stdin.lock(), io::stdout()),
+            };
+
+            if let Some(path) = record {
+                recorder.session().save_file(&path)?;
+            }
+            result
+        }
+        "profile" => {
+            let args: Vec<String> = args.collect();
+            let folded = args.iter().any(|arg| arg == "--folded");
+            let program = args
+                .first()
+                .ok_or_else(|| "Missing expected day or program file.".to_string())?;
+            let input = args[1..]
+                .iter()
+                .filter(|arg| *arg != "--folded")
+                .map(|arg| arg.parse().map_err(|_| format!("Invalid input {}", arg)))
+                .collect::<Result<Vec<i64>, String>>()?;
+
+            let profiler = intcode::profile::Profiler::new();
+            let mut intcode = read_program(Some(program.clone()))?
+                .parse::<Intcode>()?
+                .with_profiler(profiler.clone())
+                .with_input(&input);
+            let response = intcode.run()?;
+
+            if folded {
+                print!("{}", profiler.profile().folded(&intcode));
+            } else {
+                println!("{:?} after {} steps\n", response, intcode.steps);
+                print!("{}", profiler.profile().report(&intcode, 20));
+            }
+            Ok(())
+        }
+        #[cfg(feature = "bigint")]
+        "big" => {
+            let program = args
+                .next()
+                .ok_or_else(|| "Missing expected day or program file.".to_string())?;
+            let input = args
+                .map(|arg| arg.parse().map_err(|_| format!("Invalid input {}", arg)))
+                .collect::<Result<Vec<i64>, String>>()?;
+
+            let intcode: Intcode = read_program(Some(program))?.parse()?;
+            let mut intcode = intcode::big::BigIntcode::from(intcode).with_input(&input);
+            let response = intcode.run()?;
+
+            for value in intcode.output.iter() {
+                println!("{}", value);
+            }
+            println!("{:?} after {} steps", response, intcode.steps);
+            Ok(())
+        }
+        "replay" => {
+            let program = args
+                .next()
+                .ok_or_else(|| "Missing expected day or program file.".to_string())?;
+            let intcode: Intcode = read_program(Some(program))?.parse()?;
+            let path = args
+                .next()
+                .ok_or_else(|| "Missing expected session file.".to_string())?;
+            let session = intcode::session::Session::load_file(&path)?;
+
+            session.replay(intcode)?;
+            println!(
+                "Replayed {} exchanges over {} steps",
+                session.exchanges.len(),
+                session.exchanges.last().map_or(0, |exchange| exchange.step)
+            );
+            Ok(())
+        }
+        "run" => {
+            let program = args
+                .next()
+                .ok_or_else(|| "Missing expected day or program file.".to_string())?;
+            let mut intcode: Intcode = read_program(Some(program))?.parse()?;
+            let mut dialect = None;
+
+            for arg in args {
+                match &arg[..] {
+                    "--checked" => intcode = intcode.with_arithmetic(intcode::Arithmetic::Checked),
+                    "--extended" => {
+                        dialect = Some(intcode::dialect::Dialect::extended());
+                    }
+                    "--host" => {
+                        let seed = SystemTime::now()
+                            .duration_since(UNIX_EPOCH)
+                            .map_err(|e| e.to_string())?
+                            .as_nanos() as u64;
+                        let host = intcode::host::Host::standard(io::stdout(), seed);
+                        match &mut dialect {
+                            Some(dialect) => host.install(dialect, intcode::host::SYSCALL)?,
+                            None => dialect = Some(host.dialect()),
+                        }
+                    }
+                    _ => return Err(format!("Unknown argument {}", arg)),
+                };
+            }
+            if let Some(dialect) = dialect {
+                intcode = intcode.with_dialect(dialect);
+            }
+
+            let response = intcode
+                .with_io(intcode::io::Stdin::default(), intcode::io::Stdout)
+                .run()?;
+
+            println!("{:?}", response);
+            Ok(())
+        }
+        "trace" => trace(&args.collect::<Vec<String>>()),
+        _ => {
+            let puzzle: Puzzle = command.parse()?;
+            puzzle.run().map(|output| println!("{}", output))
+        }
+    }
+}
+
+/// Record, summarize, filter or compare Intcode execution traces.
+fn trace(args: &[String]) -> Result<(), String> {
+    use intcode::trace::{self, Filter, Format, Summary, TraceReader, Tracer};
+
+    let arg = |index: usize| {
+        args.get(index)
+            .ok_or_else(|| "Missing argument, see README.md for usage.".to_string())
+    };
+
+    match &arg(0)?[..] {
+        "record" => {
+            let format = if args.iter().any(|arg| arg == "--binary") {
+                Format::Binary
+            } else {
+                Format::Text
+            };
+            let input = args[3.min(args.len())..]
+                .iter()
+                .filter(|arg| *arg != "--binary")
+                .map(|arg| arg.parse().map_err(|_| format!("Invalid input {}", arg)))
+                .collect::<Result<Vec<i64>, String>>()?;
+
+            let tracer = Tracer::create(arg(2)?, format)?;
+            let mut intcode = read_program(Some(arg(1)?.clone()))?
+                .parse::<Intcode>()?
+                .with_tracer(tracer.clone())
+                .with_input(&input);
+
+            let response = intcode.run();
+            tracer.finish()?;
+            println!("{:?} after {} steps", response?, intcode.steps);
+            println!("Output: {:?}", intcode.output);
+        }
+        "summary" => {
+            let mut summary = Summary::default();
+            for event in TraceReader::open(arg(1)?)? {
+                summary.add(&event?);
+            }
+            print!("{}", summary);
+        }
+        "filter" => {
+            let filter = Filter::parse(&args[2.min(args.len())..])?;
+            for event in TraceReader::open(arg(1)?)? {
+                let event = event?;
+                if filter.matches(&event) {
+                    println!("{}", event);
+                }
+            }
+        }
+        "diff" => match trace::diff(TraceReader::open(arg(1)?)?, TraceReader::open(arg(2)?)?)? {
+            None => println!("Traces are identical."),
+            Some((index, a, b)) => {
+                let show = |event: Option<intcode::trace::Event>| {
+                    event.map_or("(end of trace)".to_string(), |e| e.to_string())
+                };
+                println!("Traces diverge at event {}:", index);
+                println!("< {}", show(a));
+                println!("> {}", show(b));
+            }
+        },
+        command => return Err(format!("Unknown trace command {}", command)),
+    }
+
+    Ok(())
+}
+
+/// Run a program symbolically and list the paths through it, given the memory and input to treat
+/// as variables.
+fn explore(args: &[String]) -> Result<(), String> {
+    let program = args
+        .first()
+        .ok_or_else(|| "Missing expected day or program file.".to_string())?;
+    let mut explorer =
+        intcode::symbolic::Explorer::new(read_program(Some(program.clone()))?.parse()?);
+
+    let mut args = args[1..].iter();
+    while let Some(arg) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| format!("Missing value for {}", arg))?;
+        // A variable is written as name:low..high.
+        let variable = |value: &str| {
+            value
+                .split_once(':')
+                .ok_or_else(|| format!("Invalid variable {}", value))
+                .and_then(|(name, range)| Ok((name.to_string(), parse_range(range)?)))
+        };
+
+        match &arg[..] {
+            "--set" => {
+                let (address, assigned) = value
+                    .split_once('=')
+                    .ok_or_else(|| format!("Invalid assignment {}", value))?;
+                let (name, range) = variable(assigned)?;
+                explorer.set_symbolic(parse_number(address)?, &name, range);
+            }
+            "--input" if value.contains(':') => {
+                let (name, range) = variable(value)?;
+                explorer.input_symbolic(&name, range);
+            }
+            "--input" => explorer.input(parse_number(value)?),
+            "--text" => explorer.input_str(&value.replace("\\n", "\n")),
+            "--steps" => explorer = explorer.with_step_limit(parse_number(value)?),
+            "--paths" => explorer = explorer.with_path_limit(parse_number(value)?),
+            _ => return Err(format!("Unknown argument {}", arg)),
+        }
+    }
+
+    print!("{}", explorer.report(&explorer.explore()));
+    Ok(())
+}
+
+/// Parse a number of whatever type an argument needs, such as an address or a count.
+fn parse_number<T: str::FromStr>(value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid number {}", value))
+}
+
+/// Parse a range of numbers written as `low..high`.
+fn parse_range(range: &str) -> Result<Range<i64>, String> {
+    range
+        .split_once("..")
+        .and_then(|(low, high)| Some(low.parse().ok()?..high.parse().ok()?))
+        .ok_or_else(|| format!("Invalid range {}", range))
+}
+
+/// Read an Intcode program for one of the tool subcommands. The argument may be a day number, in
+/// which case that day's puzzle input is used, or a path to a file. Without an argument, the
+/// program is read from stdin.
+fn read_program(arg: Option<String>) -> Result<String, String> {
+    let mut buffer = String::new();
+
+    match arg {
+        Some(arg) => {
+            let path = match arg.parse::<u8>() {
+                Ok(day) => format!("src/day{}/input.txt", day),
+                Err(_) => arg,
+            };
+
+            File::open(&path)
+                .map_err(|e| format!("{}: {}", path, e))?
+                .read_to_string(&mut buffer)
+                .map_err(|e| format!("{}", e))?;
+        }
+        None => {
+            io::stdin()
+                .read_to_string(&mut buffer)
+                .map_err(|e| format!("{}", e))?;
+        }
+    }
 
-    puzzle.run().map(|output| println!("{}", output))
+    Ok(buffer)
 }
 
 struct Puzzle {